@@ -0,0 +1,89 @@
+mod utils;
+
+use log::*;
+use std::os::unix::io::AsRawFd;
+
+use tapip_rs::iface::{Config, Interface, SocketSet};
+use tapip_rs::phy::wait as phy_wait;
+use tapip_rs::phy::{Device, Medium};
+use tapip_rs::socket::dhcpv4;
+use tapip_rs::time::{Duration, Instant};
+use tapip_rs::wire::{EthernetAddress, IpCidr, Ipv4Cidr};
+
+fn main() {
+    utils::setup_logging("");
+
+    let (mut opts, mut free) = utils::create_options();
+    utils::add_tuntap_options(&mut opts, &mut free);
+    utils::add_middleware_options(&mut opts, &mut free);
+
+    let mut matches = utils::parse_options(&opts, free);
+    let mut device = utils::parse_tuntap_options(&mut matches);
+    let fd = device.as_raw_fd();
+
+    // Create interface
+    let mut config = match device.capabilities().medium {
+        Medium::Ethernet => {
+            Config::new(EthernetAddress([0x02, 0x00, 0x00, 0x00, 0x00, 0x01]).into())
+        }
+    };
+    config.random_seed = rand::random();
+    let mut iface = Interface::new(config, &mut device, Instant::now());
+
+    // Create sockets
+    let mut dhcp_socket = dhcpv4::Socket::new();
+
+    // Set a ridiculously short max lease time to show DHCP renews work properly.
+    // This will cause the DHCP client to start renewing after 5 seconds, and give up the
+    // lease after 10 seconds if renew hasn't succeeded.
+    // IMPORTANT: This should be removed in production.
+    dhcp_socket.set_max_lease_duration(Some(Duration::from_secs(10)));
+
+    let mut sockets = SocketSet::new(vec![]);
+    let dhcp_handle = sockets.add(dhcp_socket);
+
+    loop {
+        let timestamp = Instant::now();
+        iface.poll(timestamp, &mut device, &mut sockets);
+
+        let event = sockets.get_mut::<dhcpv4::Socket>(dhcp_handle).poll();
+        match event {
+            None => {}
+            Some(dhcpv4::Event::Configured(config)) => {
+                debug!("DHCP config acquired!");
+
+                debug!("IP address:      {}", config.address);
+                set_ipv4_addr(&mut iface, config.address);
+
+                if let Some(router) = config.router {
+                    debug!("Default gateway: {}", router);
+                    iface.routes_mut().add_default_ipv4_route(router).unwrap();
+                } else {
+                    debug!("Default gateway: None");
+                    iface.routes_mut().remove_default_ipv4_route();
+                }
+
+                for (i, s) in config.dns_servers.iter().enumerate() {
+                    debug!("DNS server {}:    {}", i, s);
+                }
+            }
+            Some(dhcpv4::Event::Deconfigured) => {
+                debug!("DHCP lost config!");
+                iface.update_ip_addrs(|addrs| addrs.clear()).unwrap();
+                iface.routes_mut().remove_default_ipv4_route();
+            }
+        }
+
+        phy_wait(fd, iface.poll_delay(timestamp, &sockets)).expect("wait error");
+    }
+}
+
+/// Clear any existing IP addresses & add the new one
+fn set_ipv4_addr(iface: &mut Interface, cidr: Ipv4Cidr) {
+    iface
+        .update_ip_addrs(|addrs| {
+            addrs.clear();
+            addrs.push(IpCidr::Ipv4(cidr));
+        })
+        .unwrap();
+}
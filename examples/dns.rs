@@ -0,0 +1,71 @@
+mod utils;
+
+use std::os::unix::io::AsRawFd;
+use tapip_rs::iface::{Config, Interface, SocketSet, StaticConfig};
+use tapip_rs::phy::Device;
+use tapip_rs::phy::{wait as phy_wait, Medium};
+use tapip_rs::socket::dns::{self, GetQueryResultError};
+use tapip_rs::time::Instant;
+use tapip_rs::wire::{DnsQueryType, EthernetAddress, Ipv4Address};
+
+fn main() {
+    utils::setup_logging("warn");
+
+    let (mut opts, mut free) = utils::create_options();
+    utils::add_tuntap_options(&mut opts, &mut free);
+    utils::add_middleware_options(&mut opts, &mut free);
+    free.push("ADDRESS");
+
+    let mut matches = utils::parse_options(&opts, free);
+    let mut device = utils::parse_tuntap_options(&mut matches);
+    let fd = device.as_raw_fd();
+    let name = &matches.free[0];
+
+    // Create interface
+    let mut config = match device.capabilities().medium {
+        Medium::Ethernet => {
+            Config::new(EthernetAddress([0x02, 0x00, 0x00, 0x00, 0x00, 0x01]).into())
+        }
+    };
+    config.random_seed = rand::random();
+
+    let mut iface = Interface::new(config, &mut device, Instant::now());
+    let static_config: StaticConfig = include_str!("tap0.conf").parse().unwrap();
+    static_config.apply(&mut iface).unwrap();
+
+    // Create sockets
+    let servers = &[
+        Ipv4Address::new(8, 8, 4, 4).into(),
+        Ipv4Address::new(8, 8, 8, 8).into(),
+    ];
+    let dns_socket = dns::Socket::new(servers, vec![]);
+
+    let mut sockets = SocketSet::new(vec![]);
+    let dns_handle = sockets.add(dns_socket);
+
+    let socket = sockets.get_mut::<dns::Socket>(dns_handle);
+    let query = socket
+        .start_query(iface.context(), name, DnsQueryType::A)
+        .unwrap();
+
+    loop {
+        let timestamp = Instant::now();
+        log::debug!("timestamp {:?}", timestamp);
+
+        iface.poll(timestamp, &mut device, &mut sockets);
+
+        match sockets
+            .get_mut::<dns::Socket>(dns_handle)
+            .get_query_result(query)
+        {
+            Ok(addrs) => {
+                println!("Query done: {addrs:?}");
+                break;
+            }
+            Err(GetQueryResultError::Pending) => {} // not done yet
+            Err(e) => panic!("query failed: {e:?}"),
+        }
+
+        phy_wait(fd, iface.poll_delay(timestamp, &sockets)).expect("wait error");
+    }
+}
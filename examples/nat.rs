@@ -0,0 +1,266 @@
+mod utils;
+
+use std::collections::HashMap;
+use std::os::unix::io::AsRawFd;
+use std::str::FromStr;
+
+use tapip_rs::iface::{Nat, PortForward};
+use tapip_rs::phy::wait as phy_wait;
+use tapip_rs::phy::{Device, Medium, RxToken, TunTapInterface, TxToken};
+use tapip_rs::time::{Duration, Instant};
+use tapip_rs::wire::{
+    ArpOperation, ArpPacket, ArpRepr, EthernetAddress, EthernetFrame, EthernetProtocol,
+    EthernetRepr, IpProtocol, Ipv4Address, Ipv4Packet, ETHERNET_HEADER_LEN,
+};
+
+/// One side of the router.
+struct Port {
+    device: TunTapInterface,
+    hardware_addr: EthernetAddress,
+    ip_addr: Ipv4Address,
+    /// Hardware addresses of the hosts on the link, learned from their packets.
+    neighbors: HashMap<Ipv4Address, EthernetAddress>,
+}
+
+impl Port {
+    fn new(name: &str, hardware_addr: EthernetAddress, ip_addr: Ipv4Address) -> Port {
+        Port {
+            device: TunTapInterface::new(name, Medium::Ethernet).unwrap(),
+            hardware_addr,
+            ip_addr,
+            neighbors: HashMap::new(),
+        }
+    }
+
+    fn receive(&mut self, timestamp: Instant) -> Option<Vec<u8>> {
+        let (rx_token, _) = self.device.receive(timestamp)?;
+        Some(rx_token.consume(|frame| frame.to_vec()))
+    }
+
+    fn send(
+        &mut self,
+        dst_addr: EthernetAddress,
+        ethertype: EthernetProtocol,
+        payload: &[u8],
+        timestamp: Instant,
+    ) {
+        let Some(tx_token) = self.device.transmit(timestamp) else {
+            return;
+        };
+        let repr = EthernetRepr {
+            src_addr: self.hardware_addr,
+            dst_addr,
+            ethertype,
+        };
+        tx_token.consume(ETHERNET_HEADER_LEN + payload.len(), |buffer| {
+            let mut frame = EthernetFrame::new_unchecked(buffer);
+            repr.emit(&mut frame);
+            frame.payload_mut().copy_from_slice(payload);
+        })
+    }
+
+    /// Learn the sender of an ARP packet, and answer the requests for our address.
+    fn process_arp(&mut self, payload: &[u8], timestamp: Instant) {
+        let Ok(ArpRepr::EthernetIpv4 {
+            operation,
+            source_hardware_addr,
+            source_protocol_addr,
+            target_protocol_addr,
+            ..
+        }) = ArpPacket::new_checked(payload).and_then(|packet| ArpRepr::parse(&packet))
+        else {
+            return;
+        };
+        self.neighbors
+            .insert(source_protocol_addr, source_hardware_addr);
+
+        if operation == ArpOperation::Request && target_protocol_addr == self.ip_addr {
+            let reply = ArpRepr::EthernetIpv4 {
+                operation: ArpOperation::Reply,
+                source_hardware_addr: self.hardware_addr,
+                source_protocol_addr: self.ip_addr,
+                target_hardware_addr: source_hardware_addr,
+                target_protocol_addr: source_protocol_addr,
+            };
+            let mut buffer = vec![0; reply.buffer_len()];
+            reply.emit(&mut ArpPacket::new_unchecked(&mut buffer[..]));
+            self.send(
+                source_hardware_addr,
+                EthernetProtocol::Arp,
+                &buffer,
+                timestamp,
+            );
+        }
+    }
+}
+
+/// Decrement the TTL of a forwarded packet, returning `false` if it ran out.
+fn decrement_ttl(packet: &mut [u8]) -> bool {
+    let Ok(mut ipv4_packet) = Ipv4Packet::new_checked(packet) else {
+        return false;
+    };
+    if ipv4_packet.hop_limit() <= 1 {
+        return false;
+    }
+    ipv4_packet.set_hop_limit(ipv4_packet.hop_limit() - 1);
+    ipv4_packet.fill_checksum();
+    true
+}
+
+fn parse_hardware_addr(s: &str) -> EthernetAddress {
+    let octets: Vec<u8> = s
+        .split(':')
+        .map(|octet| u8::from_str_radix(octet, 16).expect("invalid hardware address"))
+        .collect();
+    assert_eq!(octets.len(), 6, "invalid hardware address");
+    EthernetAddress::from_bytes(&octets)
+}
+
+/// Parse a port forward given as `PROTOCOL:PORT:ADDRESS:PORT`.
+fn parse_port_forward(s: &str) -> PortForward {
+    let fields: Vec<&str> = s.split(':').collect();
+    let [protocol, external_port, internal_addr, internal_port] = fields[..] else {
+        panic!("invalid port forward {s}");
+    };
+    PortForward {
+        protocol: match protocol {
+            "tcp" => IpProtocol::Tcp,
+            "udp" => IpProtocol::Udp,
+            _ => panic!("invalid port forward protocol {protocol}"),
+        },
+        external_port: u16::from_str(external_port).unwrap(),
+        internal_addr: Ipv4Address::from_str(internal_addr).unwrap(),
+        internal_port: u16::from_str(internal_port).unwrap(),
+    }
+}
+
+fn main() {
+    utils::setup_logging("info");
+
+    let (mut opts, free) = utils::create_options();
+    opts.optopt(
+        "",
+        "inside",
+        "TAP interface of the internal network",
+        "tap0",
+    );
+    opts.optopt(
+        "",
+        "outside",
+        "TAP interface of the upstream network",
+        "tap1",
+    );
+    opts.optopt(
+        "",
+        "inside-addr",
+        "Address of the router on the internal network (default: 192.168.69.1)",
+        "ADDRESS",
+    );
+    opts.optopt(
+        "",
+        "external-addr",
+        "Address of the router on the upstream network (default: 192.168.70.1)",
+        "ADDRESS",
+    );
+    opts.optopt(
+        "",
+        "gateway",
+        "Hardware address of the upstream gateway",
+        "02:00:00:00:00:03",
+    );
+    opts.optmulti(
+        "",
+        "forward",
+        "Forward an external port to an internal endpoint",
+        "tcp:8080:192.168.69.100:80",
+    );
+
+    let matches = utils::parse_options(&opts, free);
+    let inside_addr = matches
+        .opt_str("inside-addr")
+        .map(|s| Ipv4Address::from_str(&s).unwrap())
+        .unwrap_or(Ipv4Address::new(192, 168, 69, 1));
+    let external_addr = matches
+        .opt_str("external-addr")
+        .map(|s| Ipv4Address::from_str(&s).unwrap())
+        .unwrap_or(Ipv4Address::new(192, 168, 70, 1));
+    let gateway = parse_hardware_addr(&matches.opt_str("gateway").expect("missing --gateway"));
+
+    let mut inside = Port::new(
+        &matches.opt_str("inside").expect("missing --inside"),
+        EthernetAddress([0x02, 0x00, 0x00, 0x00, 0x00, 0x01]),
+        inside_addr,
+    );
+    let mut outside = Port::new(
+        &matches.opt_str("outside").expect("missing --outside"),
+        EthernetAddress([0x02, 0x00, 0x00, 0x00, 0x00, 0x02]),
+        external_addr,
+    );
+    let fd = inside.device.as_raw_fd();
+
+    let mut nat = Nat::new(external_addr);
+    for forward in matches.opt_strs("forward") {
+        nat.add_port_forward(parse_port_forward(&forward)).unwrap();
+    }
+
+    loop {
+        let timestamp = Instant::now();
+
+        while let Some(frame) = inside.receive(timestamp) {
+            let Ok(frame) = EthernetFrame::new_checked(&frame[..]) else {
+                continue;
+            };
+            match frame.ethertype() {
+                EthernetProtocol::Arp => inside.process_arp(frame.payload(), timestamp),
+                EthernetProtocol::Ipv4 if frame.dst_addr() == inside.hardware_addr => {
+                    let mut packet = frame.payload().to_vec();
+                    let Ok(ipv4_packet) = Ipv4Packet::new_checked(&packet[..]) else {
+                        continue;
+                    };
+                    inside
+                        .neighbors
+                        .insert(ipv4_packet.src_addr(), frame.src_addr());
+                    // The router itself doesn't answer anything but ARP.
+                    if ipv4_packet.dst_addr() == inside.ip_addr || !decrement_ttl(&mut packet) {
+                        continue;
+                    }
+                    match nat.translate_outbound(&mut packet, timestamp) {
+                        Ok(()) => outside.send(gateway, EthernetProtocol::Ipv4, &packet, timestamp),
+                        Err(err) => log::debug!("dropping outbound packet: {err}"),
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        while let Some(frame) = outside.receive(timestamp) {
+            let Ok(frame) = EthernetFrame::new_checked(&frame[..]) else {
+                continue;
+            };
+            match frame.ethertype() {
+                EthernetProtocol::Arp => outside.process_arp(frame.payload(), timestamp),
+                EthernetProtocol::Ipv4 if frame.dst_addr() == outside.hardware_addr => {
+                    let mut packet = frame.payload().to_vec();
+                    if !decrement_ttl(&mut packet) {
+                        continue;
+                    }
+                    if let Err(err) = nat.translate_inbound(&mut packet, timestamp) {
+                        log::debug!("dropping inbound packet: {err}");
+                        continue;
+                    }
+                    let dst_addr = Ipv4Packet::new_unchecked(&packet[..]).dst_addr();
+                    match inside.neighbors.get(&dst_addr) {
+                        Some(&hardware_addr) => {
+                            inside.send(hardware_addr, EthernetProtocol::Ipv4, &packet, timestamp)
+                        }
+                        None => log::debug!("no hardware address for {dst_addr}"),
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        // Both taps are polled; only the internal one wakes the router up early.
+        phy_wait(fd, Some(Duration::from_millis(10))).expect("wait error");
+    }
+}
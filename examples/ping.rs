@@ -5,13 +5,15 @@ use std::cmp;
 use std::collections::HashMap;
 use std::os::unix::io::AsRawFd;
 use std::str::FromStr;
-use tapip_rs::iface::{Interface, SocketSet};
+use tapip_rs::iface::{Interface, SocketSet, StaticConfig};
 
 use tapip_rs::iface::Config;
 use tapip_rs::phy::wait as phy_wait;
 use tapip_rs::phy::Device;
 use tapip_rs::socket::icmp;
-use tapip_rs::wire::{EthernetAddress, Icmpv4Packet, Icmpv4Repr, IpAddress, IpCidr, Ipv4Address};
+use tapip_rs::wire::{
+    EthernetAddress, Icmpv4Packet, Icmpv4Repr, Icmpv6Packet, Icmpv6Repr, IpAddress,
+};
 use tapip_rs::{
     phy::Medium,
     time::{Duration, Instant},
@@ -111,13 +113,8 @@ fn main() {
     config.random_seed = rand::random();
 
     let mut iface = Interface::new(config, &mut device, Instant::now());
-    iface.update_ip_addrs(|ip_addrs| {
-        ip_addrs.push(IpCidr::new(IpAddress::v4(192, 168, 69, 1), 24));
-    });
-    iface
-        .routes_mut()
-        .add_default_ipv4_route(Ipv4Address::new(192, 168, 69, 100))
-        .unwrap();
+    let static_config: StaticConfig = include_str!("tap0.conf").parse().unwrap();
+    static_config.apply(&mut iface).unwrap();
 
     // Create sockets
     let icmp_rx_buffer = icmp::PacketBuffer::new(vec![icmp::PacketMetadata::EMPTY], vec![0; 256]);
@@ -160,6 +157,23 @@ fn main() {
                     );
                     icmp_repr.emit(&mut icmp_packet, &device_caps.checksum);
                 }
+                IpAddress::Ipv6(address) => {
+                    let (icmp_repr, mut icmp_packet) = send_icmp_ping!(
+                        Icmpv6Repr,
+                        Icmpv6Packet,
+                        ident,
+                        seq_no,
+                        echo_payload,
+                        socket,
+                        remote_addr
+                    );
+                    icmp_repr.emit(
+                        &iface.get_source_address_ipv6(&address),
+                        &address,
+                        &mut icmp_packet,
+                        &device_caps.checksum,
+                    );
+                }
             }
 
             waiting_queue.insert(seq_no, timestamp);
@@ -184,6 +198,25 @@ fn main() {
                         received
                     );
                 }
+                IpAddress::Ipv6(address) => {
+                    let icmp_packet = Icmpv6Packet::new_checked(&payload).unwrap();
+                    let icmp_repr = Icmpv6Repr::parse(
+                        &address,
+                        &iface.get_source_address_ipv6(&address),
+                        &icmp_packet,
+                        &device_caps.checksum,
+                    )
+                    .unwrap();
+                    get_icmp_pong!(
+                        Icmpv6Repr,
+                        icmp_repr,
+                        payload,
+                        waiting_queue,
+                        remote_addr,
+                        timestamp,
+                        received
+                    );
+                }
             }
         }
 
@@ -0,0 +1,282 @@
+mod utils;
+
+use std::cmp;
+use std::io::{self, Write};
+use std::os::unix::io::AsRawFd;
+use std::str::FromStr;
+use tapip_rs::iface::{Interface, SocketSet, StaticConfig};
+
+use tapip_rs::iface::Config;
+use tapip_rs::phy::wait as phy_wait;
+use tapip_rs::phy::Device;
+use tapip_rs::socket::icmp;
+use tapip_rs::wire::{
+    EthernetAddress, Icmpv4Packet, Icmpv4Repr, Icmpv6Packet, Icmpv6Repr, IpAddress,
+};
+use tapip_rs::{
+    phy::Medium,
+    time::{Duration, Instant},
+};
+
+/// The answer to a probe.
+enum Response {
+    /// A router on the path dropped the probe because its hop limit ran out.
+    Hop,
+    /// The probe reached the destination.
+    Reached,
+    /// The destination was reported unreachable.
+    Unreachable,
+}
+
+macro_rules! send_icmp_probe {
+    ( $repr_type:ident, $packet_type:ident, $ident:expr, $seq_no:expr,
+      $probe_payload:expr, $socket:expr, $remote_addr:expr ) => {{
+        let icmp_repr = $repr_type::EchoRequest {
+            ident: $ident,
+            seq_no: $seq_no,
+            data: &$probe_payload,
+        };
+
+        let icmp_payload = $socket.send(icmp_repr.buffer_len(), $remote_addr).unwrap();
+
+        let icmp_packet = $packet_type::new_unchecked(icmp_payload);
+        (icmp_repr, icmp_packet)
+    }};
+}
+
+macro_rules! get_icmp_response {
+    ( $repr_type:ident, $repr:expr, $ident:expr, $seq_no:expr ) => {{
+        match $repr {
+            $repr_type::EchoReply { ident, seq_no, .. } if ident == $ident && seq_no == $seq_no => {
+                Some(Response::Reached)
+            }
+            $repr_type::TimeExceeded { .. }
+                if $repr.quoted_echo_request() == Some(($ident, $seq_no)) =>
+            {
+                Some(Response::Hop)
+            }
+            $repr_type::DstUnreachable { .. }
+                if $repr.quoted_echo_request() == Some(($ident, $seq_no)) =>
+            {
+                Some(Response::Unreachable)
+            }
+            _ => None,
+        }
+    }};
+}
+
+fn main() {
+    utils::setup_logging("warn");
+
+    let (mut opts, mut free) = utils::create_options();
+    utils::add_tuntap_options(&mut opts, &mut free);
+    utils::add_middleware_options(&mut opts, &mut free);
+    opts.optopt(
+        "m",
+        "max-hops",
+        "Maximum number of hops to probe (default: 30)",
+        "MAX_HOPS",
+    );
+    opts.optopt(
+        "q",
+        "queries",
+        "Amount of probes sent to each hop (default: 3)",
+        "QUERIES",
+    );
+    opts.optopt(
+        "",
+        "timeout",
+        "Maximum wait duration for a response to a probe (seconds) (default: 3)",
+        "TIMEOUT",
+    );
+    free.push("ADDRESS");
+
+    let mut matches = utils::parse_options(&opts, free);
+    let device = utils::parse_tuntap_options(&mut matches);
+    let fd = device.as_raw_fd();
+    let mut device = device;
+    let device_caps = device.capabilities();
+    let remote_addr = IpAddress::from_str(&matches.free[0]).expect("invalid address format");
+    let max_hops = matches
+        .opt_str("max-hops")
+        .map(|s| u8::from_str(&s).unwrap())
+        .unwrap_or(30);
+    let queries = matches
+        .opt_str("queries")
+        .map(|s| usize::from_str(&s).unwrap())
+        .unwrap_or(3);
+    let timeout = Duration::from_secs(
+        matches
+            .opt_str("timeout")
+            .map(|s| u64::from_str(&s).unwrap())
+            .unwrap_or(3),
+    );
+
+    // Create interface
+    let mut config = match device.capabilities().medium {
+        Medium::Ethernet => {
+            Config::new(EthernetAddress([0x02, 0x00, 0x00, 0x00, 0x00, 0x01]).into())
+        }
+    };
+    config.random_seed = rand::random();
+
+    let mut iface = Interface::new(config, &mut device, Instant::now());
+    let static_config: StaticConfig = include_str!("tap0.conf").parse().unwrap();
+    static_config.apply(&mut iface).unwrap();
+
+    // Create sockets
+    let icmp_rx_buffer =
+        icmp::PacketBuffer::new(vec![icmp::PacketMetadata::EMPTY; 4], vec![0; 1024]);
+    let icmp_tx_buffer = icmp::PacketBuffer::new(vec![icmp::PacketMetadata::EMPTY], vec![0; 256]);
+    let icmp_socket = icmp::Socket::new(icmp_rx_buffer, icmp_tx_buffer);
+    let mut sockets = SocketSet::new(vec![]);
+    let icmp_handle = sockets.add(icmp_socket);
+
+    let probe_payload = [0xffu8; 32];
+    let ident = 0x22c;
+    let mut seq_no = 0u16;
+    let mut hop = 1u8;
+    let mut query = 0;
+    // Sequence number and send time of the probe in flight. Probes are sent one
+    // at a time, since the hop limit is a property of the socket.
+    let mut outstanding: Option<(u16, Instant)> = None;
+    let mut hop_addr: Option<IpAddress> = None;
+    let mut reached = false;
+
+    println!("traceroute to {remote_addr}, {max_hops} hops max");
+
+    loop {
+        let timestamp = Instant::now();
+        iface.poll(timestamp, &mut device, &mut sockets);
+
+        let timestamp = Instant::now();
+        let socket = sockets.get_mut::<icmp::Socket>(icmp_handle);
+        if !socket.is_open() {
+            socket.bind(icmp::Endpoint::Ident(ident)).unwrap();
+        }
+
+        if outstanding.is_none() && socket.can_send() {
+            if query == 0 {
+                print!("{hop:2}");
+            }
+            socket.set_hop_limit(Some(hop));
+
+            match remote_addr {
+                IpAddress::Ipv4(_) => {
+                    let (icmp_repr, mut icmp_packet) = send_icmp_probe!(
+                        Icmpv4Repr,
+                        Icmpv4Packet,
+                        ident,
+                        seq_no,
+                        probe_payload,
+                        socket,
+                        remote_addr
+                    );
+                    icmp_repr.emit(&mut icmp_packet, &device_caps.checksum);
+                }
+                IpAddress::Ipv6(address) => {
+                    let (icmp_repr, mut icmp_packet) = send_icmp_probe!(
+                        Icmpv6Repr,
+                        Icmpv6Packet,
+                        ident,
+                        seq_no,
+                        probe_payload,
+                        socket,
+                        remote_addr
+                    );
+                    icmp_repr.emit(
+                        &iface.get_source_address_ipv6(&address),
+                        &address,
+                        &mut icmp_packet,
+                        &device_caps.checksum,
+                    );
+                }
+            }
+
+            outstanding = Some((seq_no, timestamp));
+            seq_no = seq_no.wrapping_add(1);
+        }
+
+        let mut answered = false;
+        if let Some((probe_seq_no, sent_at)) = outstanding {
+            while socket.can_recv() {
+                let (payload, addr) = socket.recv().unwrap();
+
+                let response = match addr {
+                    IpAddress::Ipv4(_) => {
+                        let icmp_packet = Icmpv4Packet::new_checked(&payload).unwrap();
+                        let icmp_repr =
+                            Icmpv4Repr::parse(&icmp_packet, &device_caps.checksum).unwrap();
+                        get_icmp_response!(Icmpv4Repr, icmp_repr, ident, probe_seq_no)
+                    }
+                    IpAddress::Ipv6(address) => {
+                        let icmp_packet = Icmpv6Packet::new_checked(&payload).unwrap();
+                        let icmp_repr = Icmpv6Repr::parse(
+                            &address,
+                            &iface.get_source_address_ipv6(&address),
+                            &icmp_packet,
+                            &device_caps.checksum,
+                        )
+                        .unwrap();
+                        get_icmp_response!(Icmpv6Repr, icmp_repr, ident, probe_seq_no)
+                    }
+                };
+
+                // Responses to earlier, timed out probes are ignored.
+                let Some(response) = response else { continue };
+                if hop_addr != Some(addr) {
+                    print!("  {addr}");
+                    hop_addr = Some(addr);
+                }
+                print!("  {}ms", (timestamp - sent_at).total_millis());
+                match response {
+                    Response::Hop => (),
+                    Response::Reached => reached = true,
+                    Response::Unreachable => {
+                        print!(" !");
+                        reached = true;
+                    }
+                }
+                answered = true;
+                break;
+            }
+
+            if !answered && timestamp - sent_at >= timeout {
+                print!("  *");
+                answered = true;
+            }
+        }
+
+        if answered {
+            io::stdout().flush().unwrap();
+            outstanding = None;
+            query += 1;
+            if query == queries {
+                println!();
+                if reached || hop == max_hops {
+                    break;
+                }
+                hop += 1;
+                query = 0;
+                hop_addr = None;
+            }
+            continue;
+        }
+
+        let timestamp = Instant::now();
+        let resume_at = match outstanding {
+            Some((_, sent_at)) => cmp::max(sent_at + timeout, timestamp),
+            None => timestamp,
+        };
+        match iface.poll_at(timestamp, &sockets) {
+            Some(poll_at) if timestamp < poll_at => {
+                let resume_at = cmp::min(poll_at, resume_at);
+                phy_wait(fd, Some(resume_at - timestamp)).expect("wait error");
+            }
+            Some(_) => (),
+            None => {
+                phy_wait(fd, Some(resume_at - timestamp)).expect("wait error");
+            }
+        }
+    }
+}
@@ -0,0 +1,540 @@
+use std::collections::HashMap;
+
+use crate::config::IFACE_CONNTRACK_COUNT;
+use crate::phy::ChecksumCapabilities;
+use crate::time::{Duration, Instant};
+use crate::wire::{
+    Icmpv4Packet, Icmpv4Repr, IpProtocol, Ipv4Address, Ipv4Repr, TcpControl, TcpPacket, TcpRepr,
+    UdpPacket,
+};
+
+use super::packet::IpPayload;
+
+/// The addresses and ports identifying a connection in one direction.
+///
+/// ICMP query messages use their identifier as both ports.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Tuple {
+    pub protocol: IpProtocol,
+    pub src_addr: Ipv4Address,
+    pub dst_addr: Ipv4Address,
+    pub src_port: u16,
+    pub dst_port: u16,
+}
+
+impl Tuple {
+    /// Return the tuple of the packets flowing in the opposite direction.
+    pub fn reverse(&self) -> Tuple {
+        Tuple {
+            protocol: self.protocol,
+            src_addr: self.dst_addr,
+            dst_addr: self.src_addr,
+            src_port: self.dst_port,
+            dst_port: self.src_port,
+        }
+    }
+}
+
+/// The state of a tracked TCP connection, as seen from its control flags.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TcpState {
+    /// A SYN was sent by the initiator.
+    SynSent,
+    /// The responder answered with a SYN-ACK.
+    SynReceived,
+    /// The initiator acknowledged the SYN-ACK.
+    Established,
+    /// One of the ends sent a FIN.
+    FinWait,
+    /// Both ends sent a FIN.
+    TimeWait,
+    /// One of the ends sent a RST.
+    Close,
+}
+
+/// How a packet relates to the tracked connections, as matched by firewall rules.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Status {
+    /// The packet starts a new connection.
+    New,
+    /// The packet belongs to a tracked connection, in either direction.
+    Established,
+    /// The packet is an ICMP error about a tracked connection.
+    Related,
+    /// The packet doesn't start a connection and belongs to none, like a TCP
+    /// segment without SYN or an unsolicited echo reply.
+    Invalid,
+}
+
+/// Lifetimes of the connections, by protocol and state.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timeouts {
+    pub tcp_syn_sent: Duration,
+    pub tcp_syn_received: Duration,
+    pub tcp_established: Duration,
+    pub tcp_fin_wait: Duration,
+    pub tcp_time_wait: Duration,
+    pub tcp_close: Duration,
+    /// Lifetime of a UDP connection that only saw packets from its initiator.
+    pub udp: Duration,
+    /// Lifetime of a UDP connection that saw packets in both directions.
+    pub udp_replied: Duration,
+    pub icmp: Duration,
+}
+
+impl Default for Timeouts {
+    fn default() -> Self {
+        Timeouts {
+            tcp_syn_sent: Duration::from_secs(120),
+            tcp_syn_received: Duration::from_secs(60),
+            tcp_established: Duration::from_secs(5 * 24 * 3600),
+            tcp_fin_wait: Duration::from_secs(120),
+            tcp_time_wait: Duration::from_secs(120),
+            tcp_close: Duration::from_secs(10),
+            udp: Duration::from_secs(30),
+            udp_replied: Duration::from_secs(180),
+            icmp: Duration::from_secs(30),
+        }
+    }
+}
+
+/// Packet and byte counts of one direction of a connection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Counters {
+    pub packets: u64,
+    /// Length of the IP payloads.
+    pub bytes: u64,
+}
+
+/// A tracked connection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Connection {
+    /// The tuple of the packets sent by the initiator.
+    pub tuple: Tuple,
+    /// The state of a TCP connection, `None` for other protocols.
+    pub tcp_state: Option<TcpState>,
+    /// Whether the responder sent any packet.
+    pub replied: bool,
+    /// Packets sent by the initiator.
+    pub original: Counters,
+    /// Packets sent by the responder.
+    pub reply: Counters,
+    pub expires_at: Instant,
+}
+
+/// The direction of a packet within its connection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Dir {
+    Original,
+    Reply,
+}
+
+/// What a packet means for the connection tracker.
+#[derive(Debug, Clone, Copy)]
+enum Kind {
+    /// A TCP segment, with its control flag and whether it carries an ACK.
+    Tcp {
+        control: TcpControl,
+        ack: bool,
+    },
+    Udp,
+    /// An ICMP query, like an echo request.
+    IcmpQuery,
+    /// An ICMP reply to a query, like an echo reply.
+    IcmpReply,
+    /// An ICMP error. The tuple is the one of the packet it quotes.
+    IcmpError,
+}
+
+/// The fields of a packet that the connection tracker looks at.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Tracked {
+    tuple: Tuple,
+    kind: Kind,
+    len: usize,
+}
+
+impl Tracked {
+    /// Extract the fields of a received packet, or `None` if the packet is not
+    /// tracked.
+    pub(crate) fn parse(ipv4_repr: &Ipv4Repr, payload: &[u8]) -> Option<Tracked> {
+        let src_addr = ipv4_repr.src_addr.into();
+        let dst_addr = ipv4_repr.dst_addr.into();
+        // The checksums are verified when the packet is processed.
+        let checksum_caps = ChecksumCapabilities::ignored();
+
+        match ipv4_repr.next_header {
+            IpProtocol::Tcp => {
+                let tcp_packet = TcpPacket::new_checked(payload).ok()?;
+                let tcp_repr =
+                    TcpRepr::parse(&tcp_packet, &src_addr, &dst_addr, &checksum_caps).ok()?;
+                Some(Tracked {
+                    tuple: Tracked::tuple(ipv4_repr, tcp_repr.src_port, tcp_repr.dst_port),
+                    kind: Kind::Tcp {
+                        control: tcp_repr.control,
+                        ack: tcp_repr.ack_number.is_some(),
+                    },
+                    len: payload.len(),
+                })
+            }
+            IpProtocol::Udp => {
+                let udp_packet = UdpPacket::new_checked(payload).ok()?;
+                Some(Tracked {
+                    tuple: Tracked::tuple(ipv4_repr, udp_packet.src_port(), udp_packet.dst_port()),
+                    kind: Kind::Udp,
+                    len: payload.len(),
+                })
+            }
+            IpProtocol::Icmp => {
+                let icmp_packet = Icmpv4Packet::new_checked(payload).ok()?;
+                let icmp_repr = Icmpv4Repr::parse(&icmp_packet, &checksum_caps).ok()?;
+                Tracked::from_icmp(ipv4_repr, &icmp_repr, payload.len())
+            }
+            _ => None,
+        }
+    }
+
+    /// Extract the fields of a packet about to be sent, or `None` if the packet
+    /// is not tracked.
+    pub(crate) fn from_payload(ipv4_repr: &Ipv4Repr, payload: &IpPayload) -> Option<Tracked> {
+        match payload {
+            IpPayload::Icmpv4(icmp_repr) => {
+                Tracked::from_icmp(ipv4_repr, icmp_repr, ipv4_repr.payload_len)
+            }
+            IpPayload::Udp(udp_repr, _) | IpPayload::Dhcpv4(udp_repr, _) => Some(Tracked {
+                tuple: Tracked::tuple(ipv4_repr, udp_repr.src_port, udp_repr.dst_port),
+                kind: Kind::Udp,
+                len: ipv4_repr.payload_len,
+            }),
+            IpPayload::Raw(payload) => Tracked::parse(ipv4_repr, payload),
+            IpPayload::Icmpv6(_) | IpPayload::Igmp(_) => None,
+        }
+    }
+
+    fn from_icmp(ipv4_repr: &Ipv4Repr, icmp_repr: &Icmpv4Repr, len: usize) -> Option<Tracked> {
+        let (ident, kind) = match *icmp_repr {
+            Icmpv4Repr::EchoRequest { ident, .. } | Icmpv4Repr::TimestampRequest { ident, .. } => {
+                (ident, Kind::IcmpQuery)
+            }
+            Icmpv4Repr::EchoReply { ident, .. } | Icmpv4Repr::TimestampReply { ident, .. } => {
+                (ident, Kind::IcmpReply)
+            }
+            Icmpv4Repr::DstUnreachable { header, data, .. }
+            | Icmpv4Repr::TimeExceeded { header, data, .. }
+            | Icmpv4Repr::Redirect { header, data, .. }
+            | Icmpv4Repr::ParamProblem { header, data, .. } => {
+                return Some(Tracked {
+                    tuple: Tracked::quoted(&header, data)?,
+                    kind: Kind::IcmpError,
+                    len,
+                });
+            }
+            _ => return None,
+        };
+        Some(Tracked {
+            tuple: Tracked::tuple(ipv4_repr, ident, ident),
+            kind,
+            len,
+        })
+    }
+
+    /// Return the tuple of the packet quoted by an ICMP error.
+    fn quoted(header: &Ipv4Repr, data: &[u8]) -> Option<Tuple> {
+        let (src_port, dst_port) = match header.next_header {
+            IpProtocol::Tcp | IpProtocol::Udp if data.len() >= 4 => (
+                u16::from_be_bytes([data[0], data[1]]),
+                u16::from_be_bytes([data[2], data[3]]),
+            ),
+            IpProtocol::Icmp if data.len() >= 6 => {
+                let ident = u16::from_be_bytes([data[4], data[5]]);
+                (ident, ident)
+            }
+            _ => return None,
+        };
+        Some(Tracked::tuple(header, src_port, dst_port))
+    }
+
+    fn tuple(ipv4_repr: &Ipv4Repr, src_port: u16, dst_port: u16) -> Tuple {
+        Tuple {
+            protocol: ipv4_repr.next_header,
+            src_addr: ipv4_repr.src_addr,
+            dst_addr: ipv4_repr.dst_addr,
+            src_port,
+            dst_port,
+        }
+    }
+
+    /// Check whether the packet may start a connection.
+    fn is_initial(&self) -> bool {
+        match self.kind {
+            Kind::Tcp { control, ack } => control == TcpControl::Syn && !ack,
+            Kind::Udp | Kind::IcmpQuery => true,
+            Kind::IcmpReply | Kind::IcmpError => false,
+        }
+    }
+}
+
+/// A tracked connection, keyed by its original tuple.
+#[derive(Debug, Clone, Copy)]
+struct Entry {
+    tcp_state: Option<TcpState>,
+    /// Whether each direction sent a FIN, original first.
+    fin: [bool; 2],
+    replied: bool,
+    original: Counters,
+    reply: Counters,
+    expires_at: Instant,
+}
+
+/// A connection tracker.
+///
+/// The interface passes every IPv4 packet it receives or sends to the tracker,
+/// which follows TCP connections through their control flags and pairs UDP
+/// datagrams and ICMP queries with their replies. The [Status] of each packet is
+/// available to the [firewall rules](super::Rule::state), and the connections to
+/// [connections](#method.connections).
+///
+/// Connections are only created by packets accepted by the firewall. When the
+/// table is full, the connection closest to expiry is evicted.
+#[derive(Debug)]
+pub struct Conntrack {
+    connections: HashMap<Tuple, Entry>,
+    timeouts: Timeouts,
+    capacity: usize,
+}
+
+impl Conntrack {
+    /// Create an empty connection tracker.
+    pub fn new() -> Self {
+        Self::with_capacity(IFACE_CONNTRACK_COUNT)
+    }
+
+    /// Create an empty connection tracker following up to `capacity` connections.
+    /// A tracker without capacity follows none.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Conntrack {
+            connections: HashMap::new(),
+            timeouts: Timeouts::default(),
+            capacity,
+        }
+    }
+
+    /// Return the lifetimes of the connections.
+    pub fn timeouts(&self) -> Timeouts {
+        self.timeouts
+    }
+
+    /// Set the lifetimes of the connections. Existing connections keep their
+    /// expiry time until they see another packet.
+    pub fn set_timeouts(&mut self, timeouts: Timeouts) {
+        self.timeouts = timeouts;
+    }
+
+    /// Return the connections that are still alive.
+    pub fn connections(&self, timestamp: Instant) -> impl Iterator<Item = Connection> + '_ {
+        self.connections
+            .iter()
+            .filter(move |(_, entry)| timestamp < entry.expires_at)
+            .map(|(tuple, entry)| Self::connection(tuple, entry))
+    }
+
+    /// Return the connection a tuple belongs to, in either direction.
+    pub fn lookup(&self, tuple: &Tuple, timestamp: Instant) -> Option<Connection> {
+        let (original, entry, _) = self.find(tuple, timestamp)?;
+        Some(Self::connection(&original, entry))
+    }
+
+    /// Forget the connection a tuple belongs to, in either direction.
+    pub fn remove(&mut self, tuple: &Tuple) -> Option<Connection> {
+        let original = if self.connections.contains_key(tuple) {
+            *tuple
+        } else {
+            tuple.reverse()
+        };
+        let entry = self.connections.remove(&original)?;
+        Some(Self::connection(&original, &entry))
+    }
+
+    /// Forget all the connections.
+    pub fn flush(&mut self) {
+        self.connections.clear()
+    }
+
+    fn connection(tuple: &Tuple, entry: &Entry) -> Connection {
+        Connection {
+            tuple: *tuple,
+            tcp_state: entry.tcp_state,
+            replied: entry.replied,
+            original: entry.original,
+            reply: entry.reply,
+            expires_at: entry.expires_at,
+        }
+    }
+
+    /// Find the live connection of a tuple, returning its original tuple and the
+    /// direction of the tuple.
+    fn find(&self, tuple: &Tuple, timestamp: Instant) -> Option<(Tuple, &Entry, Dir)> {
+        let alive = |entry: &&Entry| timestamp < entry.expires_at;
+        if let Some(entry) = self.connections.get(tuple).filter(alive) {
+            return Some((*tuple, entry, Dir::Original));
+        }
+        let reverse = tuple.reverse();
+        let entry = self.connections.get(&reverse).filter(alive)?;
+        Some((reverse, entry, Dir::Reply))
+    }
+
+    /// Return how a packet relates to the tracked connections.
+    pub(crate) fn classify(&self, packet: &Tracked, timestamp: Instant) -> Status {
+        match (self.find(&packet.tuple, timestamp), packet.kind) {
+            (Some(_), Kind::IcmpError) => Status::Related,
+            (None, _) if packet.is_initial() => Status::New,
+            (None, _) => Status::Invalid,
+            // A closed TCP connection may be reopened with the same ports.
+            (Some((_, entry, Dir::Original)), _)
+                if packet.is_initial()
+                    && matches!(entry.tcp_state, Some(TcpState::TimeWait | TcpState::Close)) =>
+            {
+                Status::New
+            }
+            (Some(_), _) => Status::Established,
+        }
+    }
+
+    /// Update the connections with a packet accepted by the firewall.
+    pub(crate) fn track(&mut self, packet: &Tracked, timestamp: Instant) {
+        let (original, dir) = match self.classify(packet, timestamp) {
+            Status::Related | Status::Invalid => return,
+            Status::New => {
+                self.insert(packet, timestamp);
+                (packet.tuple, Dir::Original)
+            }
+            Status::Established => match self.find(&packet.tuple, timestamp) {
+                Some((original, _, dir)) => (original, dir),
+                None => return,
+            },
+        };
+
+        let timeouts = self.timeouts;
+        let Some(entry) = self.connections.get_mut(&original) else {
+            return;
+        };
+        let counters = match dir {
+            Dir::Original => &mut entry.original,
+            Dir::Reply => {
+                entry.replied = true;
+                &mut entry.reply
+            }
+        };
+        counters.packets += 1;
+        counters.bytes += packet.len as u64;
+
+        let timeout = match packet.kind {
+            Kind::Tcp { control, ack } => {
+                let state = Self::tcp_transition(entry, dir, control, ack);
+                if entry.tcp_state != Some(state) {
+                    net_trace!("conntrack: {:?} {:?}", original, state);
+                    entry.tcp_state = Some(state);
+                }
+                match state {
+                    TcpState::SynSent => timeouts.tcp_syn_sent,
+                    TcpState::SynReceived => timeouts.tcp_syn_received,
+                    TcpState::Established => timeouts.tcp_established,
+                    TcpState::FinWait => timeouts.tcp_fin_wait,
+                    TcpState::TimeWait => timeouts.tcp_time_wait,
+                    TcpState::Close => timeouts.tcp_close,
+                }
+            }
+            Kind::Udp if entry.replied => timeouts.udp_replied,
+            Kind::Udp => timeouts.udp,
+            _ => timeouts.icmp,
+        };
+        entry.expires_at = timestamp + timeout;
+    }
+
+    /// Create the connection started by a packet, replacing a closed one.
+    fn insert(&mut self, packet: &Tracked, timestamp: Instant) {
+        if self.capacity == 0 {
+            return;
+        }
+        if !self.connections.contains_key(&packet.tuple) && self.connections.len() >= self.capacity
+        {
+            self.connections
+                .retain(|_, entry| timestamp < entry.expires_at);
+            if self.connections.len() >= self.capacity {
+                let oldest = self
+                    .connections
+                    .iter()
+                    .min_by_key(|(_, entry)| entry.expires_at)
+                    .map(|(tuple, _)| *tuple);
+                if let Some(tuple) = oldest {
+                    net_debug!("conntrack: table full, evicting {:?}", tuple);
+                    self.connections.remove(&tuple);
+                }
+            }
+        }
+
+        net_trace!("conntrack: new {:?}", packet.tuple);
+        self.connections.insert(
+            packet.tuple,
+            Entry {
+                tcp_state: None,
+                fin: [false; 2],
+                replied: false,
+                original: Counters::default(),
+                reply: Counters::default(),
+                expires_at: timestamp,
+            },
+        );
+    }
+
+    /// Return the state of a TCP connection after a segment went through.
+    fn tcp_transition(entry: &mut Entry, dir: Dir, control: TcpControl, ack: bool) -> TcpState {
+        let Some(state) = entry.tcp_state else {
+            return TcpState::SynSent;
+        };
+        match (state, dir, control) {
+            (_, _, TcpControl::Rst) => TcpState::Close,
+            (TcpState::SynSent, Dir::Reply, TcpControl::Syn) if ack => TcpState::SynReceived,
+            (TcpState::SynReceived, Dir::Original, _) if ack && control != TcpControl::Syn => {
+                TcpState::Established
+            }
+            (TcpState::Established | TcpState::FinWait, _, TcpControl::Fin) => {
+                entry.fin[dir as usize] = true;
+                if entry.fin == [true; 2] {
+                    TcpState::TimeWait
+                } else {
+                    TcpState::FinWait
+                }
+            }
+            (state, _, _) => state,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::wire::UdpRepr;
+
+    fn udp(src_port: u16) -> Tracked {
+        let ipv4_repr = Ipv4Repr {
+            src_addr: Ipv4Address::new(192, 168, 1, 1),
+            dst_addr: Ipv4Address::new(192, 168, 1, 2),
+            next_header: IpProtocol::Udp,
+            payload_len: 8,
+            hop_limit: 64,
+        };
+        let udp_repr = UdpRepr {
+            src_port,
+            dst_port: 53,
+        };
+        Tracked::from_payload(&ipv4_repr, &IpPayload::Udp(udp_repr, &[])).unwrap()
+    }
+
+    #[test]
+    fn test_zero_capacity() {
+        let mut conntrack = Conntrack::with_capacity(0);
+        conntrack.track(&udp(1000), Instant::ZERO);
+        assert_eq!(conntrack.connections(Instant::ZERO).count(), 0);
+    }
+}
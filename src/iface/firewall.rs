@@ -0,0 +1,305 @@
+use crate::config::IFACE_MAX_FIREWALL_RULE_COUNT;
+use crate::wire::{Icmpv4Message, IpProtocol, Ipv4Address, Ipv4Cidr, Ipv4Repr};
+
+use super::conntrack::Status;
+use super::packet::IpPayload;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RuleTableFull;
+
+impl core::fmt::Display for RuleTableFull {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "Rule table full")
+    }
+}
+
+impl std::error::Error for RuleTableFull {}
+
+/// The direction of the packets a chain of rules applies to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Chain {
+    /// Packets received by the interface and addressed to it.
+    Input,
+    /// Packets sent by the interface.
+    Output,
+}
+
+/// What to do with a packet matching a rule.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Action {
+    /// Let the packet through.
+    Accept,
+    /// Silently discard the packet.
+    Drop,
+    /// Discard the packet, answering with an ICMP "communication administratively
+    /// prohibited" error.
+    ///
+    /// This behaves exactly like `Drop` in two cases:
+    ///
+    /// - on the [`Output`](Chain::Output) chain, where no error is reported to the
+    ///   sending socket;
+    /// - for received ICMP errors, which are never answered with another error.
+    Reject,
+}
+
+/// A packet filter rule.
+///
+/// Fields set to `None` match any packet. Port constraints only match UDP and TCP
+/// packets, the ICMP type constraint only matches ICMP packets, and the state
+/// constraint only matches packets followed by the [connection tracker](super::Conntrack).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rule {
+    pub src_cidr: Option<Ipv4Cidr>,
+    pub dst_cidr: Option<Ipv4Cidr>,
+    pub protocol: Option<IpProtocol>,
+    pub src_port: Option<u16>,
+    pub dst_port: Option<u16>,
+    pub icmp_type: Option<Icmpv4Message>,
+    /// How the packet relates to the tracked connections, e.g.
+    /// `Some(Status::Established)` to accept the traffic of accepted connections.
+    pub state: Option<Status>,
+    pub action: Action,
+}
+
+impl Rule {
+    /// Returns a rule applying `action` to all packets.
+    pub const fn new(action: Action) -> Rule {
+        Rule {
+            src_cidr: None,
+            dst_cidr: None,
+            protocol: None,
+            src_port: None,
+            dst_port: None,
+            icmp_type: None,
+            state: None,
+            action,
+        }
+    }
+
+    fn matches(&self, flow: &Flow) -> bool {
+        fn matches<T: PartialEq>(constraint: Option<T>, value: Option<T>) -> bool {
+            constraint.is_none() || constraint == value
+        }
+
+        self.src_cidr
+            .map_or(true, |cidr| cidr.contains_addr(&flow.src_addr))
+            && self
+                .dst_cidr
+                .map_or(true, |cidr| cidr.contains_addr(&flow.dst_addr))
+            && matches(self.protocol, Some(flow.protocol))
+            && matches(self.src_port, flow.src_port)
+            && matches(self.dst_port, flow.dst_port)
+            && matches(self.icmp_type, flow.icmp_type)
+            && matches(self.state, flow.state)
+    }
+}
+
+/// The fields of a packet that rules match on.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Flow {
+    src_addr: Ipv4Address,
+    dst_addr: Ipv4Address,
+    protocol: IpProtocol,
+    src_port: Option<u16>,
+    dst_port: Option<u16>,
+    icmp_type: Option<Icmpv4Message>,
+    state: Option<Status>,
+}
+
+impl Flow {
+    /// Extract the flow of a received packet.
+    pub(crate) fn parse(ipv4_repr: &Ipv4Repr, payload: &[u8]) -> Flow {
+        let mut flow = Flow::new(ipv4_repr);
+        match ipv4_repr.next_header {
+            IpProtocol::Udp | IpProtocol::Tcp if payload.len() >= 4 => {
+                flow.src_port = Some(u16::from_be_bytes([payload[0], payload[1]]));
+                flow.dst_port = Some(u16::from_be_bytes([payload[2], payload[3]]));
+            }
+            IpProtocol::Icmp if !payload.is_empty() => {
+                flow.icmp_type = Some(Icmpv4Message::from(payload[0]));
+            }
+            _ => (),
+        }
+        flow
+    }
+
+    /// Extract the flow of a packet about to be sent.
+    pub(crate) fn from_payload(ipv4_repr: &Ipv4Repr, payload: &IpPayload) -> Flow {
+        match payload {
+            IpPayload::Icmpv4(icmp_repr) => Flow {
+                icmp_type: Some(icmp_repr.msg_type()),
+                ..Flow::new(ipv4_repr)
+            },
+            IpPayload::Udp(udp_repr, _) | IpPayload::Dhcpv4(udp_repr, _) => Flow {
+                src_port: Some(udp_repr.src_port),
+                dst_port: Some(udp_repr.dst_port),
+                ..Flow::new(ipv4_repr)
+            },
+            IpPayload::Raw(payload) => Flow::parse(ipv4_repr, payload),
+            IpPayload::Icmpv6(_) | IpPayload::Igmp(_) => Flow::new(ipv4_repr),
+        }
+    }
+
+    fn new(ipv4_repr: &Ipv4Repr) -> Flow {
+        Flow {
+            src_addr: ipv4_repr.src_addr,
+            dst_addr: ipv4_repr.dst_addr,
+            protocol: ipv4_repr.next_header,
+            src_port: None,
+            dst_port: None,
+            icmp_type: None,
+            state: None,
+        }
+    }
+
+    /// Set how the packet relates to the tracked connections.
+    pub(crate) fn set_state(&mut self, state: Status) {
+        self.state = Some(state);
+    }
+
+    /// Check whether the packet is an ICMP error, which must not be answered with one.
+    pub(crate) fn is_icmp_error(&self) -> bool {
+        matches!(
+            self.icmp_type,
+            Some(
+                Icmpv4Message::DstUnreachable
+                    | Icmpv4Message::Redirect
+                    | Icmpv4Message::TimeExceeded
+                    | Icmpv4Message::ParamProblem
+            )
+        )
+    }
+}
+
+/// An ordered list of rules, and how many packets each one matched.
+#[derive(Debug)]
+struct Table {
+    rules: Vec<(Rule, u64)>,
+    policy: Action,
+}
+
+impl Table {
+    fn new() -> Self {
+        Table {
+            rules: Vec::new(),
+            policy: Action::Accept,
+        }
+    }
+}
+
+/// An IPv4 packet filter.
+///
+/// Every packet is checked against the rules of its chain in order, and the action of
+/// the first matching rule applies. Rules may match on the state of the packet's
+/// connection, as classified by the connection tracker. Packets matching no rule get
+/// the policy of the chain, which is to accept them unless changed.
+#[derive(Debug)]
+pub struct Firewall {
+    input: Table,
+    output: Table,
+    max_rules: usize,
+}
+
+impl Firewall {
+    /// Creates a firewall accepting all packets.
+    pub fn new() -> Self {
+        Self::with_max_rules(IFACE_MAX_FIREWALL_RULE_COUNT)
+    }
+
+    /// Creates a firewall accepting all packets, holding up to `max_rules` rules
+    /// per chain.
+    pub fn with_max_rules(max_rules: usize) -> Self {
+        Self {
+            input: Table::new(),
+            output: Table::new(),
+            max_rules,
+        }
+    }
+
+    fn table(&self, chain: Chain) -> &Table {
+        match chain {
+            Chain::Input => &self.input,
+            Chain::Output => &self.output,
+        }
+    }
+
+    fn table_mut(&mut self, chain: Chain) -> &mut Table {
+        match chain {
+            Chain::Input => &mut self.input,
+            Chain::Output => &mut self.output,
+        }
+    }
+
+    /// Return the action applied to the packets of `chain` matching no rule.
+    pub fn policy(&self, chain: Chain) -> Action {
+        self.table(chain).policy
+    }
+
+    /// Set the action applied to the packets of `chain` matching no rule.
+    pub fn set_policy(&mut self, chain: Chain, policy: Action) {
+        self.table_mut(chain).policy = policy;
+    }
+
+    /// Return the rules of `chain` in order, with the number of packets each one matched.
+    pub fn rules(&self, chain: Chain) -> impl Iterator<Item = (&Rule, u64)> + '_ {
+        self.table(chain)
+            .rules
+            .iter()
+            .map(|(rule, hits)| (rule, *hits))
+    }
+
+    /// Append a rule to `chain`.
+    pub fn push(&mut self, chain: Chain, rule: Rule) -> Result<(), RuleTableFull> {
+        let len = self.table(chain).rules.len();
+        self.insert(chain, len, rule)
+    }
+
+    /// Insert a rule into `chain` at position `index`.
+    ///
+    /// # Panics
+    /// This function panics if `index` is greater than the number of rules in `chain`.
+    pub fn insert(&mut self, chain: Chain, index: usize, rule: Rule) -> Result<(), RuleTableFull> {
+        let max_rules = self.max_rules;
+        let rules = &mut self.table_mut(chain).rules;
+        if rules.len() >= max_rules {
+            return Err(RuleTableFull);
+        }
+        rules.insert(index, (rule, 0));
+        Ok(())
+    }
+
+    /// Remove the rule at position `index` from `chain`, if any.
+    pub fn remove(&mut self, chain: Chain, index: usize) -> Option<Rule> {
+        let rules = &mut self.table_mut(chain).rules;
+        (index < rules.len()).then(|| rules.remove(index).0)
+    }
+
+    /// Remove all the rules of `chain`.
+    pub fn clear(&mut self, chain: Chain) {
+        self.table_mut(chain).rules.clear()
+    }
+
+    /// Reset the hit counters of all rules.
+    pub fn reset_counters(&mut self) {
+        for (_, hits) in self
+            .input
+            .rules
+            .iter_mut()
+            .chain(self.output.rules.iter_mut())
+        {
+            *hits = 0;
+        }
+    }
+
+    /// Return the action to apply to a packet.
+    pub(crate) fn filter(&mut self, chain: Chain, flow: &Flow) -> Action {
+        let table = self.table_mut(chain);
+        match table.rules.iter_mut().find(|(rule, _)| rule.matches(flow)) {
+            Some((rule, hits)) => {
+                *hits += 1;
+                rule.action
+            }
+            None => table.policy,
+        }
+    }
+}
@@ -22,6 +22,10 @@ impl InterfaceInner {
             EthernetProtocol::Arp => self.process_arp(self.now, &eth_frame),
             EthernetProtocol::Ipv4 => {
                 let ipv4_packet = check!(Ipv4Packet::new_checked(eth_frame.payload()));
+                if ipv4_packet.src_addr().is_loopback() || ipv4_packet.dst_addr().is_loopback() {
+                    net_debug!("dropping loopback-addressed packet received on the wire");
+                    return None;
+                }
 
                 self.process_ipv4(
                     sockets,
@@ -32,6 +36,16 @@ impl InterfaceInner {
                 )
                 .map(EthernetPacket::Ip)
             }
+            EthernetProtocol::Ipv6 => {
+                let ipv6_packet = check!(Ipv6Packet::new_checked(eth_frame.payload()));
+                if ipv6_packet.src_addr().is_loopback() || ipv6_packet.dst_addr().is_loopback() {
+                    net_debug!("dropping loopback-addressed packet received on the wire");
+                    return None;
+                }
+
+                self.process_ipv6(sockets, meta, eth_frame.src_addr().into(), &ipv6_packet)
+                    .map(EthernetPacket::Ip)
+            }
             // Drop all other traffic.
             _ => None,
         }
@@ -1,19 +1,68 @@
 use super::*;
 
+use crate::socket::udp::IcmpErrorKind;
+
+/// Lifetime of the host routes installed by ICMP redirects.
+const REDIRECT_ROUTE_LIFETIME: Duration = Duration::from_secs(600);
+
+const MILLIS_PER_DAY: i64 = 24 * 60 * 60 * 1000;
+
+/// Smallest path MTU accepted from "fragmentation needed" errors (RFC 791, RFC 1191).
+const IPV4_MIN_PATH_MTU: usize = 68;
+
+/// Common MTU plateaus, to estimate the path MTU when a router doesn't report
+/// the next-hop MTU (RFC 1191 § 7).
+const MTU_PLATEAUS: [usize; 10] = [32000, 17914, 8166, 4352, 2002, 1492, 1006, 508, 296, 68];
+
 impl InterfaceInner {
     /// Get an IPv4 source address based on a destination address.
     ///
-    /// **NOTE**: unlike for IPv6, no specific selection algorithm is implemented. The first IPv4
-    /// address from the interface is returned.
+    /// On-link destinations are reached from the address of their network. Routed
+    /// destinations are reached from the preferred source of their route, or else
+    /// from the address of the gateway's network. Otherwise, the first IPv4 address
+    /// from the interface is returned.
     #[allow(unused)]
-    pub(crate) fn get_source_address_ipv4(&self, _dst_addr: &Ipv4Address) -> Option<Ipv4Address> {
-        for cidr in self.ip_addrs.iter() {
-            #[allow(irrefutable_let_patterns)] // if only ipv4 is enabled
-            if let IpCidr::Ipv4(cidr) = cidr {
-                return Some(cidr.address());
+    pub(crate) fn get_source_address_ipv4(&self, dst_addr: &Ipv4Address) -> Option<Ipv4Address> {
+        // Packets looped back to ourselves are sent from the address they are sent to.
+        if dst_addr.is_loopback() {
+            return Some(Ipv4Address::LOCALHOST);
+        }
+        if self.has_ip_addr(*dst_addr) {
+            return Some(*dst_addr);
+        }
+
+        let ipv4_cidrs = || {
+            self.ip_addrs.iter().filter_map(|cidr| match cidr {
+                IpCidr::Ipv4(cidr) => Some(*cidr),
+                #[allow(unreachable_patterns)] // if only ipv4 is enabled
+                _ => None,
+            })
+        };
+        let network_addr = |addr: &Ipv4Address| {
+            ipv4_cidrs()
+                .find(|cidr| cidr.contains_addr(addr))
+                .map(|cidr| cidr.address())
+        };
+
+        if let Some(addr) = network_addr(dst_addr) {
+            return Some(addr);
+        }
+        if dst_addr.x_is_unicast() {
+            if let Some(route) = self.routes.lookup(&(*dst_addr).into(), None, 0, self.now) {
+                if let Some(IpAddress::Ipv4(pref_src)) = route.pref_src {
+                    if self.has_ip_addr(pref_src) {
+                        return Some(pref_src);
+                    }
+                }
+                if let IpAddress::Ipv4(gateway) = route.via_router {
+                    if let Some(addr) = network_addr(&gateway) {
+                        return Some(addr);
+                    }
+                }
             }
         }
-        None
+
+        ipv4_cidrs().next().map(|cidr| cidr.address())
     }
 
     /// Checks if an address is broadcast, taking into account ipv4 subnet-local
@@ -27,6 +76,7 @@ impl InterfaceInner {
             .iter()
             .filter_map(|own_cidr| match own_cidr {
                 IpCidr::Ipv4(own_ip) => Some(own_ip.broadcast()?),
+                IpCidr::Ipv6(_) => None,
             })
             .any(|broadcast_address| address == broadcast_address)
     }
@@ -40,7 +90,6 @@ impl InterfaceInner {
     pub fn ipv4_addr(&self) -> Option<Ipv4Address> {
         self.ip_addrs.iter().find_map(|addr| match *addr {
             IpCidr::Ipv4(cidr) => Some(cidr.address()),
-            #[allow(unreachable_patterns)]
             _ => None,
         })
     }
@@ -62,13 +111,41 @@ impl InterfaceInner {
 
         let ip_payload = ipv4_packet.payload();
 
-        let ip_repr = IpRepr::Ipv4(ipv4_repr);
+        // DHCP replies may be sent to the address being offered, so the DHCP socket
+        // sees them before the destination check and the Input chain.
+        {
+            use crate::socket::dhcpv4::Socket as Dhcpv4Socket;
 
-        let handled_by_raw_socket = self.raw_socket_filter(sockets, &ip_repr, ip_payload);
+            if ipv4_repr.next_header == IpProtocol::Udp
+                && matches!(self.caps.medium, Medium::Ethernet)
+            {
+                let udp_packet = check!(UdpPacket::new_checked(ip_payload));
+                if let Some(dhcp_socket) = sockets
+                    .items_mut()
+                    .find_map(|i| Dhcpv4Socket::downcast_mut(&mut i.socket))
+                {
+                    // First check for source and dest ports, then do `UdpRepr::parse` if they match.
+                    // This way we avoid validating the UDP checksum twice for all non-DHCP UDP packets (one here, one in `process_udp`)
+                    if udp_packet.src_port() == dhcp_socket.server_port
+                        && udp_packet.dst_port() == dhcp_socket.client_port
+                    {
+                        let udp_repr = check!(UdpRepr::parse(
+                            &udp_packet,
+                            &ipv4_repr.src_addr.into(),
+                            &ipv4_repr.dst_addr.into(),
+                            &self.caps.checksum
+                        ));
+                        dhcp_socket.process(self, &ipv4_repr, &udp_repr, udp_packet.payload());
+                        return None;
+                    }
+                }
+            }
+        }
 
         if !self.has_ip_addr(ipv4_repr.dst_addr)
             && !self.has_multicast_group(ipv4_repr.dst_addr)
             && !self.is_broadcast_v4(ipv4_repr.dst_addr)
+            && !ipv4_repr.dst_addr.is_loopback()
         {
             // Ignore IP packets not directed at us, or broadcast, or any of the multicast groups.
             // If AnyIP is enabled, also check if the packet is routed locally.
@@ -88,8 +165,8 @@ impl InterfaceInner {
 
             if self
                 .routes
-                .lookup(&IpAddress::Ipv4(ipv4_repr.dst_addr), self.now)
-                .map_or(true, |router_addr| !self.has_ip_addr(router_addr))
+                .lookup(&IpAddress::Ipv4(ipv4_repr.dst_addr), None, 0, self.now)
+                .map_or(true, |route| !self.has_ip_addr(route.via_router))
             {
                 net_trace!("Rejecting IPv4 packet; no matching routes");
 
@@ -97,6 +174,39 @@ impl InterfaceInner {
             }
         }
 
+        // Only the packets for us go through the Input chain.
+        let mut flow = Flow::parse(&ipv4_repr, ip_payload);
+        let tracked = Tracked::parse(&ipv4_repr, ip_payload);
+        if let Some(tracked) = &tracked {
+            flow.set_state(self.conntrack.classify(tracked, self.now));
+        }
+        match self.firewall.filter(Chain::Input, &flow) {
+            Action::Accept => {
+                if let Some(tracked) = &tracked {
+                    self.conntrack.track(tracked, self.now);
+                }
+            }
+            Action::Reject if !flow.is_icmp_error() => {
+                net_trace!("firewall: rejecting packet from {}", ipv4_repr.src_addr);
+                let payload_len =
+                    icmp_reply_payload_len(ip_payload.len(), IPV4_MIN_MTU, ipv4_repr.buffer_len());
+                let icmp_reply_repr = Icmpv4Repr::DstUnreachable {
+                    reason: Icmpv4DstUnreachable::CommProhibited,
+                    header: ipv4_repr,
+                    data: &ip_payload[0..payload_len],
+                };
+                return self.icmpv4_reply(ipv4_repr, icmp_reply_repr);
+            }
+            Action::Reject | Action::Drop => {
+                net_trace!("firewall: dropping packet from {}", ipv4_repr.src_addr);
+                return None;
+            }
+        }
+
+        let ip_repr = IpRepr::Ipv4(ipv4_repr);
+
+        let handled_by_raw_socket = self.raw_socket_filter(sockets, &ip_repr, ip_payload);
+
         if self.is_unicast_v4(ipv4_repr.dst_addr) {
             self.neighbor_cache.reset_expiry_if_existing(
                 IpAddress::Ipv4(ipv4_repr.src_addr),
@@ -107,10 +217,14 @@ impl InterfaceInner {
 
         match ipv4_repr.next_header {
             IpProtocol::Icmp => self.process_icmpv4(sockets, ipv4_repr, ip_payload),
-            // TODO:
-            // IpProtocol::Udp => {
-            //     self.process_udp(sockets, meta, handled_by_raw_socket, ip_repr, ip_payload)
-            // }
+            IpProtocol::Igmp => self.process_igmp(ipv4_repr, ip_payload),
+            IpProtocol::Udp => self.process_udp(
+                sockets,
+                meta,
+                handled_by_raw_socket,
+                ipv4_repr.into(),
+                ip_payload,
+            ),
             // IpProtocol::Tcp => self.process_tcp(sockets, ip_repr, ip_payload),
             _ if handled_by_raw_socket => None,
             _ => {
@@ -194,7 +308,7 @@ impl InterfaceInner {
 
     pub(super) fn process_icmpv4<'frame>(
         &mut self,
-        _sockets: &mut SocketSet,
+        sockets: &mut SocketSet,
         ip_repr: Ipv4Repr,
         ip_payload: &'frame [u8],
     ) -> Option<Packet<'frame>> {
@@ -203,7 +317,7 @@ impl InterfaceInner {
 
         let mut handled_by_icmp_socket = false;
 
-        for icmp_socket in _sockets
+        for icmp_socket in sockets
             .items_mut()
             .filter_map(|i| icmp::Socket::downcast_mut(&mut i.socket))
         {
@@ -213,6 +327,25 @@ impl InterfaceInner {
             }
         }
 
+        // Report errors to the socket that sent the offending datagram.
+        let error = match icmp_repr {
+            Icmpv4Repr::DstUnreachable {
+                reason,
+                header,
+                data,
+            } => Some((header, data, reason.into())),
+            Icmpv4Repr::TimeExceeded { header, data, .. } => {
+                Some((header, data, IcmpErrorKind::TimeExceeded))
+            }
+            Icmpv4Repr::ParamProblem { header, data, .. } => {
+                Some((header, data, IcmpErrorKind::ParameterProblem))
+            }
+            _ => None,
+        };
+        if let Some((header, data, kind)) = error {
+            self.process_udp_error(sockets, ip_repr.src_addr.into(), header.into(), data, kind);
+        }
+
         match icmp_repr {
             // Respond to echo requests.
             Icmpv4Repr::EchoRequest {
@@ -231,6 +364,59 @@ impl InterfaceInner {
             // Ignore any echo replies.
             Icmpv4Repr::EchoReply { .. } => None,
 
+            // Respond to timestamp requests.
+            Icmpv4Repr::TimestampRequest {
+                ident,
+                seq_no,
+                originate,
+                ..
+            } => {
+                // There is no wall clock, so the time is not relative to midnight UT.
+                // RFC 792 requires flagging such a timestamp with the high-order bit.
+                let now = (self.now.total_millis() % MILLIS_PER_DAY) as u32 | 0x8000_0000;
+                let icmp_reply_repr = Icmpv4Repr::TimestampReply {
+                    ident,
+                    seq_no,
+                    originate,
+                    receive: now,
+                    transmit: now,
+                };
+                self.icmpv4_reply(ip_repr, icmp_reply_repr)
+            }
+
+            Icmpv4Repr::DstUnreachable {
+                reason: Icmpv4DstUnreachable::FragRequired,
+                header,
+                ..
+            } => {
+                if self.has_ip_addr(header.src_addr) {
+                    // The header only has the length of the data quoted by the router,
+                    // the plateau search needs the length of the original datagram.
+                    let total_len =
+                        Ipv4Packet::new_unchecked(icmp_packet.data()).total_len() as usize;
+                    let mtu = match icmp_packet.next_hop_mtu() as usize {
+                        mtu if mtu != 0 && mtu < total_len => mtu,
+                        _ => MTU_PLATEAUS
+                            .iter()
+                            .copied()
+                            .find(|&plateau| plateau < total_len)
+                            .unwrap_or(IPV4_MIN_PATH_MTU),
+                    };
+                    self.update_path_mtu(header.dst_addr.into(), mtu.max(IPV4_MIN_PATH_MTU));
+                }
+                None
+            }
+
+            Icmpv4Repr::Redirect {
+                gateway,
+                header,
+                data,
+                ..
+            } => {
+                self.process_redirect(ip_repr.src_addr, gateway, &header, data);
+                None
+            }
+
             // Don't report an error if a packet with unknown type
             // has been handled by an ICMP socket
             _ if handled_by_icmp_socket => None,
@@ -240,6 +426,66 @@ impl InterfaceInner {
         }
     }
 
+    /// Install a host route for a redirect, after checking it is plausible (RFC 1122 § 3.2.2.2).
+    ///
+    /// Network redirects are treated as host redirects, as recommended by RFC 1122.
+    fn process_redirect(
+        &mut self,
+        router: Ipv4Address,
+        gateway: Ipv4Address,
+        header: &Ipv4Repr,
+        data: &[u8],
+    ) {
+        if !self.accept_redirects {
+            net_debug!("ignoring redirect from {}", router);
+            return;
+        }
+
+        let dst_addr = header.dst_addr;
+        // The redirected datagram must have been sent by us.
+        if !self.has_ip_addr(header.src_addr) || !dst_addr.x_is_unicast() {
+            net_debug!("redirect from {}: not for a datagram of ours", router);
+            return;
+        }
+        // Only the gateway currently used for the destination may redirect us. The
+        // quoted datagram tells which of the equal-cost routes it took.
+        let flow_hash = Packet::new_ipv4(*header, IpPayload::Raw(data)).flow_hash();
+        if self.route(
+            &dst_addr.into(),
+            Some(&header.src_addr.into()),
+            flow_hash,
+            self.now,
+        ) != Some(router.into())
+            || self.in_same_network(&dst_addr.into())
+        {
+            net_debug!(
+                "redirect from {}: not the current gateway for {}",
+                router,
+                dst_addr
+            );
+            return;
+        }
+        // The new gateway must be directly reachable.
+        if !gateway.x_is_unicast()
+            || !self.in_same_network(&gateway.into())
+            || self.has_ip_addr(gateway)
+        {
+            net_debug!("redirect from {}: bad gateway {}", router, gateway);
+            return;
+        }
+
+        net_debug!("redirect: {} via {}", dst_addr, gateway);
+        let expires_at = self.now + REDIRECT_ROUTE_LIFETIME;
+        self.routes.remove_expired(self.now);
+        if self
+            .routes
+            .add_ipv4_host_route(dst_addr, gateway, Some(expires_at))
+            .is_err()
+        {
+            net_debug!("redirect: route table full");
+        }
+    }
+
     pub(super) fn icmpv4_reply<'frame, 'icmp: 'frame>(
         &self,
         ipv4_repr: Ipv4Repr,
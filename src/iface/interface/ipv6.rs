@@ -0,0 +1,640 @@
+use super::*;
+
+use crate::socket::udp::IcmpErrorKind;
+
+/// Enum used for the process_hopbyhop function. When discarding a packet the processing of
+/// the IP packet stops, in other cases the processing of the IP packet can continue.
+#[allow(clippy::large_enum_variant)]
+enum HopByHopResponse<'frame> {
+    /// Continue processing the IPv6 packet.
+    Continue((IpProtocol, &'frame [u8])),
+    /// Discard the packet.
+    Discard(Option<Packet<'frame>>),
+}
+
+// We implement `Default` such that we can use the check! macro.
+impl Default for HopByHopResponse<'_> {
+    fn default() -> Self {
+        Self::Discard(None)
+    }
+}
+
+impl InterfaceInner {
+    /// Return the IPv6 address that is a candidate source address for the given destination
+    /// address, based on RFC 6724.
+    ///
+    /// # Panics
+    /// This function panics if the destination address is unspecified.
+    pub(crate) fn get_source_address_ipv6(&self, dst_addr: &Ipv6Address) -> Ipv6Address {
+        assert!(!dst_addr.is_unspecified());
+
+        // See RFC 6724 Section 4: Candidate source address
+        fn is_candidate_source_address(dst_addr: &Ipv6Address, src_addr: &Ipv6Address) -> bool {
+            // For all multicast and link-local destination addresses, the candidate address MUST
+            // only be an address from the same link.
+            if dst_addr.is_link_local() && !src_addr.is_link_local() {
+                return false;
+            }
+
+            if dst_addr.is_multicast()
+                && matches!(dst_addr.x_multicast_scope(), Ipv6MulticastScope::LinkLocal)
+                && src_addr.is_multicast()
+                && !matches!(src_addr.x_multicast_scope(), Ipv6MulticastScope::LinkLocal)
+            {
+                return false;
+            }
+
+            // Unspecified addresses and multicast address can not be in the candidate source address
+            // list. Except when the destination multicast address has a link-local scope, then the
+            // source address can also be link-local multicast.
+            if src_addr.is_unspecified() || src_addr.is_multicast() {
+                return false;
+            }
+
+            true
+        }
+
+        // See RFC 6724 Section 2.2: Common Prefix Length
+        fn common_prefix_length(dst_addr: &Ipv6Cidr, src_addr: &Ipv6Address) -> usize {
+            let addr = dst_addr.address();
+            let mut bits = 0;
+            for (l, r) in addr.octets().iter().zip(src_addr.octets().iter()) {
+                if l == r {
+                    bits += 8;
+                } else {
+                    bits += (l ^ r).leading_zeros();
+                    break;
+                }
+            }
+
+            bits = bits.min(dst_addr.prefix_len() as u32);
+
+            bits as usize
+        }
+
+        // If the destination address is a loopback address, or when there are no IPv6 addresses in
+        // the interface, then the loopback address is the only candidate source address.
+        if dst_addr.is_loopback()
+            || self
+                .ip_addrs
+                .iter()
+                .filter(|a| matches!(a, IpCidr::Ipv6(_)))
+                .count()
+                == 0
+        {
+            return Ipv6Address::LOCALHOST;
+        }
+
+        let mut candidate = self
+            .ip_addrs
+            .iter()
+            .find_map(|a| match a {
+                IpCidr::Ipv4(_) => None,
+                IpCidr::Ipv6(a) => Some(a),
+            })
+            .unwrap(); // NOTE: we check above that there is at least one IPv6 address.
+
+        for addr in self.ip_addrs.iter().filter_map(|a| match a {
+            IpCidr::Ipv4(_) => None,
+            IpCidr::Ipv6(a) => Some(a),
+        }) {
+            if !is_candidate_source_address(dst_addr, &addr.address()) {
+                continue;
+            }
+
+            // Rule 1: prefer the address that is the same as the output destination address.
+            if candidate.address() != *dst_addr && addr.address() == *dst_addr {
+                candidate = addr;
+            }
+
+            // Rule 2: prefer appropriate scope.
+            if (candidate.address().x_multicast_scope() as u8)
+                < (addr.address().x_multicast_scope() as u8)
+            {
+                if (candidate.address().x_multicast_scope() as u8)
+                    < (dst_addr.x_multicast_scope() as u8)
+                {
+                    candidate = addr;
+                }
+            } else if (addr.address().x_multicast_scope() as u8)
+                > (dst_addr.x_multicast_scope() as u8)
+            {
+                candidate = addr;
+            }
+
+            // Rule 3: avoid deprecated addresses.
+            let candidate_deprecated = self.slaac.is_deprecated(&candidate.address(), self.now);
+            let addr_deprecated = self.slaac.is_deprecated(&addr.address(), self.now);
+            if candidate_deprecated && !addr_deprecated {
+                candidate = addr;
+                continue;
+            } else if addr_deprecated && !candidate_deprecated {
+                continue;
+            }
+
+            // Rule 4: prefer home addresses (TODO)
+            // Rule 5: prefer outgoing interfaces (TODO)
+            // Rule 5.5: prefer addresses in a prefix advertises by the next-hop (TODO).
+            // Rule 6: prefer matching label (TODO)
+            // Rule 7: prefer temporary addresses (TODO)
+            // Rule 8: use longest matching prefix
+            if common_prefix_length(candidate, dst_addr) < common_prefix_length(addr, dst_addr) {
+                candidate = addr;
+            }
+        }
+
+        candidate.address()
+    }
+
+    /// Determine if the given `Ipv6Address` is the solicited node
+    /// multicast address for a IPv6 addresses assigned to the interface.
+    /// See [RFC 4291 § 2.7.1] for more details.
+    ///
+    /// [RFC 4291 § 2.7.1]: https://tools.ietf.org/html/rfc4291#section-2.7.1
+    pub fn has_solicited_node(&self, addr: Ipv6Address) -> bool {
+        addr.is_solicited_node_multicast()
+            && self.ip_addrs.iter().any(|cidr| match *cidr {
+                IpCidr::Ipv6(cidr)
+                    if cidr.address().x_is_unicast()
+                        && cidr.address() != Ipv6Address::LOCALHOST =>
+                {
+                    cidr.address().solicited_node() == addr
+                }
+                _ => false,
+            })
+    }
+
+    /// Get the first IPv6 address if present.
+    pub fn ipv6_addr(&self) -> Option<Ipv6Address> {
+        self.ip_addrs.iter().find_map(|addr| match *addr {
+            IpCidr::Ipv6(cidr) => Some(cidr.address()),
+            _ => None,
+        })
+    }
+
+    pub(super) fn process_ipv6<'frame>(
+        &mut self,
+        sockets: &mut SocketSet,
+        #[allow(unused)] meta: PacketMeta,
+        source_hardware_addr: HardwareAddress,
+        ipv6_packet: &Ipv6Packet<&'frame [u8]>,
+    ) -> Option<Packet<'frame>> {
+        let ipv6_repr = check!(Ipv6Repr::parse(ipv6_packet));
+
+        // The unspecified source address is only used by ICMPv6 Neighbor Solicitations
+        // during Duplicate Address Detection, see RFC 4862 § 5.4.2.
+        if ipv6_repr.src_addr.is_multicast()
+            || (ipv6_repr.src_addr.is_unspecified() && ipv6_repr.next_header != IpProtocol::Icmpv6)
+        {
+            // Discard packets with non-unicast source addresses.
+            net_debug!("non-unicast source address");
+            return None;
+        }
+
+        let (next_header, ip_payload) = if ipv6_repr.next_header == IpProtocol::HopByHop {
+            match self.process_hopbyhop(ipv6_repr, ipv6_packet.payload()) {
+                HopByHopResponse::Discard(e) => return e,
+                HopByHopResponse::Continue(next) => next,
+            }
+        } else {
+            (ipv6_repr.next_header, ipv6_packet.payload())
+        };
+
+        if !self.has_ip_addr(ipv6_repr.dst_addr)
+            && !self.has_multicast_group(ipv6_repr.dst_addr)
+            && !ipv6_repr.dst_addr.is_loopback()
+        {
+            if !self.any_ip {
+                net_trace!("Rejecting IPv6 packet; any_ip=false");
+                return None;
+            }
+
+            if !ipv6_repr.dst_addr.x_is_unicast() {
+                net_trace!(
+                    "Rejecting IPv6 packet; {} is not a unicast address",
+                    ipv6_repr.dst_addr
+                );
+                return None;
+            }
+
+            if self
+                .routes
+                .lookup(&IpAddress::Ipv6(ipv6_repr.dst_addr), None, 0, self.now)
+                .map_or(true, |route| !self.has_ip_addr(route.via_router))
+            {
+                net_trace!("Rejecting IPv6 packet; no matching routes");
+
+                return None;
+            }
+        }
+
+        let handled_by_raw_socket = self.raw_socket_filter(sockets, &ipv6_repr.into(), ip_payload);
+
+        if ipv6_repr.dst_addr.x_is_unicast() {
+            self.neighbor_cache.reset_expiry_if_existing(
+                IpAddress::Ipv6(ipv6_repr.src_addr),
+                source_hardware_addr,
+                self.now,
+            );
+        }
+
+        self.process_nxt_hdr(
+            sockets,
+            meta,
+            ipv6_repr,
+            next_header,
+            handled_by_raw_socket,
+            ip_payload,
+        )
+    }
+
+    fn process_hopbyhop<'frame>(
+        &mut self,
+        ipv6_repr: Ipv6Repr,
+        ip_payload: &'frame [u8],
+    ) -> HopByHopResponse<'frame> {
+        let param_problem = || {
+            let payload_len =
+                icmp_reply_payload_len(ip_payload.len(), IPV6_MIN_MTU, ipv6_repr.buffer_len());
+            self.icmpv6_reply(
+                ipv6_repr,
+                Icmpv6Repr::ParamProblem {
+                    reason: Icmpv6ParamProblem::UnrecognizedOption,
+                    pointer: ipv6_repr.buffer_len() as u32,
+                    header: ipv6_repr,
+                    data: &ip_payload[0..payload_len],
+                },
+            )
+        };
+
+        let ext_hdr = check!(Ipv6ExtHeader::new_checked(ip_payload));
+        let ext_repr = check!(Ipv6ExtHeaderRepr::parse(&ext_hdr));
+        let hbh_hdr = check!(Ipv6HopByHopHeader::new_checked(ext_repr.data));
+        let hbh_repr = check!(Ipv6HopByHopRepr::parse(&hbh_hdr));
+
+        for opt_repr in &hbh_repr.options {
+            match opt_repr {
+                Ipv6OptionRepr::Pad1 | Ipv6OptionRepr::PadN(_) | Ipv6OptionRepr::RouterAlert(_) => {
+                }
+
+                Ipv6OptionRepr::Unknown { type_, .. } => {
+                    match Ipv6OptionFailureType::from(*type_) {
+                        Ipv6OptionFailureType::Skip => (),
+                        Ipv6OptionFailureType::Discard => {
+                            return HopByHopResponse::Discard(None);
+                        }
+                        Ipv6OptionFailureType::DiscardSendAll => {
+                            return HopByHopResponse::Discard(param_problem());
+                        }
+                        Ipv6OptionFailureType::DiscardSendUnicast => {
+                            if !ipv6_repr.dst_addr.is_multicast() {
+                                return HopByHopResponse::Discard(param_problem());
+                            } else {
+                                return HopByHopResponse::Discard(None);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        HopByHopResponse::Continue((
+            ext_repr.next_header,
+            &ip_payload[ext_repr.header_len() + ext_repr.data.len()..],
+        ))
+    }
+
+    /// Given the next header value forward the payload onto the correct process
+    /// function.
+    fn process_nxt_hdr<'frame>(
+        &mut self,
+        sockets: &mut SocketSet,
+        #[allow(unused)] meta: PacketMeta,
+        ipv6_repr: Ipv6Repr,
+        nxt_hdr: IpProtocol,
+        handled_by_raw_socket: bool,
+        ip_payload: &'frame [u8],
+    ) -> Option<Packet<'frame>> {
+        match nxt_hdr {
+            IpProtocol::Icmpv6 => self.process_icmpv6(sockets, ipv6_repr, ip_payload),
+            IpProtocol::Udp => self.process_udp(
+                sockets,
+                meta,
+                handled_by_raw_socket,
+                ipv6_repr.into(),
+                ip_payload,
+            ),
+            // IpProtocol::Tcp => self.process_tcp(sockets, ipv6_repr.into(), ip_payload),
+            _ if handled_by_raw_socket => None,
+            _ => {
+                // Send back as much of the original payload as we can.
+                let payload_len =
+                    icmp_reply_payload_len(ip_payload.len(), IPV6_MIN_MTU, ipv6_repr.buffer_len());
+                let icmp_reply_repr = Icmpv6Repr::ParamProblem {
+                    reason: Icmpv6ParamProblem::UnrecognizedNxtHdr,
+                    // The offending packet is after the IPv6 header.
+                    pointer: ipv6_repr.buffer_len() as u32,
+                    header: ipv6_repr,
+                    data: &ip_payload[0..payload_len],
+                };
+                self.icmpv6_reply(ipv6_repr, icmp_reply_repr)
+            }
+        }
+    }
+
+    pub(super) fn process_icmpv6<'frame>(
+        &mut self,
+        sockets: &mut SocketSet,
+        ip_repr: Ipv6Repr,
+        ip_payload: &'frame [u8],
+    ) -> Option<Packet<'frame>> {
+        let icmp_packet = check!(Icmpv6Packet::new_checked(ip_payload));
+        let icmp_repr = check!(Icmpv6Repr::parse(
+            &ip_repr.src_addr,
+            &ip_repr.dst_addr,
+            &icmp_packet,
+            &self.caps.checksum,
+        ));
+
+        if ip_repr.src_addr.is_unspecified()
+            && !matches!(
+                icmp_repr,
+                Icmpv6Repr::Ndisc(NdiscRepr::NeighborSolicit { .. })
+            )
+        {
+            net_debug!("non-unicast source address");
+            return None;
+        }
+
+        let mut handled_by_icmp_socket = false;
+
+        use crate::socket::icmp::Socket as IcmpSocket;
+        for icmp_socket in sockets
+            .items_mut()
+            .filter_map(|i| IcmpSocket::downcast_mut(&mut i.socket))
+        {
+            if icmp_socket.accepts_v6(self, &ip_repr, &icmp_repr) {
+                icmp_socket.process_v6(self, &ip_repr, &icmp_repr);
+                handled_by_icmp_socket = true;
+            }
+        }
+
+        // Report errors to the socket that sent the offending datagram.
+        let error = match icmp_repr {
+            Icmpv6Repr::DstUnreachable {
+                reason,
+                header,
+                data,
+            } => Some((header, data, reason.into())),
+            Icmpv6Repr::PktTooBig { header, data, .. } => {
+                Some((header, data, IcmpErrorKind::MessageTooLong))
+            }
+            Icmpv6Repr::TimeExceeded { header, data, .. } => {
+                Some((header, data, IcmpErrorKind::TimeExceeded))
+            }
+            Icmpv6Repr::ParamProblem { header, data, .. } => {
+                Some((header, data, IcmpErrorKind::ParameterProblem))
+            }
+            _ => None,
+        };
+        if let Some((header, data, kind)) = error {
+            self.process_udp_error(sockets, ip_repr.src_addr.into(), header.into(), data, kind);
+        }
+
+        match icmp_repr {
+            // Respond to echo requests.
+            Icmpv6Repr::EchoRequest {
+                ident,
+                seq_no,
+                data,
+            } => {
+                let icmp_reply_repr = Icmpv6Repr::EchoReply {
+                    ident,
+                    seq_no,
+                    data,
+                };
+                self.icmpv6_reply(ip_repr, icmp_reply_repr)
+            }
+
+            // Ignore any echo replies.
+            Icmpv6Repr::EchoReply { .. } => None,
+
+            Icmpv6Repr::PktTooBig { mtu, header, .. } => {
+                if self.has_ip_addr(header.src_addr) {
+                    // IPv6 links have an MTU of at least 1280 octets (RFC 8201 § 4).
+                    let mtu = (mtu as usize).max(IPV6_MIN_MTU);
+                    self.update_path_mtu(header.dst_addr.into(), mtu);
+                }
+                None
+            }
+
+            // Forward any NDISC packets to the ndisc packet handler. Packets that
+            // may have been forwarded by a router are not valid, see RFC 4861 § 7.1.
+            Icmpv6Repr::Ndisc(repr) if ip_repr.hop_limit == 0xff => {
+                self.process_ndisc(ip_repr, repr)
+            }
+
+            // Don't report an error if a packet with unknown type
+            // has been handled by an ICMP socket
+            _ if handled_by_icmp_socket => None,
+
+            // FIXME: do something correct here?
+            _ => None,
+        }
+    }
+
+    pub(super) fn process_ndisc<'frame>(
+        &mut self,
+        ip_repr: Ipv6Repr,
+        repr: NdiscRepr<'frame>,
+    ) -> Option<Packet<'frame>> {
+        match repr {
+            NdiscRepr::NeighborAdvert {
+                lladdr,
+                target_addr,
+                flags,
+            } => {
+                if self.slaac.is_tentative(&target_addr) {
+                    self.slaac_duplicate_address(target_addr);
+                    return None;
+                }
+
+                let ip_addr = ip_repr.src_addr.into();
+                if let Some(lladdr) = lladdr {
+                    let lladdr = check!(lladdr.parse(self.caps.medium));
+                    if !lladdr.is_unicast() || !target_addr.x_is_unicast() {
+                        return None;
+                    }
+                    if flags.contains(NdiscNeighborFlags::OVERRIDE)
+                        || !self.neighbor_cache.lookup(&ip_addr, self.now).found()
+                    {
+                        self.neighbor_cache.fill(ip_addr, lladdr, self.now)
+                    }
+                }
+                None
+            }
+            NdiscRepr::NeighborSolicit {
+                target_addr,
+                lladdr,
+                ..
+            } => {
+                // Another node performing Duplicate Address Detection for the same address.
+                if ip_repr.src_addr.is_unspecified() {
+                    if lladdr.is_some() || !ip_repr.dst_addr.is_solicited_node_multicast() {
+                        return None;
+                    }
+                    if self.slaac.is_tentative(&target_addr) {
+                        self.slaac_duplicate_address(target_addr);
+                        return None;
+                    }
+                }
+
+                if let Some(lladdr) = lladdr {
+                    let lladdr = check!(lladdr.parse(self.caps.medium));
+                    if !lladdr.is_unicast() || !target_addr.x_is_unicast() {
+                        return None;
+                    }
+                    self.neighbor_cache
+                        .fill(ip_repr.src_addr.into(), lladdr, self.now);
+                }
+
+                if self.has_solicited_node(ip_repr.dst_addr) && self.has_ip_addr(target_addr) {
+                    // Answers to Duplicate Address Detection go to all nodes and are
+                    // not solicited, see RFC 4861 § 7.2.4.
+                    let (flags, dst_addr) = if ip_repr.src_addr.is_unspecified() {
+                        (NdiscNeighborFlags::OVERRIDE, IPV6_LINK_LOCAL_ALL_NODES)
+                    } else {
+                        (NdiscNeighborFlags::SOLICITED, ip_repr.src_addr)
+                    };
+                    let advert = Icmpv6Repr::Ndisc(NdiscRepr::NeighborAdvert {
+                        flags,
+                        target_addr,
+                        lladdr: Some(self.hardware_addr.into()),
+                    });
+                    let ip_repr = Ipv6Repr {
+                        src_addr: target_addr,
+                        dst_addr,
+                        next_header: IpProtocol::Icmpv6,
+                        hop_limit: 0xff,
+                        payload_len: advert.buffer_len(),
+                    };
+                    Some(Packet::new_ipv6(ip_repr, IpPayload::Icmpv6(advert)))
+                } else {
+                    None
+                }
+            }
+            // Router advertisements must come from a link-local address, see RFC 4861 § 6.1.2.
+            NdiscRepr::RouterAdvert {
+                router_lifetime,
+                lladdr,
+                prefix_info,
+                ..
+            } if ip_repr.src_addr.is_link_local() => {
+                // The source link-layer address option updates the entry of the router,
+                // see RFC 4861 § 6.3.4.
+                if let Some(lladdr) = lladdr {
+                    let lladdr = check!(lladdr.parse(self.caps.medium));
+                    if lladdr.is_unicast() {
+                        self.neighbor_cache
+                            .fill(ip_repr.src_addr.into(), lladdr, self.now);
+                    }
+                }
+
+                self.slaac_process_router_advert(ip_repr.src_addr, router_lifetime, prefix_info);
+                None
+            }
+            // A redirect carrying the target link-layer address option updates
+            // the entry of the redirect target, see RFC 4861 § 8.3.
+            NdiscRepr::Redirect {
+                target_addr,
+                dest_addr,
+                lladdr: Some(lladdr),
+                redirected_hdr,
+            } => {
+                let lladdr = check!(lladdr.parse(self.caps.medium));
+                if lladdr.is_unicast()
+                    && self.redirect_permitted(
+                        ip_repr.src_addr,
+                        target_addr,
+                        dest_addr,
+                        redirected_hdr,
+                    )
+                {
+                    self.neighbor_cache
+                        .fill(target_addr.into(), lladdr, self.now);
+                }
+                None
+            }
+            _ => None,
+        }
+    }
+
+    /// Check whether a redirect from `router` may be followed (RFC 4861 § 8.1).
+    fn redirect_permitted(
+        &self,
+        router: Ipv6Address,
+        target_addr: Ipv6Address,
+        dest_addr: Ipv6Address,
+        redirected_hdr: Option<NdiscRedirectedHeader>,
+    ) -> bool {
+        if !self.accept_redirects {
+            net_debug!("ignoring redirect from {}", router);
+            return false;
+        }
+        if !router.is_link_local()
+            || !dest_addr.x_is_unicast()
+            || !(target_addr.is_link_local() || target_addr == dest_addr)
+        {
+            net_debug!("redirect from {}: invalid addresses", router);
+            return false;
+        }
+        // Only the first-hop router currently used for the destination may
+        // redirect us. The redirected header, when present, tells which of the
+        // equal-cost routes the datagram took.
+        let (src_addr, flow_hash) = match redirected_hdr {
+            Some(NdiscRedirectedHeader { header, data }) => (
+                Some(IpAddress::Ipv6(header.src_addr)),
+                Packet::new_ipv6(header, IpPayload::Raw(data)).flow_hash(),
+            ),
+            None => (None, 0),
+        };
+        if self.route(&dest_addr.into(), src_addr.as_ref(), flow_hash, self.now)
+            != Some(router.into())
+        {
+            net_debug!(
+                "redirect from {}: not the current router for {}",
+                router,
+                dest_addr
+            );
+            return false;
+        }
+        true
+    }
+
+    pub(super) fn icmpv6_reply<'frame, 'icmp: 'frame>(
+        &self,
+        ipv6_repr: Ipv6Repr,
+        icmp_repr: Icmpv6Repr<'icmp>,
+    ) -> Option<Packet<'frame>> {
+        let src_addr = ipv6_repr.dst_addr;
+        let dst_addr = ipv6_repr.src_addr;
+
+        let src_addr = if src_addr.x_is_unicast() {
+            src_addr
+        } else {
+            self.get_source_address_ipv6(&dst_addr)
+        };
+
+        let ipv6_reply_repr = Ipv6Repr {
+            src_addr,
+            dst_addr,
+            next_header: IpProtocol::Icmpv6,
+            payload_len: icmp_repr.buffer_len(),
+            hop_limit: 64,
+        };
+        Some(Packet::new_ipv6(
+            ipv6_reply_repr,
+            IpPayload::Icmpv6(icmp_repr),
+        ))
+    }
+}
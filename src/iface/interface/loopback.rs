@@ -0,0 +1,102 @@
+use super::*;
+
+impl Interface {
+    /// Process a packet from the loopback queue, as if it was received from the device.
+    pub(super) fn loopback_ingress(
+        &mut self,
+        device: &mut (impl Device + ?Sized),
+        sockets: &mut SocketSet<'_>,
+        packet: Vec<u8>,
+    ) -> PollIngressSingleResult {
+        let Some(response) = self
+            .inner
+            .process_loopback(sockets, &packet, &mut self.fragments)
+        else {
+            return PollIngressSingleResult::SocketStateChanged;
+        };
+
+        if !self.inner.icmp_error_permitted(&response) {
+            return PollIngressSingleResult::SocketStateChanged;
+        }
+
+        // Responses normally go back to one of our own addresses, unless a raw
+        // socket sent the packet with a foreign source address.
+        let Some(response) = self.inner.output_ip(response) else {
+            return PollIngressSingleResult::SocketStateChanged;
+        };
+        if let Some(tx_token) = device.transmit(self.inner.now) {
+            if let Err(err) = self.inner.transmit_ip(
+                tx_token,
+                PacketMeta::default(),
+                response,
+                &mut self.fragmenter,
+            ) {
+                net_debug!("Failed to send response: {:?}", err);
+            }
+        } else {
+            net_debug!("failed to transmit IP: device exhausted");
+        }
+
+        PollIngressSingleResult::SocketStateChanged
+    }
+}
+
+impl InterfaceInner {
+    /// Check whether packets to `addr` are delivered to ourselves, without
+    /// going through the device.
+    pub(crate) fn is_loopback_dst(&self, addr: &IpAddress) -> bool {
+        addr.is_loopback() || self.has_ip_addr(*addr)
+    }
+
+    /// Queue a packet addressed to ourselves. It is processed by the next ingress poll.
+    pub(super) fn dispatch_loopback(&mut self, packet: Packet) {
+        let ip_repr = packet.ip_repr();
+
+        if self.loopback.len() >= self.limits.loopback_queue_len {
+            net_debug!(
+                "loopback queue full, dropping packet to {}",
+                ip_repr.dst_addr()
+            );
+            return;
+        }
+
+        // The packet never reaches the device, so checksums are always computed
+        // instead of being offloaded.
+        let mut caps = self.caps.clone();
+        caps.checksum = ChecksumCapabilities::default();
+
+        let mut buffer = vec![0; ip_repr.buffer_len()];
+        ip_repr.emit(&mut buffer[..], &caps.checksum);
+        packet.emit_payload(&ip_repr, &mut buffer[ip_repr.header_len()..], &caps);
+
+        net_trace!("loopback: queued {} octets", buffer.len());
+        self.loopback.push_back(buffer);
+    }
+
+    fn process_loopback<'frame>(
+        &mut self,
+        sockets: &mut SocketSet,
+        packet: &'frame [u8],
+        fragments: &'frame mut FragmentsBuffer,
+    ) -> Option<Packet<'frame>> {
+        let hardware_addr = self.hardware_addr;
+
+        match IpVersion::of_packet(packet) {
+            Ok(IpVersion::Ipv4) => {
+                let ipv4_packet = check!(Ipv4Packet::new_checked(packet));
+                self.process_ipv4(
+                    sockets,
+                    PacketMeta::default(),
+                    hardware_addr,
+                    &ipv4_packet,
+                    fragments,
+                )
+            }
+            Ok(IpVersion::Ipv6) => {
+                let ipv6_packet = check!(Ipv6Packet::new_checked(packet));
+                self.process_ipv6(sockets, PacketMeta::default(), hardware_addr, &ipv6_packet)
+            }
+            Err(_) => None,
+        }
+    }
+}
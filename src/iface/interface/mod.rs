@@ -5,17 +5,34 @@
 mod ethernet;
 
 mod ipv4;
+mod ipv6;
+mod loopback;
+
+mod multicast;
+mod slaac;
+mod udp;
+
+#[cfg(test)]
+mod tests;
 
 // mod tcp;
 // mod udp;
 
 use super::packet::*;
 
+pub use multicast::MulticastError;
+
 use core::result::Result;
+use std::collections::VecDeque;
 
+use super::conntrack::{Conntrack, Tracked};
+use super::firewall::{Action, Chain, Firewall, Flow};
 use super::fragmentation::{Fragmenter, FragmentsBuffer};
 
-use super::neighbor::{Answer as NeighborAnswer, Cache as NeighborCache};
+use super::limits::Limits;
+use super::neighbor::{Answer as NeighborAnswer, Cache as NeighborCache, NeighborCacheFull};
+use super::path_mtu::Cache as PathMtuCache;
+use super::rate_limit::{IcmpRateLimiter, RateLimit};
 use super::socket_set::SocketSet;
 use crate::iface::Routes;
 use crate::phy::PacketMeta;
@@ -101,12 +118,34 @@ pub struct InterfaceInner {
     rand: Rand,
 
     neighbor_cache: NeighborCache,
+    path_mtu_cache: PathMtuCache,
     hardware_addr: HardwareAddress,
     ip_addrs: Vec<IpCidr>,
     any_ip: bool,
     routes: Routes,
+    multicast: multicast::State,
+    slaac: slaac::State,
+    accept_redirects: bool,
+    icmp_rate_limiter: IcmpRateLimiter,
+    /// Packets addressed to ourselves, waiting for the next ingress poll.
+    loopback: VecDeque<Vec<u8>>,
+    firewall: Firewall,
+    conntrack: Conntrack,
+    limits: Limits,
+}
+
+/// Error returned when the IP addresses don't fit in the interface.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AddrTableFull;
+
+impl core::fmt::Display for AddrTableFull {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "Address table full")
+    }
 }
 
+impl std::error::Error for AddrTableFull {}
+
 /// Configuration structure used for creating a network interface.
 #[non_exhaustive]
 pub struct Config {
@@ -123,6 +162,36 @@ pub struct Config {
     /// # Panics
     /// Creating the interface panics if the address is not unicast.
     pub hardware_addr: HardwareAddress,
+
+    /// Enable IPv6 stateless address autoconfiguration.
+    ///
+    /// The interface configures a link-local address, solicits routers, and
+    /// configures addresses and default routes from their advertisements.
+    pub slaac: bool,
+
+    /// Accept ICMP Redirect messages.
+    ///
+    /// ICMP redirects received from the current gateway of a destination install
+    /// a temporary host route via the new gateway, and NDISC redirects from the
+    /// current router of a destination update the neighbor cache entry of their
+    /// target. Disable this when the link is not trusted, since redirects can be
+    /// forged to divert traffic.
+    pub accept_redirects: bool,
+
+    /// Rate limit of the ICMP error messages sent by the interface, `None` for no limit.
+    ///
+    /// Defaults to 100 messages per second, with bursts of 50.
+    pub icmp_error_rate_limit: Option<RateLimit>,
+
+    /// Rate limit of the ICMP error messages sent to each destination, `None` for no limit.
+    ///
+    /// Defaults to 10 messages per second, with bursts of 10.
+    pub icmp_error_rate_limit_per_dst: Option<RateLimit>,
+
+    /// Capacities of the tables of the interface.
+    ///
+    /// Defaults to the `IFACE_*` constants of [`crate::config`].
+    pub limits: Limits,
 }
 
 impl Config {
@@ -130,6 +199,11 @@ impl Config {
         Config {
             random_seed: 0,
             hardware_addr,
+            slaac: false,
+            accept_redirects: true,
+            icmp_error_rate_limit: Some(RateLimit::new(100, 50)),
+            icmp_error_rate_limit_per_dst: Some(RateLimit::new(10, 10)),
+            limits: Limits::default(),
         }
     }
 }
@@ -148,6 +222,7 @@ impl Interface {
             "The hardware address does not match the medium of the interface."
         );
 
+        let limits = config.limits;
         let mut rand = Rand::new(config.random_seed);
 
         let mut ipv4_id;
@@ -168,8 +243,22 @@ impl Interface {
                 hardware_addr: config.hardware_addr,
                 ip_addrs: Vec::new(),
                 any_ip: false,
-                routes: Routes::new(),
-                neighbor_cache: NeighborCache::new(),
+                routes: Routes::with_limits(limits.max_routes, limits.max_routing_rules),
+                neighbor_cache: NeighborCache::new(limits.neighbor_cache_size),
+                path_mtu_cache: PathMtuCache::new(limits.path_mtu_cache_size),
+                multicast: multicast::State::new(),
+                slaac: slaac::State::new(config.slaac, config.hardware_addr, now),
+                accept_redirects: config.accept_redirects,
+                icmp_rate_limiter: IcmpRateLimiter::new(
+                    config.icmp_error_rate_limit,
+                    config.icmp_error_rate_limit_per_dst,
+                    limits.icmp_rate_limit_dst_count,
+                    now,
+                ),
+                loopback: VecDeque::new(),
+                firewall: Firewall::with_max_rules(limits.max_firewall_rules),
+                conntrack: Conntrack::with_capacity(limits.conntrack_size),
+                limits,
                 rand,
             },
         }
@@ -202,6 +291,11 @@ impl Interface {
         self.inner.hardware_addr = addr;
     }
 
+    /// Get the capacities of the tables of the interface.
+    pub fn limits(&self) -> &Limits {
+        &self.inner.limits
+    }
+
     /// Get the IP addresses of the interface.
     pub fn ip_addrs(&self) -> &[IpCidr] {
         self.inner.ip_addrs.as_ref()
@@ -213,26 +307,78 @@ impl Interface {
     }
 
     /// Get an address from the interface that could be used as source address. For IPv4, this is
-    /// the first IPv4 address from the list of addresses. For IPv6, the address is based on the
-    /// destination address and uses RFC6724 for selecting the source address.
+    /// the address of the destination's network or the preferred source of its route. For IPv6,
+    /// the address is based on the destination address and uses RFC6724 for selecting the source
+    /// address.
     pub fn get_source_address(&self, dst_addr: &IpAddress) -> Option<IpAddress> {
         self.inner.get_source_address(dst_addr)
     }
 
-    /// Get an address from the interface that could be used as source address. This is the first
-    /// IPv4 address from the list of addresses in the interface.
+    /// Get an address from the interface that could be used as source address. This is the
+    /// address of the destination's network, or else the preferred source of its route, or else
+    /// the first IPv4 address from the list of addresses in the interface.
     pub fn get_source_address_ipv4(&self, dst_addr: &Ipv4Address) -> Option<Ipv4Address> {
         self.inner.get_source_address_ipv4(dst_addr)
     }
 
+    /// Get an address from the interface that could be used as source address. The selection is
+    /// based on RFC6724.
+    pub fn get_source_address_ipv6(&self, dst_addr: &Ipv6Address) -> Ipv6Address {
+        self.inner.get_source_address_ipv6(dst_addr)
+    }
+
     /// Update the IP addresses of the interface.
     ///
+    /// If there are more addresses than [`Limits::max_ip_addrs`], they are left
+    /// unchanged and `AddrTableFull` is returned.
+    ///
     /// # Panics
     /// This function panics if any of the addresses are not unicast.
-    pub fn update_ip_addrs<F: FnOnce(&mut Vec<IpCidr>)>(&mut self, f: F) {
-        f(&mut self.inner.ip_addrs);
+    pub fn update_ip_addrs<F: FnOnce(&mut Vec<IpCidr>)>(
+        &mut self,
+        f: F,
+    ) -> Result<(), AddrTableFull> {
+        let mut ip_addrs = self.inner.ip_addrs.clone();
+        f(&mut ip_addrs);
+        if ip_addrs.len() > self.inner.limits.max_ip_addrs {
+            return Err(AddrTableFull);
+        }
+        InterfaceInner::check_ip_addrs(&ip_addrs);
+        self.inner.ip_addrs = ip_addrs;
         InterfaceInner::flush_neighbor_cache(&mut self.inner);
-        InterfaceInner::check_ip_addrs(&self.inner.ip_addrs);
+        Ok(())
+    }
+
+    /// Add a static neighbor (ie. "ip neigh add `protocol_addr` lladdr `hardware_addr` nud
+    /// permanent"), which never expires and isn't replaced by neighbor discovery.
+    ///
+    /// Returns the previous hardware address of the static neighbor, if any, or
+    /// `NeighborCacheFull` if the cache is full of static neighbors.
+    ///
+    /// # Panics
+    /// This function panics if either address is not unicast.
+    pub fn add_static_neighbor(
+        &mut self,
+        protocol_addr: IpAddress,
+        hardware_addr: HardwareAddress,
+    ) -> Result<Option<HardwareAddress>, NeighborCacheFull> {
+        assert!(protocol_addr.is_unicast() && hardware_addr.is_unicast());
+        self.inner
+            .neighbor_cache
+            .fill_static(protocol_addr, hardware_addr)
+    }
+
+    /// Remove a static neighbor, returning its hardware address.
+    pub fn remove_static_neighbor(&mut self, protocol_addr: &IpAddress) -> Option<HardwareAddress> {
+        self.inner.neighbor_cache.remove_static(protocol_addr)
+    }
+
+    /// Get the path MTU to a destination, in octets of IP packet.
+    ///
+    /// This is the MTU of the link, lowered by the "fragmentation needed" and
+    /// "packet too big" ICMP errors received for that destination.
+    pub fn path_mtu<T: Into<IpAddress>>(&self, dst_addr: T) -> usize {
+        self.inner.path_mtu(&dst_addr.into())
     }
 
     /// Check whether the interface has the given IP address assigned.
@@ -266,6 +412,64 @@ impl Interface {
         self.inner.any_ip
     }
 
+    /// Get the IPv4 packet filter of the interface.
+    pub fn firewall(&self) -> &Firewall {
+        &self.inner.firewall
+    }
+
+    /// Get the IPv4 packet filter of the interface, mutably.
+    pub fn firewall_mut(&mut self) -> &mut Firewall {
+        &mut self.inner.firewall
+    }
+
+    /// Get the connection tracker of the interface.
+    pub fn conntrack(&self) -> &Conntrack {
+        &self.inner.conntrack
+    }
+
+    /// Get the connection tracker of the interface, mutably.
+    pub fn conntrack_mut(&mut self) -> &mut Conntrack {
+        &mut self.inner.conntrack
+    }
+
+    /// Enable or disable the processing of ICMP Redirect messages.
+    ///
+    /// See [`Config::accept_redirects`] for details.
+    pub fn set_accept_redirects(&mut self, accept_redirects: bool) {
+        self.inner.accept_redirects = accept_redirects;
+    }
+
+    /// Get whether ICMP Redirect messages are processed.
+    pub fn accept_redirects(&self) -> bool {
+        self.inner.accept_redirects
+    }
+
+    /// Get the rate limit of the ICMP error messages sent by the interface.
+    pub fn icmp_error_rate_limit(&self) -> Option<RateLimit> {
+        self.inner.icmp_rate_limiter.global_limit()
+    }
+
+    /// Set the rate limit of the ICMP error messages sent by the interface.
+    ///
+    /// See [`Config::icmp_error_rate_limit`] for details.
+    pub fn set_icmp_error_rate_limit(&mut self, limit: Option<RateLimit>) {
+        self.inner
+            .icmp_rate_limiter
+            .set_global_limit(limit, self.inner.now);
+    }
+
+    /// Get the rate limit of the ICMP error messages sent to each destination.
+    pub fn icmp_error_rate_limit_per_dst(&self) -> Option<RateLimit> {
+        self.inner.icmp_rate_limiter.per_dst_limit()
+    }
+
+    /// Set the rate limit of the ICMP error messages sent to each destination.
+    ///
+    /// See [`Config::icmp_error_rate_limit_per_dst`] for details.
+    pub fn set_icmp_error_rate_limit_per_dst(&mut self, limit: Option<RateLimit>) {
+        self.inner.icmp_rate_limiter.set_per_dst_limit(limit);
+    }
+
     /// Transmit packets queued in the sockets, and receive packets queued
     /// in the device.
     ///
@@ -330,6 +534,9 @@ impl Interface {
             _ => {}
         }
 
+        self.multicast_egress(device);
+        self.slaac_egress(device);
+
         self.socket_egress(device, sockets)
     }
 
@@ -361,6 +568,10 @@ impl Interface {
     pub fn poll_at(&mut self, timestamp: Instant, sockets: &SocketSet<'_>) -> Option<Instant> {
         self.inner.now = timestamp;
 
+        let multicast_poll_at = self.inner.multicast.poll_at();
+        let slaac_poll_at = self.inner.slaac.poll_at();
+        let loopback_poll_at = (!self.inner.loopback.is_empty()).then_some(Instant::from_millis(0));
+
         let inner = &mut self.inner;
 
         sockets
@@ -376,6 +587,9 @@ impl Interface {
                     PollAt::Now => Some(Instant::from_millis(0)),
                 }
             })
+            .chain(multicast_poll_at)
+            .chain(slaac_poll_at)
+            .chain(loopback_poll_at)
             .min()
     }
 
@@ -400,6 +614,10 @@ impl Interface {
         device: &mut (impl Device + ?Sized),
         sockets: &mut SocketSet<'_>,
     ) -> PollIngressSingleResult {
+        if let Some(packet) = self.inner.loopback.pop_front() {
+            return self.loopback_ingress(device, sockets, packet);
+        }
+
         let Some((rx_token, tx_token)) = device.receive(self.inner.now) else {
             return PollIngressSingleResult::None;
         };
@@ -416,10 +634,16 @@ impl Interface {
                         self.inner
                             .process_ethernet(sockets, rx_meta, frame, &mut self.fragments)
                     {
-                        if let Err(err) =
-                            self.inner.dispatch(tx_token, packet, &mut self.fragmenter)
-                        {
-                            net_debug!("Failed to send response: {:?}", err);
+                        let permitted = match &packet {
+                            EthernetPacket::Ip(packet) => self.inner.icmp_error_permitted(packet),
+                            _ => true,
+                        };
+                        if permitted {
+                            if let Err(err) =
+                                self.inner.dispatch(tx_token, packet, &mut self.fragmenter)
+                            {
+                                net_debug!("Failed to send response: {:?}", err);
+                            }
                         }
                     }
                 }
@@ -458,14 +682,37 @@ impl Interface {
 
             let mut neighbor_addr = None;
             let mut respond = |inner: &mut InterfaceInner, meta: PacketMeta, response: Packet| {
-                neighbor_addr = Some(response.ip_repr().dst_addr());
+                // Remember the next hop of this packet rather than its destination:
+                // the route depends on the source address and the flow, so the
+                // destination alone can't tell which neighbor the socket waits for.
+                // Multicast and broadcast packets aren't routed.
+                let ip_repr = response.ip_repr();
+                let dst_addr = ip_repr.dst_addr();
+                neighbor_addr = Some(if dst_addr.is_unicast() {
+                    inner
+                        .route(
+                            &dst_addr,
+                            Some(&ip_repr.src_addr()),
+                            response.flow_hash(),
+                            inner.now,
+                        )
+                        .unwrap_or(dst_addr)
+                } else {
+                    dst_addr
+                });
+                // Packets to ourselves never reach the device, so they don't
+                // need a transmit token.
+                let Some(response) = inner.output_ip(response) else {
+                    result = PollResult::SocketStateChanged;
+                    return Ok(());
+                };
                 let t = device.transmit(inner.now).ok_or_else(|| {
                     net_debug!("failed to transmit IP: device exhausted");
                     EgressError::Exhausted
                 })?;
 
                 inner
-                    .dispatch_ip(t, meta, response, &mut self.fragmenter)
+                    .transmit_ip(t, meta, response, &mut self.fragmenter)
                     .map_err(|_| EgressError::Dispatch)?;
 
                 result = PollResult::SocketStateChanged;
@@ -488,15 +735,53 @@ impl Interface {
                             PacketMeta::default(),
                             Packet::new_ipv4(ipv4_repr, IpPayload::Icmpv4(icmpv4_repr)),
                         ),
-                        #[allow(unreachable_patterns)]
+                        (IpRepr::Ipv6(ipv6_repr), IcmpRepr::Ipv6(icmpv6_repr)) => respond(
+                            inner,
+                            PacketMeta::default(),
+                            Packet::new_ipv6(ipv6_repr, IpPayload::Icmpv6(icmpv6_repr)),
+                        ),
                         _ => unreachable!(),
                     })
-                } // Socket::Udp(socket) => {
-                  //     socket.dispatch(&mut self.inner, |inner, meta, (ip, udp, payload)| {
-                  //         respond(inner, meta, Packet::new(ip, IpPayload::Udp(udp, payload)))
-                  //     })
-                  // }
-                  // Socket::Tcp(socket) => socket.dispatch(&mut self.inner, |inner, (ip, tcp)| {
+                }
+                Socket::Dhcpv4(socket) => {
+                    socket.dispatch(&mut self.inner, |inner, (ip, udp, dhcp)| {
+                        respond(
+                            inner,
+                            PacketMeta::default(),
+                            Packet::new_ipv4(ip, IpPayload::Dhcpv4(udp, dhcp)),
+                        )
+                    })
+                }
+                Socket::Dhcpv4Server(socket) => {
+                    socket.dispatch(&mut self.inner, |inner, (ip, udp, dhcp)| {
+                        respond(
+                            inner,
+                            PacketMeta::default(),
+                            Packet::new_ipv4(ip, IpPayload::Dhcpv4(udp, dhcp)),
+                        )
+                    })
+                }
+                Socket::Dns(socket) => socket.dispatch(&mut self.inner, |inner, (ip, udp, dns)| {
+                    respond(
+                        inner,
+                        PacketMeta::default(),
+                        Packet::new(ip, IpPayload::Udp(udp, dns)),
+                    )
+                }),
+                Socket::DnsResponder(socket) => {
+                    socket.dispatch(&mut self.inner, |inner, (ip, udp, dns)| {
+                        respond(
+                            inner,
+                            PacketMeta::default(),
+                            Packet::new(ip, IpPayload::Udp(udp, dns)),
+                        )
+                    })
+                }
+                Socket::Udp(socket) => {
+                    socket.dispatch(&mut self.inner, |inner, meta, (ip, udp, payload)| {
+                        respond(inner, meta, Packet::new(ip, IpPayload::Udp(udp, payload)))
+                    })
+                } // Socket::Tcp(socket) => socket.dispatch(&mut self.inner, |inner, (ip, tcp)| {
                   //     respond(
                   //         inner,
                   //         PacketMeta::default(),
@@ -545,6 +830,51 @@ impl InterfaceInner {
         self.caps.ip_mtu()
     }
 
+    /// Check the rate limit of ICMP errors, if `packet` is one (RFC 1812 § 4.3.2.8).
+    fn icmp_error_permitted(&mut self, packet: &Packet) -> bool {
+        let is_error = match packet.payload() {
+            IpPayload::Icmpv4(repr) => matches!(
+                repr,
+                Icmpv4Repr::DstUnreachable { .. }
+                    | Icmpv4Repr::TimeExceeded { .. }
+                    | Icmpv4Repr::ParamProblem { .. }
+            ),
+            IpPayload::Icmpv6(repr) => matches!(
+                repr,
+                Icmpv6Repr::DstUnreachable { .. }
+                    | Icmpv6Repr::PktTooBig { .. }
+                    | Icmpv6Repr::TimeExceeded { .. }
+                    | Icmpv6Repr::ParamProblem { .. }
+            ),
+            _ => false,
+        };
+        let dst_addr = packet.ip_repr().dst_addr();
+        if is_error && !self.icmp_rate_limiter.permit(dst_addr, self.now) {
+            net_debug!("ICMP error to {} rate limited", dst_addr);
+            return false;
+        }
+        true
+    }
+
+    /// Return the path MTU to `dst_addr`, see [`Interface::path_mtu`].
+    pub(crate) fn path_mtu(&self, dst_addr: &IpAddress) -> usize {
+        let mtu = self.caps.ip_mtu();
+        match self.path_mtu_cache.lookup(dst_addr, self.now) {
+            Some(path_mtu) => path_mtu.min(mtu),
+            None => mtu,
+        }
+    }
+
+    /// Lower the path MTU to `dst_addr`, after an ICMP error reported `mtu`.
+    fn update_path_mtu(&mut self, dst_addr: IpAddress, mtu: usize) {
+        if mtu >= self.path_mtu(&dst_addr) {
+            return;
+        }
+        if self.path_mtu_cache.fill(dst_addr, mtu, self.now) {
+            net_debug!("path MTU to {} is now {}", dst_addr, mtu);
+        }
+    }
+
     #[allow(unused)] // unused depending on which sockets are enabled, and in tests
     pub(crate) fn rand(&mut self) -> &mut Rand {
         &mut self.rand
@@ -554,6 +884,7 @@ impl InterfaceInner {
     pub(crate) fn get_source_address(&self, dst_addr: &IpAddress) -> Option<IpAddress> {
         match dst_addr {
             IpAddress::Ipv4(addr) => self.get_source_address_ipv4(addr).map(|a| a.into()),
+            IpAddress::Ipv6(addr) => Some(self.get_source_address_ipv6(addr).into()),
         }
     }
 
@@ -582,17 +913,6 @@ impl InterfaceInner {
         self.ip_addrs.iter().any(|probe| probe.address() == addr)
     }
 
-    /// Check whether the interface listens to given destination multicast IP address.
-    fn has_multicast_group<T: Into<IpAddress>>(&self, addr: T) -> bool {
-        let addr = addr.into();
-
-        match addr {
-            IpAddress::Ipv4(key) => key == IPV4_MULTICAST_ALL_SYSTEMS,
-            #[allow(unreachable_patterns)]
-            _ => false,
-        }
-    }
-
     fn raw_socket_filter(
         &mut self,
         sockets: &mut SocketSet,
@@ -619,6 +939,7 @@ impl InterfaceInner {
     pub(crate) fn is_broadcast(&self, address: &IpAddress) -> bool {
         match address {
             IpAddress::Ipv4(address) => self.is_broadcast_v4(*address),
+            IpAddress::Ipv6(_) => false,
         }
     }
 
@@ -658,7 +979,16 @@ impl InterfaceInner {
         self.ip_addrs.iter().any(|cidr| cidr.contains_addr(addr))
     }
 
-    fn route(&self, addr: &IpAddress, timestamp: Instant) -> Option<IpAddress> {
+    /// Return the next hop towards `addr` for packets sent from `src_addr`, or from
+    /// an address yet to be selected if `None`, picking among equal-cost routes
+    /// with `flow_hash`.
+    fn route(
+        &self,
+        addr: &IpAddress,
+        src_addr: Option<&IpAddress>,
+        flow_hash: u32,
+        timestamp: Instant,
+    ) -> Option<IpAddress> {
         // Send directly.
         // note: no need to use `self.is_broadcast()` to check for subnet-local broadcast addrs
         //       here because `in_same_network` will already return true.
@@ -667,23 +997,29 @@ impl InterfaceInner {
         }
 
         // Route via a router.
-        self.routes.lookup(addr, timestamp)
+        self.routes
+            .lookup(addr, src_addr, flow_hash, timestamp)
+            .map(|route| route.via_router)
     }
 
+    /// Check whether the next hop `addr` can be sent to without neighbor discovery.
     fn has_neighbor(&self, addr: &IpAddress) -> bool {
-        match self.route(addr, self.now) {
-            Some(_routed_addr) => match self.caps.medium {
-                Medium::Ethernet => self.neighbor_cache.lookup(&_routed_addr, self.now).found(),
-            },
-            None => false,
+        if self.is_loopback_dst(addr) {
+            return true;
+        }
+
+        match self.caps.medium {
+            Medium::Ethernet => self.neighbor_cache.lookup(addr, self.now).found(),
         }
     }
 
     fn lookup_hardware_addr<Tx>(
         &mut self,
         tx_token: Tx,
+        src_addr: &IpAddress,
         dst_addr: &IpAddress,
-        _fragmenter: &mut Fragmenter,
+        flow_hash: u32,
+        fragmenter: &mut Fragmenter,
     ) -> Result<(HardwareAddress, Tx), DispatchError>
     where
         Tx: TxToken,
@@ -711,13 +1047,21 @@ impl InterfaceInner {
                         ]))
                     }
                 },
+                IpAddress::Ipv6(addr) => match self.caps.medium {
+                    Medium::Ethernet => {
+                        let b = addr.octets();
+                        HardwareAddress::Ethernet(EthernetAddress::from_bytes(&[
+                            0x33, 0x33, b[12], b[13], b[14], b[15],
+                        ]))
+                    }
+                },
             };
 
             return Ok((hardware_addr, tx_token));
         }
 
         let dst_addr = self
-            .route(dst_addr, self.now)
+            .route(dst_addr, Some(src_addr), flow_hash, self.now)
             .ok_or(DispatchError::NoRoute)?;
 
         match self.neighbor_cache.lookup(&dst_addr, self.now) {
@@ -757,7 +1101,36 @@ impl InterfaceInner {
                 }
             }
 
-            #[allow(unreachable_patterns)]
+            IpAddress::Ipv6(dst_addr) => {
+                net_debug!(
+                    "address {} not in neighbor cache, sending Neighbor Solicitation",
+                    dst_addr
+                );
+
+                let solicit = Icmpv6Repr::Ndisc(NdiscRepr::NeighborSolicit {
+                    target_addr: dst_addr,
+                    lladdr: Some(self.hardware_addr.into()),
+                });
+
+                let packet = Packet::new_ipv6(
+                    Ipv6Repr {
+                        src_addr: self.get_source_address_ipv6(&dst_addr),
+                        dst_addr: dst_addr.solicited_node(),
+                        next_header: IpProtocol::Icmpv6,
+                        payload_len: solicit.buffer_len(),
+                        hop_limit: 0xff,
+                    },
+                    IpPayload::Icmpv6(solicit),
+                );
+
+                if let Err(e) =
+                    self.dispatch_ip(tx_token, PacketMeta::default(), packet, fragmenter)
+                {
+                    net_debug!("Failed to dispatch NDISC solicit: {:?}", e);
+                    return Err(DispatchError::NeighborPending);
+                }
+            }
+
             _ => (),
         }
 
@@ -771,6 +1144,51 @@ impl InterfaceInner {
     }
 
     fn dispatch_ip<Tx: TxToken>(
+        &mut self,
+        tx_token: Tx,
+        meta: PacketMeta,
+        packet: Packet,
+        frag: &mut Fragmenter,
+    ) -> Result<(), DispatchError> {
+        match self.output_ip(packet) {
+            Some(packet) => self.transmit_ip(tx_token, meta, packet, frag),
+            None => Ok(()),
+        }
+    }
+
+    /// Run the part of the output path that doesn't need the device.
+    ///
+    /// Packets dropped by the firewall or looped back to ourselves yield `None`;
+    /// any other packet is handed back to be sent with
+    /// [`transmit_ip`](Self::transmit_ip).
+    fn output_ip<'p>(&mut self, packet: Packet<'p>) -> Option<Packet<'p>> {
+        let ip_repr = packet.ip_repr();
+        assert!(!ip_repr.dst_addr().is_unspecified());
+
+        if let IpRepr::Ipv4(ipv4_repr) = ip_repr {
+            let mut flow = Flow::from_payload(&ipv4_repr, packet.payload());
+            let tracked = Tracked::from_payload(&ipv4_repr, packet.payload());
+            if let Some(tracked) = &tracked {
+                flow.set_state(self.conntrack.classify(tracked, self.now));
+            }
+            if self.firewall.filter(Chain::Output, &flow) != Action::Accept {
+                // `Reject` acts as `Drop` here: nothing is reported to the socket.
+                net_trace!("firewall: dropping packet to {}", ipv4_repr.dst_addr);
+                return None;
+            }
+            if let Some(tracked) = &tracked {
+                self.conntrack.track(tracked, self.now);
+            }
+        }
+
+        if self.is_loopback_dst(&ip_repr.dst_addr()) {
+            self.dispatch_loopback(packet);
+            return None;
+        }
+        Some(packet)
+    }
+
+    fn transmit_ip<Tx: TxToken>(
         &mut self,
         // NOTE(unused_mut): tx_token isn't always mutated, depending on
         // the feature set that is used.
@@ -780,7 +1198,6 @@ impl InterfaceInner {
         frag: &mut Fragmenter,
     ) -> Result<(), DispatchError> {
         let mut ip_repr = packet.ip_repr();
-        assert!(!ip_repr.dst_addr().is_unspecified());
 
         // Dispatch IEEE802.15.4:
 
@@ -799,7 +1216,13 @@ impl InterfaceInner {
         // If the medium is Ethernet, then we need to retrieve the destination hardware address.
         let (dst_hardware_addr, mut tx_token) = match self.caps.medium {
             Medium::Ethernet => {
-                match self.lookup_hardware_addr(tx_token, &ip_repr.dst_addr(), frag)? {
+                match self.lookup_hardware_addr(
+                    tx_token,
+                    &ip_repr.src_addr(),
+                    &ip_repr.dst_addr(),
+                    packet.flow_hash(),
+                    frag,
+                )? {
                     (HardwareAddress::Ethernet(addr), tx_token) => (addr, tx_token),
                 }
             }
@@ -815,6 +1238,7 @@ impl InterfaceInner {
 
             match repr.version() {
                 IpVersion::Ipv4 => frame.set_ethertype(EthernetProtocol::Ipv4),
+                IpVersion::Ipv6 => frame.set_ethertype(EthernetProtocol::Ipv6),
             }
 
             Ok(())
@@ -829,15 +1253,19 @@ impl InterfaceInner {
         };
 
         let total_ip_len = ip_repr.buffer_len();
+        let path_mtu = self.path_mtu(&ip_repr.dst_addr());
 
         match &mut ip_repr {
             IpRepr::Ipv4(_repr) => {
                 // If we have an IPv4 packet, then we need to check if we need to fragment it.
-                if total_ip_len > self.caps.ip_mtu() {
-                    {
-                        net_debug!("Enable the `proto-ipv4-fragmentation` feature for fragmentation support.");
-                        Ok(())
-                    }
+                if total_ip_len > path_mtu {
+                    net_debug!(
+                        "packet of {} octets exceeds the path MTU of {} to {}, dropping (fragmentation is not supported)",
+                        total_ip_len,
+                        path_mtu,
+                        ip_repr.dst_addr()
+                    );
+                    Ok(())
                 } else {
                     tx_token.set_meta(meta);
 
@@ -853,6 +1281,25 @@ impl InterfaceInner {
                     })
                 }
             }
+            // We don't support IPv6 fragmentation yet.
+            IpRepr::Ipv6(_) if total_ip_len > path_mtu => {
+                net_debug!(
+                    "packet of {} octets exceeds the path MTU of {} to {}, dropping",
+                    total_ip_len,
+                    path_mtu,
+                    ip_repr.dst_addr()
+                );
+                Ok(())
+            }
+            IpRepr::Ipv6(_) => tx_token.consume(total_len, |mut tx_buffer| {
+                if matches!(self.caps.medium, Medium::Ethernet) {
+                    emit_ethernet(&ip_repr, tx_buffer)?;
+                    tx_buffer = &mut tx_buffer[EthernetFrame::<&[u8]>::header_len()..];
+                }
+
+                emit_ip(&ip_repr, tx_buffer);
+                Ok(())
+            }),
         }
     }
 }
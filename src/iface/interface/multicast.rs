@@ -0,0 +1,508 @@
+use core::result::Result;
+use std::collections::HashMap;
+
+use super::{check, IpPayload, Packet};
+use super::{Interface, InterfaceInner};
+use crate::phy::{Device, PacketMeta};
+use crate::time::{Duration, Instant};
+use crate::wire::*;
+
+/// How long a host stays in IGMPv1/IGMPv2 compatibility mode after hearing an
+/// older version query, see [RFC 3376 § 8.12] (Robustness Variable times Query
+/// Interval plus one Query Response Interval, with the default values).
+///
+/// [RFC 3376 § 8.12]: https://tools.ietf.org/html/rfc3376#section-8.12
+const IGMP_OLDER_QUERIER_PRESENT_TIMEOUT: Duration = Duration::from_secs(260);
+
+/// Error type for `join_multicast_group`, `leave_multicast_group`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MulticastError {
+    /// The table of joined multicast groups is already full.
+    GroupTableFull,
+    /// Cannot join/leave the given multicast group.
+    Unaddressable,
+}
+
+impl core::fmt::Display for MulticastError {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self {
+            MulticastError::GroupTableFull => write!(f, "GroupTableFull"),
+            MulticastError::Unaddressable => write!(f, "Unaddressable"),
+        }
+    }
+}
+
+impl std::error::Error for MulticastError {}
+
+pub(crate) enum IgmpReportState {
+    Inactive,
+    ToGeneralQuery {
+        version: IgmpVersion,
+        timeout: Instant,
+        interval: Duration,
+        next_index: usize,
+    },
+    ToSpecificQuery {
+        version: IgmpVersion,
+        timeout: Instant,
+        group: Ipv4Address,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum GroupState {
+    /// Joining group, we have to send the join packet.
+    Joining,
+    /// We've already sent the join packet, we have nothing to do.
+    Joined,
+    /// We want to leave the group, we have to send a leave packet.
+    Leaving,
+}
+
+pub(crate) struct State {
+    groups: HashMap<IpAddress, GroupState>,
+    /// When to report for (all or) the next multicast group membership via IGMP
+    igmp_report_state: IgmpReportState,
+    /// The IGMP version an older querier forced us down to, and until when.
+    igmp_older_querier: Option<(IgmpVersion, Instant)>,
+}
+
+impl State {
+    pub(crate) fn new() -> Self {
+        Self {
+            groups: HashMap::new(),
+            igmp_report_state: IgmpReportState::Inactive,
+            igmp_older_querier: None,
+        }
+    }
+
+    pub(crate) fn has_multicast_group<T: Into<IpAddress>>(&self, addr: T) -> bool {
+        // Return false if we don't have the multicast group,
+        // or we're leaving it.
+        match self.groups.get(&addr.into()) {
+            None => false,
+            Some(GroupState::Joining) => true,
+            Some(GroupState::Joined) => true,
+            Some(GroupState::Leaving) => false,
+        }
+    }
+
+    /// Return the IGMP version the host currently speaks, see [RFC 3376 § 7.2.1].
+    ///
+    /// [RFC 3376 § 7.2.1]: https://tools.ietf.org/html/rfc3376#section-7.2.1
+    fn igmp_version(&self, now: Instant) -> IgmpVersion {
+        match self.igmp_older_querier {
+            Some((version, until)) if now < until => version,
+            _ => IgmpVersion::Version3,
+        }
+    }
+
+    /// Return the next time multicast egress has something to send.
+    pub(crate) fn poll_at(&self) -> Option<Instant> {
+        if self
+            .groups
+            .values()
+            .any(|state| *state != GroupState::Joined)
+        {
+            return Some(Instant::from_millis(0));
+        }
+
+        match self.igmp_report_state {
+            IgmpReportState::Inactive => None,
+            IgmpReportState::ToGeneralQuery { timeout, .. } => Some(timeout),
+            IgmpReportState::ToSpecificQuery { timeout, .. } => Some(timeout),
+        }
+    }
+}
+
+impl Interface {
+    /// Add an address to a list of subscribed multicast IP addresses.
+    ///
+    /// The membership report is sent on the next call to [`poll`](Self::poll).
+    pub fn join_multicast_group<T: Into<IpAddress>>(
+        &mut self,
+        addr: T,
+    ) -> Result<(), MulticastError> {
+        let addr = addr.into();
+        if !addr.is_multicast() {
+            return Err(MulticastError::Unaddressable);
+        }
+
+        if let Some(state) = self.inner.multicast.groups.get_mut(&addr) {
+            *state = match state {
+                GroupState::Joining => GroupState::Joining,
+                GroupState::Joined => GroupState::Joined,
+                GroupState::Leaving => GroupState::Joined,
+            };
+        } else {
+            if self.inner.multicast.groups.len() >= self.inner.limits.max_multicast_groups {
+                return Err(MulticastError::GroupTableFull);
+            }
+            self.inner
+                .multicast
+                .groups
+                .insert(addr, GroupState::Joining);
+        }
+        Ok(())
+    }
+
+    /// Remove an address from the subscribed multicast IP addresses.
+    ///
+    /// The leave message is sent on the next call to [`poll`](Self::poll).
+    pub fn leave_multicast_group<T: Into<IpAddress>>(
+        &mut self,
+        addr: T,
+    ) -> Result<(), MulticastError> {
+        let addr = addr.into();
+        if !addr.is_multicast() {
+            return Err(MulticastError::Unaddressable);
+        }
+
+        if let Some(state) = self.inner.multicast.groups.get_mut(&addr) {
+            let delete;
+            (*state, delete) = match state {
+                GroupState::Joining => (GroupState::Joined, true),
+                GroupState::Joined => (GroupState::Leaving, false),
+                GroupState::Leaving => (GroupState::Leaving, false),
+            };
+            if delete {
+                self.inner.multicast.groups.remove(&addr);
+            }
+        }
+        Ok(())
+    }
+
+    /// Check whether the interface listens to given destination multicast IP address.
+    pub fn has_multicast_group<T: Into<IpAddress>>(&self, addr: T) -> bool {
+        self.inner.has_multicast_group(addr)
+    }
+
+    /// Do multicast egress.
+    ///
+    /// - Send join/leave packets according to the multicast group state.
+    /// - Depending on `igmp_report_state` and the therein contained
+    ///   timeouts, send IGMP membership reports.
+    pub(crate) fn multicast_egress(&mut self, device: &mut (impl Device + ?Sized)) {
+        // Process multicast joins.
+        while let Some((&addr, _)) = self
+            .inner
+            .multicast
+            .groups
+            .iter()
+            .find(|(_, &state)| state == GroupState::Joining)
+        {
+            match addr {
+                IpAddress::Ipv4(addr) => {
+                    let version = self.inner.multicast.igmp_version(self.inner.now);
+                    if let Some(pkt) = self.inner.igmp_join_packet(version, addr) {
+                        let Some(tx_token) = device.transmit(self.inner.now) else {
+                            break;
+                        };
+
+                        // NOTE(unwrap): packet destination is multicast, which is always routable and doesn't require neighbor discovery.
+                        self.inner
+                            .dispatch_ip(tx_token, PacketMeta::default(), pkt, &mut self.fragmenter)
+                            .unwrap();
+                    }
+                }
+                // MLD is not implemented, IPv6 groups are only filtered locally.
+                IpAddress::Ipv6(_) => (),
+            }
+
+            self.inner.multicast.groups.insert(addr, GroupState::Joined);
+        }
+
+        // Process multicast leaves.
+        while let Some((&addr, _)) = self
+            .inner
+            .multicast
+            .groups
+            .iter()
+            .find(|(_, &state)| state == GroupState::Leaving)
+        {
+            match addr {
+                IpAddress::Ipv4(addr) => {
+                    let version = self.inner.multicast.igmp_version(self.inner.now);
+                    if let Some(pkt) = self.inner.igmp_leave_packet(version, addr) {
+                        let Some(tx_token) = device.transmit(self.inner.now) else {
+                            break;
+                        };
+
+                        // NOTE(unwrap): packet destination is multicast, which is always routable and doesn't require neighbor discovery.
+                        self.inner
+                            .dispatch_ip(tx_token, PacketMeta::default(), pkt, &mut self.fragmenter)
+                            .unwrap();
+                    }
+                }
+                // MLD is not implemented, IPv6 groups are only filtered locally.
+                IpAddress::Ipv6(_) => (),
+            }
+
+            self.inner.multicast.groups.remove(&addr);
+        }
+
+        match self.inner.multicast.igmp_report_state {
+            IgmpReportState::ToSpecificQuery {
+                version,
+                timeout,
+                group,
+            } if self.inner.now >= timeout => {
+                if let Some(pkt) = self.inner.igmp_report_packet(version, group) {
+                    // Send initial membership report
+                    if let Some(tx_token) = device.transmit(self.inner.now) {
+                        // NOTE(unwrap): packet destination is multicast, which is always routable and doesn't require neighbor discovery.
+                        self.inner
+                            .dispatch_ip(tx_token, PacketMeta::default(), pkt, &mut self.fragmenter)
+                            .unwrap();
+                        self.inner.multicast.igmp_report_state = IgmpReportState::Inactive;
+                    }
+                } else {
+                    self.inner.multicast.igmp_report_state = IgmpReportState::Inactive;
+                }
+            }
+            IgmpReportState::ToGeneralQuery {
+                version,
+                timeout,
+                interval,
+                next_index,
+            } if self.inner.now >= timeout => {
+                let addr = self
+                    .inner
+                    .multicast
+                    .groups
+                    .keys()
+                    .filter_map(|addr| match addr {
+                        IpAddress::Ipv4(addr) => Some(*addr),
+                        _ => None,
+                    })
+                    .nth(next_index);
+
+                match addr.and_then(|addr| self.inner.igmp_report_packet(version, addr)) {
+                    Some(pkt) => {
+                        // Send the membership report for the next group
+                        if let Some(tx_token) = device.transmit(self.inner.now) {
+                            // NOTE(unwrap): packet destination is multicast, which is always routable and doesn't require neighbor discovery.
+                            self.inner
+                                .dispatch_ip(
+                                    tx_token,
+                                    PacketMeta::default(),
+                                    pkt,
+                                    &mut self.fragmenter,
+                                )
+                                .unwrap();
+
+                            let next_timeout = (timeout + interval).max(self.inner.now);
+                            self.inner.multicast.igmp_report_state =
+                                IgmpReportState::ToGeneralQuery {
+                                    version,
+                                    timeout: next_timeout,
+                                    interval,
+                                    next_index: next_index + 1,
+                                };
+                        }
+                    }
+                    None => {
+                        self.inner.multicast.igmp_report_state = IgmpReportState::Inactive;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+impl InterfaceInner {
+    /// Check whether the interface listens to given destination multicast IP address.
+    pub(super) fn has_multicast_group<T: Into<IpAddress>>(&self, addr: T) -> bool {
+        let addr = addr.into();
+        if self.multicast.has_multicast_group(addr) {
+            return true;
+        }
+
+        match addr {
+            IpAddress::Ipv4(key) => key == IPV4_MULTICAST_ALL_SYSTEMS,
+            IpAddress::Ipv6(key) => {
+                key == IPV6_LINK_LOCAL_ALL_NODES
+                    || self.has_solicited_node(key)
+                    || self.slaac.has_tentative_solicited_node(&key)
+            }
+        }
+    }
+
+    /// Host duties of the **IGMPv1**, **IGMPv2** and **IGMPv3** protocols.
+    ///
+    /// Sets up `igmp_report_state` for responding to IGMP general/specific membership queries.
+    /// Membership must not be reported immediately in order to avoid flooding the network
+    /// after a query is broadcasted by a router; reports are spread evenly across the
+    /// maximum response time instead of using a random delay.
+    pub(super) fn process_igmp<'frame>(
+        &mut self,
+        ipv4_repr: Ipv4Repr,
+        ip_payload: &'frame [u8],
+    ) -> Option<Packet<'frame>> {
+        let igmp_packet = check!(IgmpPacket::new_checked(ip_payload));
+        let igmp_repr = check!(IgmpRepr::parse(&igmp_packet));
+
+        match igmp_repr {
+            IgmpRepr::MembershipQuery {
+                group_addr,
+                version,
+                max_resp_time,
+            } => {
+                // An older version querier forces us into compatibility mode.
+                // See RFC 3376: 7.2.1. In the Presence of Older Version Queriers
+                match version {
+                    IgmpVersion::Version1 => {
+                        self.multicast.igmp_older_querier = Some((
+                            IgmpVersion::Version1,
+                            self.now + IGMP_OLDER_QUERIER_PRESENT_TIMEOUT,
+                        ))
+                    }
+                    IgmpVersion::Version2
+                        if self.multicast.igmp_version(self.now) != IgmpVersion::Version1 =>
+                    {
+                        self.multicast.igmp_older_querier = Some((
+                            IgmpVersion::Version2,
+                            self.now + IGMP_OLDER_QUERIER_PRESENT_TIMEOUT,
+                        ))
+                    }
+                    _ => (),
+                }
+                let version = self.multicast.igmp_version(self.now);
+
+                // General query
+                if group_addr.is_unspecified() && ipv4_repr.dst_addr == IPV4_MULTICAST_ALL_SYSTEMS {
+                    let ipv4_multicast_group_count = self
+                        .multicast
+                        .groups
+                        .keys()
+                        .filter(|a| matches!(a, IpAddress::Ipv4(_)))
+                        .count();
+
+                    // Are we member in any groups?
+                    if ipv4_multicast_group_count != 0 {
+                        let interval = match version {
+                            IgmpVersion::Version1 => Duration::from_millis(100),
+                            IgmpVersion::Version2 | IgmpVersion::Version3 => {
+                                // No dependence on a random generator
+                                // (see [#24](https://github.com/m-labs/smoltcp/issues/24))
+                                // but at least spread reports evenly across max_resp_time.
+                                let intervals = ipv4_multicast_group_count as u32 + 1;
+                                max_resp_time / intervals
+                            }
+                        };
+                        self.multicast.igmp_report_state = IgmpReportState::ToGeneralQuery {
+                            version,
+                            timeout: self.now + interval,
+                            interval,
+                            next_index: 0,
+                        };
+                    }
+                } else {
+                    // Group-specific query
+                    if self.multicast.has_multicast_group(group_addr)
+                        && ipv4_repr.dst_addr == group_addr
+                    {
+                        // Don't respond immediately
+                        let timeout = max_resp_time / 4;
+                        self.multicast.igmp_report_state = IgmpReportState::ToSpecificQuery {
+                            version,
+                            timeout: self.now + timeout,
+                            group: group_addr,
+                        };
+                    }
+                }
+            }
+            // Ignore membership reports
+            IgmpRepr::MembershipReport { .. } | IgmpRepr::MembershipReportV3 { .. } => (),
+            // Ignore hosts leaving groups
+            IgmpRepr::LeaveGroup { .. } => (),
+        }
+
+        None
+    }
+
+    /// Build the packet announcing a change of membership to `group_addr`.
+    ///
+    /// IGMPv1 and IGMPv2 hosts send a membership report to the group itself,
+    /// IGMPv3 hosts a state-change record to all IGMPv3 routers.
+    fn igmp_join_packet<'any>(
+        &self,
+        version: IgmpVersion,
+        group_addr: Ipv4Address,
+    ) -> Option<Packet<'any>> {
+        match version {
+            IgmpVersion::Version3 => self.igmp_packet(
+                IPV4_MULTICAST_ALL_IGMPV3_ROUTERS,
+                IgmpRepr::MembershipReportV3 {
+                    group_addr,
+                    record_type: IgmpRecordType::ChangeToExclude,
+                },
+            ),
+            _ => self.igmp_report_packet(version, group_addr),
+        }
+    }
+
+    /// Build the packet answering a membership query for `group_addr`.
+    fn igmp_report_packet<'any>(
+        &self,
+        version: IgmpVersion,
+        group_addr: Ipv4Address,
+    ) -> Option<Packet<'any>> {
+        let dst_addr = match version {
+            IgmpVersion::Version3 => IPV4_MULTICAST_ALL_IGMPV3_ROUTERS,
+            // Send to the group being reported
+            _ => group_addr,
+        };
+        self.igmp_packet(
+            dst_addr,
+            IgmpRepr::MembershipReport {
+                group_addr,
+                version,
+            },
+        )
+    }
+
+    /// Build the packet leaving `group_addr`.
+    ///
+    /// IGMPv1 has no leave message, so `None` is returned in that case.
+    fn igmp_leave_packet<'any>(
+        &self,
+        version: IgmpVersion,
+        group_addr: Ipv4Address,
+    ) -> Option<Packet<'any>> {
+        match version {
+            IgmpVersion::Version1 => None,
+            IgmpVersion::Version2 => self.igmp_packet(
+                IPV4_MULTICAST_ALL_ROUTERS,
+                IgmpRepr::LeaveGroup { group_addr },
+            ),
+            IgmpVersion::Version3 => self.igmp_packet(
+                IPV4_MULTICAST_ALL_IGMPV3_ROUTERS,
+                IgmpRepr::MembershipReportV3 {
+                    group_addr,
+                    record_type: IgmpRecordType::ChangeToInclude,
+                },
+            ),
+        }
+    }
+
+    fn igmp_packet<'any>(
+        &self,
+        dst_addr: Ipv4Address,
+        igmp_repr: IgmpRepr,
+    ) -> Option<Packet<'any>> {
+        let iface_addr = self.ipv4_addr()?;
+        Some(Packet::new_ipv4(
+            Ipv4Repr {
+                src_addr: iface_addr,
+                dst_addr,
+                next_header: IpProtocol::Igmp,
+                payload_len: igmp_repr.buffer_len(),
+                hop_limit: 1,
+                // [#183](https://github.com/m-labs/smoltcp/issues/183).
+            },
+            IpPayload::Igmp(igmp_repr),
+        ))
+    }
+}
@@ -0,0 +1,415 @@
+use super::{Interface, InterfaceInner};
+use super::{IpPayload, Packet};
+use crate::iface::Route;
+use crate::phy::{Device, PacketMeta};
+use crate::time::{Duration, Instant};
+use crate::wire::*;
+
+/// Number of Router Solicitations sent before giving up, see [RFC 4861 § 10].
+///
+/// [RFC 4861 § 10]: https://tools.ietf.org/html/rfc4861#section-10
+const MAX_RTR_SOLICITATIONS: u8 = 3;
+
+/// Delay between Router Solicitations, see [RFC 4861 § 10].
+///
+/// [RFC 4861 § 10]: https://tools.ietf.org/html/rfc4861#section-10
+const RTR_SOLICITATION_INTERVAL: Duration = Duration::from_secs(4);
+
+/// Number of Neighbor Solicitations sent while performing Duplicate Address
+/// Detection, see [RFC 4862 § 5.1].
+///
+/// [RFC 4862 § 5.1]: https://tools.ietf.org/html/rfc4862#section-5.1
+const DUP_ADDR_DETECT_TRANSMITS: u8 = 1;
+
+/// Delay between Neighbor Solicitations, see [RFC 4861 § 10].
+///
+/// [RFC 4861 § 10]: https://tools.ietf.org/html/rfc4861#section-10
+const RETRANS_TIMER: Duration = Duration::from_secs(1);
+
+/// Lower bound for shortening the valid lifetime of an address with an
+/// unauthenticated Router Advertisement, see [RFC 4862 § 5.5.3].
+///
+/// [RFC 4862 § 5.5.3]: https://tools.ietf.org/html/rfc4862#section-5.5.3
+const TWO_HOURS: Duration = Duration::from_secs(2 * 60 * 60);
+
+/// A lifetime of all one bits means infinity, see [RFC 4861 § 4.6.2].
+///
+/// [RFC 4861 § 4.6.2]: https://tools.ietf.org/html/rfc4861#section-4.6.2
+const INFINITE_LIFETIME: Duration = Duration::from_secs(0xffff_ffff);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum AddressState {
+    /// Duplicate Address Detection is running, `remaining` Neighbor Solicitations
+    /// are left to send and the next one (or the verdict) is due at `timeout`.
+    Tentative { remaining: u8, timeout: Instant },
+    /// The address passed Duplicate Address Detection and is assigned to the interface.
+    Assigned,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Address {
+    cidr: Ipv6Cidr,
+    state: AddressState,
+    /// `None` means "forever".
+    preferred_until: Option<Instant>,
+    /// `None` means "forever".
+    valid_until: Option<Instant>,
+}
+
+pub(crate) struct State {
+    enabled: bool,
+    /// Addresses that are being configured, or have been configured, by SLAAC.
+    addrs: Vec<Address>,
+    /// Number of Router Solicitations sent and when to send the next one,
+    /// `None` when not soliciting.
+    solicit: Option<(u8, Instant)>,
+}
+
+impl State {
+    pub(crate) fn new(enabled: bool, hardware_addr: HardwareAddress, now: Instant) -> Self {
+        let mut addrs = Vec::new();
+        if enabled {
+            // Every SLAAC host starts with a link-local address, see RFC 4862 § 5.3.
+            let link_local = Ipv6Address::new(0xfe80, 0, 0, 0, 0, 0, 0, 0);
+            addrs.push(Address {
+                cidr: Ipv6Cidr::new(address_from_prefix(&link_local, &hardware_addr), 64),
+                state: AddressState::Tentative {
+                    remaining: DUP_ADDR_DETECT_TRANSMITS,
+                    timeout: now,
+                },
+                preferred_until: None,
+                valid_until: None,
+            });
+        }
+
+        Self {
+            enabled,
+            addrs,
+            solicit: None,
+        }
+    }
+
+    /// Query whether `addr` is undergoing Duplicate Address Detection.
+    pub(crate) fn is_tentative(&self, addr: &Ipv6Address) -> bool {
+        self.addrs
+            .iter()
+            .any(|a| a.cidr.address() == *addr && matches!(a.state, AddressState::Tentative { .. }))
+    }
+
+    /// Query whether `addr` is the solicited-node multicast address of an address
+    /// undergoing Duplicate Address Detection, see [RFC 4862 § 5.4.2].
+    ///
+    /// [RFC 4862 § 5.4.2]: https://tools.ietf.org/html/rfc4862#section-5.4.2
+    pub(crate) fn has_tentative_solicited_node(&self, addr: &Ipv6Address) -> bool {
+        self.addrs.iter().any(|a| {
+            matches!(a.state, AddressState::Tentative { .. })
+                && a.cidr.address().solicited_node() == *addr
+        })
+    }
+
+    /// Query whether the preferred lifetime of `addr` is over, see [RFC 4862 § 5.5.4].
+    ///
+    /// [RFC 4862 § 5.5.4]: https://tools.ietf.org/html/rfc4862#section-5.5.4
+    pub(crate) fn is_deprecated(&self, addr: &Ipv6Address, now: Instant) -> bool {
+        self.addrs.iter().any(|a| {
+            a.cidr.address() == *addr && a.preferred_until.is_some_and(|until| now >= until)
+        })
+    }
+
+    /// Return the next time SLAAC egress has something to do.
+    pub(crate) fn poll_at(&self) -> Option<Instant> {
+        let timeouts = self.addrs.iter().filter_map(|a| match a.state {
+            AddressState::Tentative { timeout, .. } => Some(timeout),
+            AddressState::Assigned => a.valid_until,
+        });
+
+        timeouts.chain(self.solicit.map(|(_, at)| at)).min()
+    }
+}
+
+impl Interface {
+    /// Do SLAAC egress.
+    ///
+    /// - Drop addresses and routes whose lifetime is over.
+    /// - Run Duplicate Address Detection for tentative addresses, and assign them
+    ///   to the interface once it succeeds.
+    /// - Send Router Solicitations after the link-local address is assigned.
+    pub(crate) fn slaac_egress(&mut self, device: &mut (impl Device + ?Sized)) {
+        if !self.inner.slaac.enabled {
+            return;
+        }
+
+        let now = self.inner.now;
+        self.inner.slaac_expire();
+
+        for i in 0..self.inner.slaac.addrs.len() {
+            let addr = self.inner.slaac.addrs[i];
+            let AddressState::Tentative { remaining, timeout } = addr.state else {
+                continue;
+            };
+            if now < timeout {
+                continue;
+            }
+
+            if remaining == 0 {
+                self.inner.slaac.addrs[i].state = AddressState::Assigned;
+                if self.inner.ip_addrs.len() < self.inner.limits.max_ip_addrs {
+                    net_debug!("SLAAC: assigned address {}", addr.cidr);
+                    self.inner.ip_addrs.push(IpCidr::Ipv6(addr.cidr));
+                } else {
+                    net_debug!("SLAAC: address table full, not assigning {}", addr.cidr);
+                }
+
+                if addr.cidr.address().is_link_local() {
+                    self.inner.slaac.solicit = Some((0, now));
+                }
+                continue;
+            }
+
+            let Some(tx_token) = device.transmit(now) else {
+                return;
+            };
+
+            // NOTE(unwrap): packet destination is multicast, which is always routable and doesn't require neighbor discovery.
+            let pkt = self.inner.dad_solicit_packet(addr.cidr.address());
+            self.inner
+                .dispatch_ip(tx_token, PacketMeta::default(), pkt, &mut self.fragmenter)
+                .unwrap();
+
+            self.inner.slaac.addrs[i].state = AddressState::Tentative {
+                remaining: remaining - 1,
+                timeout: now + RETRANS_TIMER,
+            };
+        }
+
+        match self.inner.slaac.solicit {
+            Some((sent, at)) if now >= at => {
+                let Some(tx_token) = device.transmit(now) else {
+                    return;
+                };
+
+                // NOTE(unwrap): packet destination is multicast, which is always routable and doesn't require neighbor discovery.
+                let pkt = self.inner.router_solicit_packet();
+                self.inner
+                    .dispatch_ip(tx_token, PacketMeta::default(), pkt, &mut self.fragmenter)
+                    .unwrap();
+
+                self.inner.slaac.solicit = if sent + 1 < MAX_RTR_SOLICITATIONS {
+                    Some((sent + 1, now + RTR_SOLICITATION_INTERVAL))
+                } else {
+                    None
+                };
+            }
+            _ => {}
+        }
+    }
+}
+
+impl InterfaceInner {
+    /// Drop the SLAAC addresses and the routes whose valid lifetime is over.
+    fn slaac_expire(&mut self) {
+        let now = self.now;
+
+        let ip_addrs = &mut self.ip_addrs;
+        self.slaac.addrs.retain(|a| {
+            let expired = a.valid_until.is_some_and(|until| now >= until);
+            if expired {
+                net_debug!("SLAAC: address {} expired", a.cidr);
+                ip_addrs.retain(|cidr| *cidr != IpCidr::Ipv6(a.cidr));
+            }
+            !expired
+        });
+
+        // Removing routes can't overflow the table.
+        let _ = self.routes.update(|routes| {
+            routes.retain(|r| r.expires_at.map_or(true, |expires_at| now < expires_at))
+        });
+    }
+
+    /// Neighbor Solicitation probing whether `target_addr` is already in use on
+    /// the link, see [RFC 4862 § 5.4.2].
+    ///
+    /// [RFC 4862 § 5.4.2]: https://tools.ietf.org/html/rfc4862#section-5.4.2
+    fn dad_solicit_packet(&self, target_addr: Ipv6Address) -> Packet<'static> {
+        // The source address is unspecified, so no link-layer address option may be included.
+        let solicit = Icmpv6Repr::Ndisc(NdiscRepr::NeighborSolicit {
+            target_addr,
+            lladdr: None,
+        });
+
+        Packet::new_ipv6(
+            Ipv6Repr {
+                src_addr: Ipv6Address::UNSPECIFIED,
+                dst_addr: target_addr.solicited_node(),
+                next_header: IpProtocol::Icmpv6,
+                payload_len: solicit.buffer_len(),
+                hop_limit: 0xff,
+            },
+            IpPayload::Icmpv6(solicit),
+        )
+    }
+
+    /// Router Solicitation sent to all routers, see [RFC 4861 § 6.3.7].
+    ///
+    /// [RFC 4861 § 6.3.7]: https://tools.ietf.org/html/rfc4861#section-6.3.7
+    fn router_solicit_packet(&self) -> Packet<'static> {
+        let src_addr = self
+            .ip_addrs
+            .iter()
+            .find_map(|cidr| match cidr {
+                IpCidr::Ipv6(cidr) if cidr.address().is_link_local() => Some(cidr.address()),
+                _ => None,
+            })
+            .unwrap_or(Ipv6Address::UNSPECIFIED);
+
+        let solicit = Icmpv6Repr::Ndisc(NdiscRepr::RouterSolicit {
+            lladdr: (!src_addr.is_unspecified()).then(|| self.hardware_addr.into()),
+        });
+
+        Packet::new_ipv6(
+            Ipv6Repr {
+                src_addr,
+                dst_addr: IPV6_LINK_LOCAL_ALL_ROUTERS,
+                next_header: IpProtocol::Icmpv6,
+                payload_len: solicit.buffer_len(),
+                hop_limit: 0xff,
+            },
+            IpPayload::Icmpv6(solicit),
+        )
+    }
+
+    /// Give up on a tentative address after another node turned out to use it,
+    /// see [RFC 4862 § 5.4.5].
+    ///
+    /// [RFC 4862 § 5.4.5]: https://tools.ietf.org/html/rfc4862#section-5.4.5
+    pub(super) fn slaac_duplicate_address(&mut self, addr: Ipv6Address) {
+        net_debug!("SLAAC: duplicate address {} detected", addr);
+        self.slaac.addrs.retain(|a| a.cidr.address() != addr);
+    }
+
+    /// Update the default route and the addresses of the interface from a
+    /// Router Advertisement, see [RFC 4861 § 6.3.4] and [RFC 4862 § 5.5.3].
+    ///
+    /// [RFC 4861 § 6.3.4]: https://tools.ietf.org/html/rfc4861#section-6.3.4
+    /// [RFC 4862 § 5.5.3]: https://tools.ietf.org/html/rfc4862#section-5.5.3
+    pub(super) fn slaac_process_router_advert(
+        &mut self,
+        router_addr: Ipv6Address,
+        router_lifetime: Duration,
+        prefix_info: Option<NdiscPrefixInformation>,
+    ) {
+        if !self.slaac.enabled {
+            return;
+        }
+
+        // A router answered, stop soliciting.
+        self.slaac.solicit = None;
+
+        let now = self.now;
+        let default_route = Route::new_ipv6_gateway(router_addr);
+        let result = self.routes.update(|routes| {
+            routes.retain(|r| r.cidr != default_route.cidr || r.via_router != router_addr.into());
+            if router_lifetime != Duration::ZERO {
+                routes.push(Route {
+                    preferred_until: Some(now + router_lifetime),
+                    expires_at: Some(now + router_lifetime),
+                    ..default_route
+                });
+            }
+        });
+        if result.is_err() {
+            net_debug!(
+                "SLAAC: route table full, ignoring default route via {}",
+                router_addr
+            );
+        }
+
+        let Some(prefix_info) = prefix_info else {
+            return;
+        };
+
+        if !prefix_info.flags.contains(NdiscPrefixInfoFlags::ADDRCONF)
+            || prefix_info.prefix.is_link_local()
+            || prefix_info.preferred_lifetime > prefix_info.valid_lifetime
+        {
+            return;
+        }
+
+        // The interface identifier is 64 bits long, see RFC 4291 § 2.5.1.
+        if prefix_info.prefix_len != 64 {
+            net_debug!(
+                "SLAAC: ignoring prefix {}/{}, the prefix length must be 64",
+                prefix_info.prefix,
+                prefix_info.prefix_len
+            );
+            return;
+        }
+
+        let lifetime_end = |lifetime: Duration| {
+            if lifetime == INFINITE_LIFETIME {
+                None
+            } else {
+                Some(now + lifetime)
+            }
+        };
+        let preferred_until = lifetime_end(prefix_info.preferred_lifetime);
+        let valid_until = lifetime_end(prefix_info.valid_lifetime);
+
+        let addr = address_from_prefix(&prefix_info.prefix, &self.hardware_addr);
+        if let Some(existing) = self
+            .slaac
+            .addrs
+            .iter_mut()
+            .find(|a| a.cidr.address() == addr)
+        {
+            existing.preferred_until = preferred_until;
+
+            // Only shorten the remaining valid lifetime down to two hours, so a
+            // spoofed advertisement can not make the address vanish at once.
+            let remaining = existing.valid_until.map(|until| until - now);
+            existing.valid_until = match (valid_until, remaining) {
+                (None, _) => None,
+                (Some(_), _) if prefix_info.valid_lifetime > TWO_HOURS => valid_until,
+                (Some(_), Some(remaining)) if prefix_info.valid_lifetime > remaining => valid_until,
+                (Some(_), Some(remaining)) if remaining <= TWO_HOURS => existing.valid_until,
+                (Some(_), _) => Some(now + TWO_HOURS),
+            };
+            return;
+        }
+
+        if prefix_info.valid_lifetime == Duration::ZERO {
+            return;
+        }
+
+        net_debug!("SLAAC: configuring address {} from {}", addr, router_addr);
+        self.slaac.addrs.push(Address {
+            cidr: Ipv6Cidr::new(addr, 64),
+            state: AddressState::Tentative {
+                remaining: DUP_ADDR_DETECT_TRANSMITS,
+                timeout: now,
+            },
+            preferred_until,
+            valid_until,
+        });
+    }
+}
+
+/// Form an address from the upper 64 bits of `prefix` and the modified EUI-64
+/// interface identifier of `hardware_addr`, see [RFC 4291 Appendix A].
+///
+/// [RFC 4291 Appendix A]: https://tools.ietf.org/html/rfc4291#appendix-A
+fn address_from_prefix(prefix: &Ipv6Address, hardware_addr: &HardwareAddress) -> Ipv6Address {
+    let mac = hardware_addr.ethernet_or_panic().0;
+    let mut bytes = prefix.octets();
+    bytes[8..].copy_from_slice(&[
+        mac[0] ^ 0x02,
+        mac[1],
+        mac[2],
+        0xff,
+        0xfe,
+        mac[3],
+        mac[4],
+        mac[5],
+    ]);
+    Ipv6Address::from(bytes)
+}
@@ -0,0 +1,360 @@
+use super::*;
+
+use crate::iface::neighbor::Answer;
+use crate::iface::socket_meta::Meta as SocketMeta;
+use crate::iface::{Action, Chain, Limits, Route, RouteTableFull, Rule, RuleTableFull};
+use crate::phy::Loopback;
+use crate::socket::udp;
+use crate::wire::{IpEndpoint, Ipv4Cidr, Ipv4Packet, UdpPacket, UdpRepr};
+
+const GATEWAY_A: Ipv4Address = Ipv4Address::new(192, 168, 1, 2);
+const GATEWAY_B: Ipv4Address = Ipv4Address::new(192, 168, 1, 3);
+const REMOTE_ADDR: Ipv4Address = Ipv4Address::new(10, 0, 0, 1);
+
+fn create(addrs: &[IpCidr]) -> (Interface, Loopback) {
+    let mut device = Loopback::new(Medium::Ethernet);
+    let config = Config::new(EthernetAddress([0x02, 0x00, 0x00, 0x00, 0x00, 0x01]).into());
+    let mut iface = Interface::new(config, &mut device, Instant::ZERO);
+    iface
+        .update_ip_addrs(|ip_addrs| ip_addrs.extend_from_slice(addrs))
+        .unwrap();
+    (iface, device)
+}
+
+fn resolve(iface: &mut Interface, addr: impl Into<IpAddress>) {
+    iface.inner.neighbor_cache.fill(
+        addr.into(),
+        EthernetAddress([0x02, 0x00, 0x00, 0x00, 0x00, 0x02]).into(),
+        Instant::ZERO,
+    );
+}
+
+/// Add a UDP socket bound to `local` with a datagram to `remote` queued.
+fn udp_socket(sockets: &mut SocketSet, local: IpEndpoint, remote: IpEndpoint) {
+    let mut socket = udp::Socket::new(
+        udp::PacketBuffer::new(vec![udp::PacketMetadata::EMPTY; 1], vec![0; 64]),
+        udp::PacketBuffer::new(vec![udp::PacketMetadata::EMPTY; 1], vec![0; 64]),
+    );
+    socket.bind(local).unwrap();
+    socket.send_slice(b"abcdefg", remote).unwrap();
+    sockets.add(socket);
+}
+
+/// Build an IPv4 packet holding an empty UDP datagram.
+fn udp_packet(src_addr: Ipv4Address, dst_addr: Ipv4Address) -> Vec<u8> {
+    let checksum = ChecksumCapabilities::default();
+    let udp_repr = UdpRepr {
+        src_port: 1234,
+        dst_port: 53,
+    };
+    let ipv4_repr = Ipv4Repr {
+        src_addr,
+        dst_addr,
+        next_header: IpProtocol::Udp,
+        payload_len: udp_repr.header_len(),
+        hop_limit: 64,
+    };
+    let mut buffer = vec![0; ipv4_repr.buffer_len() + ipv4_repr.payload_len];
+    let mut packet = Ipv4Packet::new_unchecked(&mut buffer[..]);
+    ipv4_repr.emit(&mut packet, &checksum);
+    udp_repr.emit(
+        &mut UdpPacket::new_unchecked(packet.payload_mut()),
+        &src_addr.into(),
+        &dst_addr.into(),
+        0,
+        |_| (),
+        &checksum,
+    );
+    buffer
+}
+
+#[test]
+fn test_waiting_for_source_constrained_next_hop() {
+    let (mut iface, mut device) = create(&[
+        IpCidr::new(IpAddress::v4(192, 168, 1, 1), 24),
+        IpCidr::new(IpAddress::v4(192, 168, 2, 1), 24),
+    ]);
+    // Packets from 192.168.2.0/24 leave through gateway A, the others through B.
+    iface
+        .routes_mut()
+        .add_route(Route {
+            src_cidr: Some(Ipv4Cidr::new(Ipv4Address::new(192, 168, 2, 0), 24).into()),
+            ..Route::new_ipv4_gateway(GATEWAY_A)
+        })
+        .unwrap();
+    iface
+        .routes_mut()
+        .add_route(Route {
+            metric: 10,
+            ..Route::new_ipv4_gateway(GATEWAY_B)
+        })
+        .unwrap();
+    resolve(&mut iface, GATEWAY_A);
+
+    let mut sockets = SocketSet::new(vec![]);
+    udp_socket(
+        &mut sockets,
+        IpEndpoint::new(IpAddress::v4(192, 168, 1, 1), 1234),
+        IpEndpoint::new(REMOTE_ADDR.into(), 53),
+    );
+
+    // The datagram waits for gateway B, and gateway A being known doesn't wake
+    // the socket up.
+    let now = Instant::ZERO;
+    iface.poll(now, &mut device, &mut sockets);
+    assert_eq!(
+        iface.poll_at(now, &sockets),
+        Some(now + SocketMeta::DISCOVERY_SILENT_TIME)
+    );
+
+    resolve(&mut iface, GATEWAY_B);
+    assert_eq!(iface.poll_at(now, &sockets), Some(Instant::ZERO));
+}
+
+#[test]
+fn test_waiting_for_equal_cost_next_hop() {
+    let local_addr = Ipv4Address::new(192, 168, 1, 1);
+    let (mut iface, mut device) = create(&[IpCidr::new(local_addr.into(), 24)]);
+    iface
+        .routes_mut()
+        .update(|routes| {
+            routes.push(Route::new_ipv4_gateway(GATEWAY_A));
+            routes.push(Route::new_ipv4_gateway(GATEWAY_B));
+        })
+        .unwrap();
+
+    // Pick a flow that doesn't go through the gateway used when the flow is
+    // ignored, and only know that other gateway.
+    let next_hop = |iface: &Interface, flow_hash| {
+        iface
+            .inner
+            .route(
+                &REMOTE_ADDR.into(),
+                Some(&local_addr.into()),
+                flow_hash,
+                Instant::ZERO,
+            )
+            .unwrap()
+    };
+    let (local_port, known, unknown) = (1024..)
+        .find_map(|local_port| {
+            let packet = Packet::new_ipv4(
+                Ipv4Repr {
+                    src_addr: local_addr,
+                    dst_addr: REMOTE_ADDR,
+                    next_header: IpProtocol::Udp,
+                    payload_len: 0,
+                    hop_limit: 64,
+                },
+                IpPayload::Udp(
+                    UdpRepr {
+                        src_port: local_port,
+                        dst_port: 53,
+                    },
+                    &[],
+                ),
+            );
+            let known = next_hop(&iface, 0);
+            let unknown = next_hop(&iface, packet.flow_hash());
+            (known != unknown).then_some((local_port, known, unknown))
+        })
+        .unwrap();
+    resolve(&mut iface, known);
+
+    let mut sockets = SocketSet::new(vec![]);
+    udp_socket(
+        &mut sockets,
+        IpEndpoint::new(local_addr.into(), local_port),
+        IpEndpoint::new(REMOTE_ADDR.into(), 53),
+    );
+
+    let now = Instant::ZERO;
+    iface.poll(now, &mut device, &mut sockets);
+    assert_eq!(
+        iface.poll_at(now, &sockets),
+        Some(now + SocketMeta::DISCOVERY_SILENT_TIME)
+    );
+
+    resolve(&mut iface, unknown);
+    assert_eq!(iface.poll_at(now, &sockets), Some(Instant::ZERO));
+}
+
+#[test]
+fn test_send_to_multicast_group() {
+    let local_addr = Ipv4Address::new(192, 168, 1, 1);
+    let (mut iface, mut device) = create(&[IpCidr::new(local_addr.into(), 24)]);
+
+    let mut sockets = SocketSet::new(vec![]);
+    udp_socket(
+        &mut sockets,
+        IpEndpoint::new(local_addr.into(), 5353),
+        IpEndpoint::new(IpAddress::v4(224, 0, 0, 251), 5353),
+    );
+
+    // Multicast groups have no route and need no neighbor discovery.
+    iface.poll(Instant::ZERO, &mut device, &mut sockets);
+    assert!(sockets.items().all(|item| match &item.socket {
+        Socket::Udp(socket) => socket.send_queue() == 0,
+        _ => true,
+    }));
+}
+
+#[test]
+fn test_input_chain_ignores_packets_not_for_us() {
+    let local_addr = Ipv4Address::new(192, 168, 1, 1);
+    let (mut iface, _device) = create(&[IpCidr::new(local_addr.into(), 24)]);
+    iface
+        .firewall_mut()
+        .set_policy(Chain::Input, Action::Reject);
+    let source_hardware_addr = EthernetAddress([0x02, 0x00, 0x00, 0x00, 0x00, 0x02]).into();
+    let mut sockets = SocketSet::new(vec![]);
+
+    // Packets for us are rejected with an ICMP error.
+    let packet = udp_packet(GATEWAY_A, local_addr);
+    let mut frag = FragmentsBuffer {};
+    let reply = iface.inner.process_ipv4(
+        &mut sockets,
+        PacketMeta::default(),
+        source_hardware_addr,
+        &Ipv4Packet::new_unchecked(&packet[..]),
+        &mut frag,
+    );
+    assert!(matches!(
+        reply.as_ref().map(Packet::payload),
+        Some(IpPayload::Icmpv4(Icmpv4Repr::DstUnreachable {
+            reason: Icmpv4DstUnreachable::CommProhibited,
+            ..
+        }))
+    ));
+
+    // Packets for others are ignored before reaching the firewall.
+    let packet = udp_packet(GATEWAY_A, REMOTE_ADDR);
+    let mut frag = FragmentsBuffer {};
+    let reply = iface.inner.process_ipv4(
+        &mut sockets,
+        PacketMeta::default(),
+        source_hardware_addr,
+        &Ipv4Packet::new_unchecked(&packet[..]),
+        &mut frag,
+    );
+    assert!(reply.is_none());
+}
+
+#[test]
+fn test_redirect_from_non_router_ignored() {
+    let router = Ipv6Address::new(0xfe80, 0, 0, 0, 0, 0, 0, 0xff);
+    let target_addr = Ipv6Address::new(0xfe80, 0, 0, 0, 0, 0, 0, 0xaa);
+    let (mut iface, _device) = create(&[
+        IpCidr::new(IpAddress::v6(0xfe80, 0, 0, 0, 0, 0, 0, 1), 64),
+        IpCidr::new(IpAddress::v6(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1), 64),
+    ]);
+    iface.routes_mut().add_default_ipv6_route(router).unwrap();
+
+    let redirect = |iface: &mut Interface, src_addr| {
+        let repr = NdiscRepr::Redirect {
+            target_addr,
+            dest_addr: Ipv6Address::new(0x2001, 0xdb8, 1, 0, 0, 0, 0, 5),
+            lladdr: Some(EthernetAddress([0x02, 0x00, 0x00, 0x00, 0x00, 0xaa]).into()),
+            redirected_hdr: None,
+        };
+        let ip_repr = Ipv6Repr {
+            src_addr,
+            dst_addr: Ipv6Address::new(0xfe80, 0, 0, 0, 0, 0, 0, 1),
+            next_header: IpProtocol::Icmpv6,
+            payload_len: Icmpv6Repr::Ndisc(repr).buffer_len(),
+            hop_limit: 0xff,
+        };
+        iface.inner.process_ndisc(ip_repr, repr);
+        matches!(
+            iface
+                .inner
+                .neighbor_cache
+                .lookup(&target_addr.into(), Instant::ZERO),
+            Answer::Found(_)
+        )
+    };
+
+    // Another host on the link can't redirect us.
+    assert!(!redirect(
+        &mut iface,
+        Ipv6Address::new(0xfe80, 0, 0, 0, 0, 0, 0, 0xbb)
+    ));
+    // Neither can the router when redirects are disabled.
+    iface.set_accept_redirects(false);
+    assert!(!redirect(&mut iface, router));
+    iface.set_accept_redirects(true);
+    assert!(redirect(&mut iface, router));
+}
+
+#[test]
+fn test_limits() {
+    let mut device = Loopback::new(Medium::Ethernet);
+    let mut config = Config::new(EthernetAddress([0x02, 0x00, 0x00, 0x00, 0x00, 0x01]).into());
+    config.limits = Limits {
+        max_ip_addrs: 1,
+        max_routes: 1,
+        neighbor_cache_size: 1,
+        max_multicast_groups: 1,
+        max_firewall_rules: 1,
+        ..Limits::default()
+    };
+    let mut iface = Interface::new(config, &mut device, Instant::ZERO);
+    assert_eq!(iface.limits().max_ip_addrs, 1);
+
+    let addr = IpCidr::new(IpAddress::v4(192, 168, 1, 1), 24);
+    iface
+        .update_ip_addrs(|ip_addrs| ip_addrs.push(addr))
+        .unwrap();
+    assert_eq!(
+        iface.update_ip_addrs(
+            |ip_addrs| ip_addrs.push(IpCidr::new(IpAddress::v4(192, 168, 2, 1), 24))
+        ),
+        Err(AddrTableFull)
+    );
+    assert_eq!(iface.ip_addrs(), [addr]);
+
+    iface
+        .routes_mut()
+        .add_default_ipv4_route(GATEWAY_A)
+        .unwrap();
+    assert_eq!(
+        iface
+            .routes_mut()
+            .add_ipv4_host_route(REMOTE_ADDR, GATEWAY_B, None),
+        Err(RouteTableFull)
+    );
+
+    iface
+        .join_multicast_group(Ipv4Address::new(224, 0, 0, 251))
+        .unwrap();
+    assert_eq!(
+        iface.join_multicast_group(Ipv4Address::new(224, 0, 0, 252)),
+        Err(MulticastError::GroupTableFull)
+    );
+
+    iface
+        .firewall_mut()
+        .insert(Chain::Input, 0, Rule::new(Action::Drop))
+        .unwrap();
+    assert_eq!(
+        iface
+            .firewall_mut()
+            .insert(Chain::Input, 0, Rule::new(Action::Accept)),
+        Err(RuleTableFull)
+    );
+
+    // The neighbor cache evicts instead of failing.
+    resolve(&mut iface, GATEWAY_A);
+    resolve(&mut iface, GATEWAY_B);
+    let lookup = |iface: &Interface, addr: Ipv4Address| {
+        matches!(
+            iface
+                .inner
+                .neighbor_cache
+                .lookup(&addr.into(), Instant::ZERO),
+            Answer::Found(_)
+        )
+    };
+    assert!(!lookup(&iface, GATEWAY_A));
+    assert!(lookup(&iface, GATEWAY_B));
+}
@@ -0,0 +1,129 @@
+use super::*;
+
+use crate::socket::dhcpv4_server::Socket as Dhcpv4ServerSocket;
+use crate::socket::dns::Socket as DnsSocket;
+use crate::socket::dns_responder::Socket as DnsResponderSocket;
+use crate::socket::udp::{IcmpErrorKind, Socket as UdpSocket};
+
+impl InterfaceInner {
+    pub(super) fn process_udp<'frame>(
+        &mut self,
+        sockets: &mut SocketSet,
+        meta: PacketMeta,
+        handled_by_raw_socket: bool,
+        ip_repr: IpRepr,
+        ip_payload: &'frame [u8],
+    ) -> Option<Packet<'frame>> {
+        let (src_addr, dst_addr) = (ip_repr.src_addr(), ip_repr.dst_addr());
+        let udp_packet = check!(UdpPacket::new_checked(ip_payload));
+        let udp_repr = check!(UdpRepr::parse(
+            &udp_packet,
+            &src_addr,
+            &dst_addr,
+            &self.caps.checksum
+        ));
+
+        for udp_socket in sockets
+            .items_mut()
+            .filter_map(|i| UdpSocket::downcast_mut(&mut i.socket))
+        {
+            if udp_socket.accepts(self, &ip_repr, &udp_repr) {
+                udp_socket.process(self, meta, &ip_repr, &udp_repr, udp_packet.payload());
+                return None;
+            }
+        }
+
+        if let IpRepr::Ipv4(ipv4_repr) = &ip_repr {
+            for dhcp_socket in sockets
+                .items_mut()
+                .filter_map(|i| Dhcpv4ServerSocket::downcast_mut(&mut i.socket))
+            {
+                if dhcp_socket.accepts(ipv4_repr, &udp_repr) {
+                    dhcp_socket.process(self, ipv4_repr, &udp_repr, udp_packet.payload());
+                    return None;
+                }
+            }
+        }
+
+        for dns_socket in sockets
+            .items_mut()
+            .filter_map(|i| DnsSocket::downcast_mut(&mut i.socket))
+        {
+            if dns_socket.accepts(&ip_repr, &udp_repr) {
+                dns_socket.process(self, &ip_repr, &udp_repr, udp_packet.payload());
+                return None;
+            }
+        }
+
+        for responder in sockets
+            .items_mut()
+            .filter_map(|i| DnsResponderSocket::downcast_mut(&mut i.socket))
+        {
+            if responder.accepts(&ip_repr, &udp_repr) {
+                responder.process(self, &ip_repr, &udp_repr, udp_packet.payload());
+                return None;
+            }
+        }
+
+        // The packet wasn't handled by a socket, send an ICMP port unreachable packet.
+        match ip_repr {
+            IpRepr::Ipv4(_) if handled_by_raw_socket => None,
+            IpRepr::Ipv6(_) if handled_by_raw_socket => None,
+            IpRepr::Ipv4(ipv4_repr) => {
+                let payload_len =
+                    icmp_reply_payload_len(ip_payload.len(), IPV4_MIN_MTU, ipv4_repr.buffer_len());
+                let icmpv4_reply_repr = Icmpv4Repr::DstUnreachable {
+                    reason: Icmpv4DstUnreachable::PortUnreachable,
+                    header: ipv4_repr,
+                    data: &ip_payload[0..payload_len],
+                };
+                self.icmpv4_reply(ipv4_repr, icmpv4_reply_repr)
+            }
+            IpRepr::Ipv6(ipv6_repr) => {
+                let payload_len =
+                    icmp_reply_payload_len(ip_payload.len(), IPV6_MIN_MTU, ipv6_repr.buffer_len());
+                let icmpv6_reply_repr = Icmpv6Repr::DstUnreachable {
+                    reason: Icmpv6DstUnreachable::PortUnreachable,
+                    header: ipv6_repr,
+                    data: &ip_payload[0..payload_len],
+                };
+                self.icmpv6_reply(ipv6_repr, icmpv6_reply_repr)
+            }
+        }
+    }
+
+    /// Report an ICMP error to the UDP socket that sent the quoted datagram
+    /// (RFC 1122 § 4.1.3.3).
+    pub(super) fn process_udp_error(
+        &mut self,
+        sockets: &mut SocketSet,
+        reported_by: IpAddress,
+        header: IpRepr,
+        data: &[u8],
+        kind: IcmpErrorKind,
+    ) {
+        if header.next_header() != IpProtocol::Udp || !self.has_ip_addr(header.src_addr()) {
+            return;
+        }
+        // The quoted datagram is usually truncated, so only the ports can be
+        // recovered, and the checksum can't be verified.
+        if data.len() < UDP_HEADER_LEN {
+            return;
+        }
+        let udp_packet = UdpPacket::new_unchecked(data);
+        let udp_repr = UdpRepr {
+            src_port: udp_packet.src_port(),
+            dst_port: udp_packet.dst_port(),
+        };
+
+        for udp_socket in sockets
+            .items_mut()
+            .filter_map(|i| UdpSocket::downcast_mut(&mut i.socket))
+        {
+            if udp_socket.accepts_error(&header, &udp_repr) {
+                udp_socket.process_error(reported_by, &header, &udp_repr, kind);
+                return;
+            }
+        }
+    }
+}
@@ -0,0 +1,65 @@
+use crate::config::{
+    IFACE_CONNTRACK_COUNT, IFACE_ICMP_RATE_LIMIT_DST_COUNT, IFACE_LOOPBACK_QUEUE_COUNT,
+    IFACE_MAX_ADDR_COUNT, IFACE_MAX_FIREWALL_RULE_COUNT, IFACE_MAX_MULTICAST_GROUP_COUNT,
+    IFACE_MAX_PORT_FORWARD_COUNT, IFACE_MAX_ROUTE_COUNT, IFACE_MAX_ROUTING_RULE_COUNT,
+    IFACE_NAT_MAPPING_COUNT, IFACE_NEIGHBOR_CACHE_COUNT, IFACE_PATH_MTU_CACHE_COUNT,
+};
+
+/// Capacities of the tables of an interface.
+///
+/// Tables configured by the user, like the addresses or the routes, return an error
+/// when full. Caches filled by the traffic, like the neighbor cache, evict their
+/// oldest entries instead.
+///
+/// The defaults are the `IFACE_*` constants of [`crate::config`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Limits {
+    /// Maximum number of IP addresses, including the ones configured by SLAAC.
+    pub max_ip_addrs: usize,
+    /// Maximum number of routes, over all the routing tables.
+    ///
+    /// Routes learned from the network count too: once the table is full, the
+    /// default routers advertised to SLAAC and the host routes of ICMP redirects
+    /// are ignored, and adding a route, like the default route of a DHCP lease,
+    /// fails with [`RouteTableFull`](super::RouteTableFull).
+    pub max_routes: usize,
+    pub max_routing_rules: usize,
+    /// Maximum number of neighbors, including the static ones.
+    pub neighbor_cache_size: usize,
+    pub path_mtu_cache_size: usize,
+    pub max_multicast_groups: usize,
+    /// Maximum number of packets addressed to ourselves waiting for the next poll.
+    pub loopback_queue_len: usize,
+    /// Maximum number of destinations whose ICMP error rate is limited separately.
+    pub icmp_rate_limit_dst_count: usize,
+    /// Maximum number of firewall rules, per chain.
+    pub max_firewall_rules: usize,
+    /// Maximum number of connections followed by the connection tracker.
+    pub conntrack_size: usize,
+    /// Maximum number of dynamic mappings of a [`Nat`](super::Nat) created with
+    /// [`Nat::with_limits`](super::Nat::with_limits).
+    pub max_nat_mappings: usize,
+    /// Maximum number of port forwards of a [`Nat`](super::Nat) created with
+    /// [`Nat::with_limits`](super::Nat::with_limits).
+    pub max_port_forwards: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            max_ip_addrs: IFACE_MAX_ADDR_COUNT,
+            max_routes: IFACE_MAX_ROUTE_COUNT,
+            max_routing_rules: IFACE_MAX_ROUTING_RULE_COUNT,
+            neighbor_cache_size: IFACE_NEIGHBOR_CACHE_COUNT,
+            path_mtu_cache_size: IFACE_PATH_MTU_CACHE_COUNT,
+            max_multicast_groups: IFACE_MAX_MULTICAST_GROUP_COUNT,
+            loopback_queue_len: IFACE_LOOPBACK_QUEUE_COUNT,
+            icmp_rate_limit_dst_count: IFACE_ICMP_RATE_LIMIT_DST_COUNT,
+            max_firewall_rules: IFACE_MAX_FIREWALL_RULE_COUNT,
+            conntrack_size: IFACE_CONNTRACK_COUNT,
+            max_nat_mappings: IFACE_NAT_MAPPING_COUNT,
+            max_port_forwards: IFACE_MAX_PORT_FORWARD_COUNT,
+        }
+    }
+}
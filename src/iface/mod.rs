@@ -4,18 +4,36 @@ The `iface` module deals with the *network interfaces*. It filters incoming fram
 provides lookup and caching of hardware addresses, and handles management packets.
 */
 
+mod conntrack;
+mod firewall;
 mod fragmentation;
 mod interface;
+mod limits;
+mod nat;
 mod neighbor;
+mod path_mtu;
+mod rate_limit;
 mod route;
 mod socket_meta;
 mod socket_set;
+mod static_config;
 
 mod packet;
 
 pub use self::interface::{
-    Config, Interface, InterfaceInner as Context, PollIngressSingleResult, PollResult,
+    AddrTableFull, Config, Interface, InterfaceInner as Context, MulticastError,
+    PollIngressSingleResult, PollResult,
 };
 
-pub use self::route::{Route, RouteTableFull, Routes};
-pub use self::socket_set::{SocketHandle, SocketSet, SocketStorage};
+pub use self::conntrack::{
+    Connection, Conntrack, Counters, Status as ConnStatus, TcpState, Timeouts as ConntrackTimeouts,
+    Tuple,
+};
+pub use self::firewall::{Action, Chain, Firewall, Rule, RuleTableFull};
+pub use self::limits::Limits;
+pub use self::nat::{Mapping as NatMapping, Nat, NatError, PortForward, Timeouts as NatTimeouts};
+pub use self::neighbor::NeighborCacheFull;
+pub use self::rate_limit::RateLimit;
+pub use self::route::{Route, RouteTableFull, Routes, RoutingRule};
+pub use self::socket_set::{HandleError, SocketHandle, SocketSet, SocketStorage};
+pub use self::static_config::{ApplyError, ParseError, ParseErrorKind, StaticConfig};
@@ -0,0 +1,782 @@
+// Heads up! Before working on this file you should read, at least,
+// RFC 3022, RFC 4787, RFC 5382 and RFC 5508.
+
+use std::collections::HashMap;
+
+use super::Limits;
+use crate::time::{Duration, Instant};
+use crate::wire::ip::checksum;
+use crate::wire::{
+    Icmpv4Message, Icmpv4Packet, IpProtocol, Ipv4Address, Ipv4Packet, TcpPacket, UdpPacket,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NatError {
+    /// The packet is malformed.
+    Malformed,
+    /// The packet can't be translated: it is a fragment, or neither a TCP or UDP
+    /// segment nor an ICMP echo message.
+    Unsupported,
+    /// No mapping or port forward matches the incoming packet.
+    NoMapping,
+    /// The mapping or port forward table is full.
+    TableFull,
+}
+
+impl core::fmt::Display for NatError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            NatError::Malformed => write!(f, "malformed packet"),
+            NatError::Unsupported => write!(f, "unsupported packet"),
+            NatError::NoMapping => write!(f, "no mapping"),
+            NatError::TableFull => write!(f, "table full"),
+        }
+    }
+}
+
+impl std::error::Error for NatError {}
+
+/// Lifetimes of the mappings, by protocol.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timeouts {
+    /// Lifetime of a TCP mapping (RFC 5382 § 5, REQ-5).
+    pub tcp_established: Duration,
+    /// Lifetime of a TCP mapping once a FIN or RST was seen (RFC 5382 § 5).
+    pub tcp_transitory: Duration,
+    /// Lifetime of a UDP mapping (RFC 4787 § 4.3, REQ-5).
+    pub udp: Duration,
+    /// Lifetime of an ICMP echo mapping (RFC 5508 § 3.1, REQ-1).
+    pub icmp: Duration,
+}
+
+impl Default for Timeouts {
+    fn default() -> Self {
+        Timeouts {
+            tcp_established: Duration::from_secs(2 * 3600 + 4 * 60),
+            tcp_transitory: Duration::from_secs(4 * 60),
+            udp: Duration::from_secs(5 * 60),
+            icmp: Duration::from_secs(60),
+        }
+    }
+}
+
+/// A static mapping of an external port to an internal endpoint.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PortForward {
+    /// Either `IpProtocol::Tcp` or `IpProtocol::Udp`.
+    pub protocol: IpProtocol,
+    pub external_port: u16,
+    pub internal_addr: Ipv4Address,
+    pub internal_port: u16,
+}
+
+/// A mapping created by an outgoing packet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Mapping {
+    /// `IpProtocol::Tcp`, `IpProtocol::Udp`, or `IpProtocol::Icmp` for echo messages.
+    pub protocol: IpProtocol,
+    pub internal_addr: Ipv4Address,
+    /// The internal port, or echo identifier.
+    pub internal_port: u16,
+    /// The external port, or echo identifier.
+    pub external_port: u16,
+    pub expires_at: Instant,
+}
+
+/// A dynamic mapping, keyed by its protocol and external port.
+#[derive(Debug, Clone, Copy)]
+struct Entry {
+    internal_addr: Ipv4Address,
+    internal_port: u16,
+    expires_at: Instant,
+    /// Whether a FIN or RST was seen on the TCP connection.
+    closing: bool,
+}
+
+/// The identifiers of a packet that are translated.
+#[derive(Debug, Clone, Copy)]
+struct Flow {
+    protocol: IpProtocol,
+    src_addr: Ipv4Address,
+    dst_addr: Ipv4Address,
+    /// Port, or echo identifier.
+    src_port: u16,
+    /// Port, or echo identifier.
+    dst_port: u16,
+    syn: bool,
+    closing: bool,
+}
+
+/// Which end of a packet is translated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Side {
+    Src,
+    Dst,
+}
+
+/// A network address and port translator, masquerading an internal network
+/// behind a single external address.
+///
+/// A router forwarding packets between the internal network and the upstream
+/// interface passes every IPv4 packet it sends upstream to
+/// [translate_outbound](#method.translate_outbound), and every packet received
+/// from upstream to [translate_inbound](#method.translate_inbound). Packets are
+/// rewritten in place, with their checksums incrementally updated; packets that
+/// can't be translated should be dropped.
+///
+/// TCP and UDP ports and ICMP echo identifiers are translated. ICMP errors are not.
+#[derive(Debug)]
+pub struct Nat {
+    external_addr: Ipv4Address,
+    mappings: HashMap<(IpProtocol, u16), Entry>,
+    port_forwards: Vec<PortForward>,
+    timeouts: Timeouts,
+    next_port: u16,
+    max_mappings: usize,
+    max_port_forwards: usize,
+}
+
+impl Nat {
+    /// First external port allocated to the mappings.
+    pub const PORT_RANGE_START: u16 = 49152;
+
+    /// Create a translator for the given external address, with the default limits.
+    pub fn new(external_addr: Ipv4Address) -> Self {
+        Self::with_limits(external_addr, &Limits::default())
+    }
+
+    /// Create a translator for the given external address, holding up to
+    /// [`Limits::max_nat_mappings`] mappings and [`Limits::max_port_forwards`]
+    /// port forwards.
+    pub fn with_limits(external_addr: Ipv4Address, limits: &Limits) -> Self {
+        Nat {
+            external_addr,
+            mappings: HashMap::new(),
+            port_forwards: Vec::new(),
+            timeouts: Timeouts::default(),
+            next_port: Self::PORT_RANGE_START,
+            max_mappings: limits.max_nat_mappings,
+            max_port_forwards: limits.max_port_forwards,
+        }
+    }
+
+    /// Return the external address.
+    pub fn external_addr(&self) -> Ipv4Address {
+        self.external_addr
+    }
+
+    /// Set the external address, forgetting all the mappings.
+    pub fn set_external_addr(&mut self, addr: Ipv4Address) {
+        if addr != self.external_addr {
+            self.external_addr = addr;
+            self.flush();
+        }
+    }
+
+    /// Return the lifetimes of the mappings.
+    pub fn timeouts(&self) -> Timeouts {
+        self.timeouts
+    }
+
+    /// Set the lifetimes of the mappings. Existing mappings keep their expiry time
+    /// until they are used again.
+    pub fn set_timeouts(&mut self, timeouts: Timeouts) {
+        self.timeouts = timeouts;
+    }
+
+    /// Return the mappings that are still alive.
+    pub fn mappings(&self, timestamp: Instant) -> impl Iterator<Item = Mapping> + '_ {
+        self.mappings
+            .iter()
+            .filter(move |(_, entry)| timestamp < entry.expires_at)
+            .map(|(&(protocol, external_port), entry)| Mapping {
+                protocol,
+                internal_addr: entry.internal_addr,
+                internal_port: entry.internal_port,
+                external_port,
+                expires_at: entry.expires_at,
+            })
+    }
+
+    /// Forget all the mappings.
+    pub fn flush(&mut self) {
+        self.mappings.clear()
+    }
+
+    /// Return the port forwards.
+    pub fn port_forwards(&self) -> &[PortForward] {
+        &self.port_forwards
+    }
+
+    /// Add a port forward, replacing any forward of the same external port.
+    ///
+    /// # Panics
+    /// This function panics if the protocol is neither TCP nor UDP.
+    pub fn add_port_forward(&mut self, forward: PortForward) -> Result<(), NatError> {
+        assert!(
+            matches!(forward.protocol, IpProtocol::Tcp | IpProtocol::Udp),
+            "port forwards are only supported for TCP and UDP"
+        );
+
+        self.remove_port_forward(forward.protocol, forward.external_port);
+        if self.port_forwards.len() >= self.max_port_forwards {
+            return Err(NatError::TableFull);
+        }
+        // The port can't be used by a mapping anymore.
+        self.mappings
+            .remove(&(forward.protocol, forward.external_port));
+        self.port_forwards.push(forward);
+        Ok(())
+    }
+
+    /// Remove the port forward of an external port, if any.
+    pub fn remove_port_forward(
+        &mut self,
+        protocol: IpProtocol,
+        external_port: u16,
+    ) -> Option<PortForward> {
+        let index = self
+            .port_forwards
+            .iter()
+            .position(|f| f.protocol == protocol && f.external_port == external_port)?;
+        Some(self.port_forwards.remove(index))
+    }
+
+    /// Translate the source of a packet sent to the upstream network, creating a
+    /// mapping if needed.
+    pub fn translate_outbound(
+        &mut self,
+        packet: &mut [u8],
+        timestamp: Instant,
+    ) -> Result<(), NatError> {
+        let flow = Self::flow(packet, Icmpv4Message::EchoRequest)?;
+
+        let external_port = match self.port_forwards.iter().find(|f| {
+            f.protocol == flow.protocol
+                && f.internal_addr == flow.src_addr
+                && f.internal_port == flow.src_port
+        }) {
+            Some(forward) => forward.external_port,
+            None => {
+                let external_port = match self.mappings.iter().find(|(&(protocol, _), entry)| {
+                    protocol == flow.protocol
+                        && entry.internal_addr == flow.src_addr
+                        && entry.internal_port == flow.src_port
+                        && timestamp < entry.expires_at
+                }) {
+                    Some((&(_, external_port), _)) => external_port,
+                    None => self.allocate(&flow, timestamp)?,
+                };
+                self.refresh(&flow, external_port, timestamp);
+                external_port
+            }
+        };
+
+        Self::rewrite(packet, Side::Src, self.external_addr, external_port);
+        Ok(())
+    }
+
+    /// Translate the destination of a packet received from the upstream network.
+    pub fn translate_inbound(
+        &mut self,
+        packet: &mut [u8],
+        timestamp: Instant,
+    ) -> Result<(), NatError> {
+        let flow = Self::flow(packet, Icmpv4Message::EchoReply)?;
+        if flow.dst_addr != self.external_addr {
+            return Err(NatError::NoMapping);
+        }
+
+        let (internal_addr, internal_port) = match self
+            .port_forwards
+            .iter()
+            .find(|f| f.protocol == flow.protocol && f.external_port == flow.dst_port)
+        {
+            Some(forward) => (forward.internal_addr, forward.internal_port),
+            None => match self.mappings.get(&(flow.protocol, flow.dst_port)) {
+                Some(entry) if timestamp < entry.expires_at => {
+                    let internal = (entry.internal_addr, entry.internal_port);
+                    self.refresh(&flow, flow.dst_port, timestamp);
+                    internal
+                }
+                _ => {
+                    net_trace!(
+                        "nat: no mapping for {} port {}",
+                        flow.protocol,
+                        flow.dst_port
+                    );
+                    return Err(NatError::NoMapping);
+                }
+            },
+        };
+
+        Self::rewrite(packet, Side::Dst, internal_addr, internal_port);
+        Ok(())
+    }
+
+    /// Create a mapping for an outgoing flow, returning its external port.
+    fn allocate(&mut self, flow: &Flow, timestamp: Instant) -> Result<u16, NatError> {
+        if self.mappings.len() >= self.max_mappings {
+            self.mappings
+                .retain(|_, entry| timestamp < entry.expires_at);
+        }
+        if self.mappings.len() >= self.max_mappings {
+            net_debug!("nat: mapping table full");
+            return Err(NatError::TableFull);
+        }
+
+        let range_len = (u16::MAX - Self::PORT_RANGE_START) as usize + 1;
+        for _ in 0..range_len {
+            let port = self.next_port;
+            self.next_port = port.checked_add(1).unwrap_or(Self::PORT_RANGE_START);
+
+            let in_use = |nat: &Nat| {
+                nat.port_forwards
+                    .iter()
+                    .any(|f| f.protocol == flow.protocol && f.external_port == port)
+                    || nat
+                        .mappings
+                        .get(&(flow.protocol, port))
+                        .is_some_and(|entry| timestamp < entry.expires_at)
+            };
+            if !in_use(self) {
+                net_trace!(
+                    "nat: mapped {} {}:{} to port {}",
+                    flow.protocol,
+                    flow.src_addr,
+                    flow.src_port,
+                    port
+                );
+                self.mappings.insert(
+                    (flow.protocol, port),
+                    Entry {
+                        internal_addr: flow.src_addr,
+                        internal_port: flow.src_port,
+                        expires_at: timestamp,
+                        closing: false,
+                    },
+                );
+                return Ok(port);
+            }
+        }
+
+        net_debug!("nat: no {} port left", flow.protocol);
+        Err(NatError::TableFull)
+    }
+
+    /// Extend the lifetime of a mapping after a packet of its flow went through.
+    fn refresh(&mut self, flow: &Flow, external_port: u16, timestamp: Instant) {
+        let timeouts = self.timeouts;
+        let Some(entry) = self.mappings.get_mut(&(flow.protocol, external_port)) else {
+            return;
+        };
+
+        // A new connection may reuse the mapping of a closed one.
+        if flow.syn {
+            entry.closing = false;
+        }
+        entry.closing |= flow.closing;
+
+        let timeout = match flow.protocol {
+            IpProtocol::Tcp if entry.closing => timeouts.tcp_transitory,
+            IpProtocol::Tcp => timeouts.tcp_established,
+            IpProtocol::Udp => timeouts.udp,
+            _ => timeouts.icmp,
+        };
+        entry.expires_at = timestamp + timeout;
+    }
+
+    /// Extract the identifiers of a packet, accepting only the given kind of ICMP
+    /// echo message.
+    fn flow(packet: &[u8], echo: Icmpv4Message) -> Result<Flow, NatError> {
+        let ipv4_packet = Ipv4Packet::new_checked(packet).map_err(|_| NatError::Malformed)?;
+        if !ipv4_packet.verify_checksum() {
+            return Err(NatError::Malformed);
+        }
+        if ipv4_packet.more_frags() || ipv4_packet.frag_offset() != 0 {
+            return Err(NatError::Unsupported);
+        }
+
+        let mut flow = Flow {
+            protocol: ipv4_packet.next_header(),
+            src_addr: ipv4_packet.src_addr(),
+            dst_addr: ipv4_packet.dst_addr(),
+            src_port: 0,
+            dst_port: 0,
+            syn: false,
+            closing: false,
+        };
+        let payload = ipv4_packet.payload();
+        match flow.protocol {
+            IpProtocol::Tcp => {
+                let tcp_packet =
+                    TcpPacket::new_checked(payload).map_err(|_| NatError::Malformed)?;
+                flow.src_port = tcp_packet.src_port();
+                flow.dst_port = tcp_packet.dst_port();
+                flow.syn = tcp_packet.syn() && !tcp_packet.ack();
+                flow.closing = tcp_packet.fin() || tcp_packet.rst();
+            }
+            IpProtocol::Udp => {
+                let udp_packet =
+                    UdpPacket::new_checked(payload).map_err(|_| NatError::Malformed)?;
+                flow.src_port = udp_packet.src_port();
+                flow.dst_port = udp_packet.dst_port();
+            }
+            IpProtocol::Icmp => {
+                let icmp_packet =
+                    Icmpv4Packet::new_checked(payload).map_err(|_| NatError::Malformed)?;
+                if icmp_packet.msg_type() != echo {
+                    return Err(NatError::Unsupported);
+                }
+                flow.src_port = icmp_packet.echo_ident();
+                flow.dst_port = icmp_packet.echo_ident();
+            }
+            _ => return Err(NatError::Unsupported),
+        }
+        Ok(flow)
+    }
+
+    /// Rewrite one end of a packet that was checked by [flow](#method.flow).
+    fn rewrite(packet: &mut [u8], side: Side, addr: Ipv4Address, port: u16) {
+        let mut ipv4_packet = Ipv4Packet::new_unchecked(packet);
+        let old_addr = match side {
+            Side::Src => ipv4_packet.src_addr(),
+            Side::Dst => ipv4_packet.dst_addr(),
+        };
+        match side {
+            Side::Src => ipv4_packet.set_src_addr(addr),
+            Side::Dst => ipv4_packet.set_dst_addr(addr),
+        }
+        ipv4_packet.fill_checksum();
+
+        let protocol = ipv4_packet.next_header();
+        let payload = ipv4_packet.payload_mut();
+        match protocol {
+            IpProtocol::Tcp => {
+                let mut tcp_packet = TcpPacket::new_unchecked(payload);
+                let old_port = match side {
+                    Side::Src => tcp_packet.src_port(),
+                    Side::Dst => tcp_packet.dst_port(),
+                };
+                match side {
+                    Side::Src => tcp_packet.set_src_port(port),
+                    Side::Dst => tcp_packet.set_dst_port(port),
+                }
+                let checksum = update_checksum(
+                    tcp_packet.checksum(),
+                    &endpoint_octets(old_addr, old_port),
+                    &endpoint_octets(addr, port),
+                );
+                tcp_packet.set_checksum(checksum);
+            }
+            IpProtocol::Udp => {
+                let mut udp_packet = UdpPacket::new_unchecked(payload);
+                let old_port = match side {
+                    Side::Src => udp_packet.src_port(),
+                    Side::Dst => udp_packet.dst_port(),
+                };
+                match side {
+                    Side::Src => udp_packet.set_src_port(port),
+                    Side::Dst => udp_packet.set_dst_port(port),
+                }
+                // A zero checksum means that the checksum was not computed (RFC 768).
+                if udp_packet.checksum() != 0 {
+                    let checksum = update_checksum(
+                        udp_packet.checksum(),
+                        &endpoint_octets(old_addr, old_port),
+                        &endpoint_octets(addr, port),
+                    );
+                    udp_packet.set_checksum(if checksum == 0 { 0xffff } else { checksum });
+                }
+            }
+            IpProtocol::Icmp => {
+                // The ICMP checksum doesn't cover a pseudo header.
+                let mut icmp_packet = Icmpv4Packet::new_unchecked(payload);
+                let old_ident = icmp_packet.echo_ident();
+                icmp_packet.set_echo_ident(port);
+                let checksum = update_checksum(
+                    icmp_packet.checksum(),
+                    &old_ident.to_be_bytes(),
+                    &port.to_be_bytes(),
+                );
+                icmp_packet.set_checksum(checksum);
+            }
+            _ => unreachable!(),
+        }
+    }
+}
+
+/// Return the octets of an address followed by a port, as they appear in a
+/// checksummed pseudo header and transport header.
+fn endpoint_octets(addr: Ipv4Address, port: u16) -> [u8; 6] {
+    let mut octets = [0; 6];
+    octets[..4].copy_from_slice(&addr.octets());
+    octets[4..].copy_from_slice(&port.to_be_bytes());
+    octets
+}
+
+/// Update a checksum after the `old` octets of the data it covers were replaced
+/// by the `new` ones (RFC 1624 § 3).
+fn update_checksum(checksum: u16, old: &[u8], new: &[u8]) -> u16 {
+    !checksum::combine(&[!checksum, !checksum::data(old), checksum::data(new)])
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::phy::ChecksumCapabilities;
+    use crate::wire::{
+        Icmpv4Repr, IpAddress, Ipv4Repr, TcpControl, TcpRepr, TcpSeqNumber, UdpRepr,
+    };
+
+    const INTERNAL_ADDR: Ipv4Address = Ipv4Address::new(192, 168, 1, 10);
+    const EXTERNAL_ADDR: Ipv4Address = Ipv4Address::new(10, 0, 0, 1);
+    const REMOTE_ADDR: Ipv4Address = Ipv4Address::new(10, 0, 0, 2);
+
+    /// Build an IPv4 packet around a payload emitted by `emit`.
+    fn packet(
+        src_addr: Ipv4Address,
+        dst_addr: Ipv4Address,
+        next_header: IpProtocol,
+        payload_len: usize,
+        emit: impl FnOnce(&mut [u8]),
+    ) -> Vec<u8> {
+        let ip_repr = Ipv4Repr {
+            src_addr,
+            dst_addr,
+            next_header,
+            payload_len,
+            hop_limit: 64,
+        };
+        let mut buffer = vec![0; ip_repr.buffer_len() + payload_len];
+        ip_repr.emit(
+            &mut Ipv4Packet::new_unchecked(&mut buffer[..]),
+            &ChecksumCapabilities::default(),
+        );
+        emit(&mut buffer[ip_repr.buffer_len()..]);
+        buffer
+    }
+
+    fn udp_packet(src: (Ipv4Address, u16), dst: (Ipv4Address, u16)) -> Vec<u8> {
+        let repr = UdpRepr {
+            src_port: src.1,
+            dst_port: dst.1,
+        };
+        let payload = b"abcdefg";
+        packet(
+            src.0,
+            dst.0,
+            IpProtocol::Udp,
+            repr.header_len() + payload.len(),
+            |buffer| {
+                repr.emit(
+                    &mut UdpPacket::new_unchecked(buffer),
+                    &src.0.into(),
+                    &dst.0.into(),
+                    payload.len(),
+                    |buffer| buffer.copy_from_slice(payload),
+                    &ChecksumCapabilities::default(),
+                )
+            },
+        )
+    }
+
+    fn tcp_packet(src: (Ipv4Address, u16), dst: (Ipv4Address, u16)) -> Vec<u8> {
+        let repr = TcpRepr {
+            src_port: src.1,
+            dst_port: dst.1,
+            control: TcpControl::Syn,
+            seq_number: TcpSeqNumber(0x1234_5678),
+            ack_number: None,
+            window_len: 1024,
+            window_scale: None,
+            max_seg_size: None,
+            sack_permitted: false,
+            sack_ranges: [None; 3],
+            timestamp: None,
+            payload: b"abcdefg",
+        };
+        packet(src.0, dst.0, IpProtocol::Tcp, repr.buffer_len(), |buffer| {
+            repr.emit(
+                &mut TcpPacket::new_unchecked(buffer),
+                &src.0.into(),
+                &dst.0.into(),
+                &ChecksumCapabilities::default(),
+            )
+        })
+    }
+
+    fn icmp_packet(src_addr: Ipv4Address, dst_addr: Ipv4Address, repr: Icmpv4Repr) -> Vec<u8> {
+        packet(
+            src_addr,
+            dst_addr,
+            IpProtocol::Icmp,
+            repr.buffer_len(),
+            |buffer| {
+                repr.emit(
+                    &mut Icmpv4Packet::new_unchecked(buffer),
+                    &ChecksumCapabilities::default(),
+                )
+            },
+        )
+    }
+
+    /// Check the checksums of a packet, returning its addresses and ports.
+    fn verify(packet: &[u8]) -> ((Ipv4Address, u16), (Ipv4Address, u16)) {
+        let ipv4_packet = Ipv4Packet::new_checked(packet).unwrap();
+        assert!(ipv4_packet.verify_checksum());
+        let src_addr = ipv4_packet.src_addr();
+        let dst_addr = ipv4_packet.dst_addr();
+        let (src_ip, dst_ip) = (IpAddress::from(src_addr), IpAddress::from(dst_addr));
+        let payload = ipv4_packet.payload();
+        let (src_port, dst_port) = match ipv4_packet.next_header() {
+            IpProtocol::Tcp => {
+                let tcp_packet = TcpPacket::new_checked(payload).unwrap();
+                assert!(tcp_packet.verify_checksum(&src_ip, &dst_ip));
+                (tcp_packet.src_port(), tcp_packet.dst_port())
+            }
+            IpProtocol::Udp => {
+                let udp_packet = UdpPacket::new_checked(payload).unwrap();
+                assert!(udp_packet.verify_checksum(&src_ip, &dst_ip));
+                (udp_packet.src_port(), udp_packet.dst_port())
+            }
+            IpProtocol::Icmp => {
+                let icmp_packet = Icmpv4Packet::new_checked(payload).unwrap();
+                assert!(icmp_packet.verify_checksum());
+                (icmp_packet.echo_ident(), icmp_packet.echo_ident())
+            }
+            _ => unreachable!(),
+        };
+        ((src_addr, src_port), (dst_addr, dst_port))
+    }
+
+    #[test]
+    fn test_udp_round_trip() {
+        let mut nat = Nat::new(EXTERNAL_ADDR);
+        let now = Instant::ZERO;
+
+        let mut outbound = udp_packet((INTERNAL_ADDR, 5000), (REMOTE_ADDR, 53));
+        nat.translate_outbound(&mut outbound, now).unwrap();
+        let (src, dst) = verify(&outbound);
+        assert_eq!(src, (EXTERNAL_ADDR, Nat::PORT_RANGE_START));
+        assert_eq!(dst, (REMOTE_ADDR, 53));
+
+        let mut inbound = udp_packet((REMOTE_ADDR, 53), (EXTERNAL_ADDR, Nat::PORT_RANGE_START));
+        nat.translate_inbound(&mut inbound, now).unwrap();
+        let (src, dst) = verify(&inbound);
+        assert_eq!(src, (REMOTE_ADDR, 53));
+        assert_eq!(dst, (INTERNAL_ADDR, 5000));
+    }
+
+    #[test]
+    fn test_tcp_checksum() {
+        let mut nat = Nat::new(EXTERNAL_ADDR);
+
+        let mut outbound = tcp_packet((INTERNAL_ADDR, 40000), (REMOTE_ADDR, 80));
+        nat.translate_outbound(&mut outbound, Instant::ZERO)
+            .unwrap();
+        let (src, _) = verify(&outbound);
+        assert_eq!(src, (EXTERNAL_ADDR, Nat::PORT_RANGE_START));
+    }
+
+    #[test]
+    fn test_icmp_echo_checksum() {
+        let mut nat = Nat::new(EXTERNAL_ADDR);
+        let now = Instant::ZERO;
+
+        let mut request = icmp_packet(
+            INTERNAL_ADDR,
+            REMOTE_ADDR,
+            Icmpv4Repr::EchoRequest {
+                ident: 0x1234,
+                seq_no: 1,
+                data: b"abcdefg",
+            },
+        );
+        nat.translate_outbound(&mut request, now).unwrap();
+        let (src, _) = verify(&request);
+        assert_eq!(src, (EXTERNAL_ADDR, Nat::PORT_RANGE_START));
+
+        let mut reply = icmp_packet(
+            REMOTE_ADDR,
+            EXTERNAL_ADDR,
+            Icmpv4Repr::EchoReply {
+                ident: Nat::PORT_RANGE_START,
+                seq_no: 1,
+                data: b"abcdefg",
+            },
+        );
+        nat.translate_inbound(&mut reply, now).unwrap();
+        let (_, dst) = verify(&reply);
+        assert_eq!(dst, (INTERNAL_ADDR, 0x1234));
+    }
+
+    #[test]
+    fn test_inbound_without_mapping() {
+        let mut nat = Nat::new(EXTERNAL_ADDR);
+
+        let mut inbound = udp_packet((REMOTE_ADDR, 53), (EXTERNAL_ADDR, 1234));
+        assert_eq!(
+            nat.translate_inbound(&mut inbound, Instant::ZERO),
+            Err(NatError::NoMapping)
+        );
+    }
+
+    #[test]
+    fn test_allocation_skips_port_forwards() {
+        let mut nat = Nat::new(EXTERNAL_ADDR);
+        nat.add_port_forward(PortForward {
+            protocol: IpProtocol::Udp,
+            external_port: Nat::PORT_RANGE_START,
+            internal_addr: INTERNAL_ADDR,
+            internal_port: 7,
+        })
+        .unwrap();
+
+        let mut outbound = udp_packet((INTERNAL_ADDR, 5000), (REMOTE_ADDR, 53));
+        nat.translate_outbound(&mut outbound, Instant::ZERO)
+            .unwrap();
+        let (src, _) = verify(&outbound);
+        assert_eq!(src, (EXTERNAL_ADDR, Nat::PORT_RANGE_START + 1));
+    }
+
+    #[test]
+    fn test_table_exhausted() {
+        let limits = Limits {
+            max_nat_mappings: 4,
+            ..Limits::default()
+        };
+        let mut nat = Nat::with_limits(EXTERNAL_ADDR, &limits);
+        let now = Instant::ZERO;
+
+        for port in 0..4 {
+            let mut outbound = udp_packet((INTERNAL_ADDR, 1000 + port), (REMOTE_ADDR, 53));
+            nat.translate_outbound(&mut outbound, now).unwrap();
+            let (src, _) = verify(&outbound);
+            assert_eq!(src, (EXTERNAL_ADDR, Nat::PORT_RANGE_START + port));
+        }
+
+        // The table is full, and the packet is left untouched.
+        let mut outbound = udp_packet((INTERNAL_ADDR, 999), (REMOTE_ADDR, 53));
+        let original = outbound.clone();
+        assert_eq!(
+            nat.translate_outbound(&mut outbound, now),
+            Err(NatError::TableFull)
+        );
+        assert_eq!(outbound, original);
+
+        // Existing flows keep their mapping.
+        let mut outbound = udp_packet((INTERNAL_ADDR, 1000), (REMOTE_ADDR, 53));
+        nat.translate_outbound(&mut outbound, now).unwrap();
+        let (src, _) = verify(&outbound);
+        assert_eq!(src, (EXTERNAL_ADDR, Nat::PORT_RANGE_START));
+
+        // Expired mappings make room for new ones.
+        let later = now + nat.timeouts().udp;
+        let mut outbound = udp_packet((INTERNAL_ADDR, 999), (REMOTE_ADDR, 53));
+        nat.translate_outbound(&mut outbound, later).unwrap();
+        let (src, _) = verify(&outbound);
+        assert_eq!(src, (EXTERNAL_ADDR, Nat::PORT_RANGE_START + 4));
+        assert_eq!(nat.mappings(later).count(), 1);
+    }
+}
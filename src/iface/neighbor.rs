@@ -38,10 +38,27 @@ impl Answer {
     }
 }
 
+/// Error returned when a static neighbor doesn't fit in the neighbor cache.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NeighborCacheFull;
+
+impl core::fmt::Display for NeighborCacheFull {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "Neighbor cache full")
+    }
+}
+
+impl std::error::Error for NeighborCacheFull {}
+
 /// A neighbor cache backed by a map.
+///
+/// Static neighbors are never expired, flushed, or replaced by discovered ones.
+/// When the cache is full, discovered neighbors evict the one closest to expiry.
 #[derive(Debug)]
 pub struct Cache {
     storage: HashMap<IpAddress, Neighbor>,
+    static_storage: HashMap<IpAddress, HardwareAddress>,
+    capacity: usize,
     silent_until: Instant,
 }
 
@@ -52,14 +69,20 @@ impl Cache {
     /// Neighbor entry lifetime, in milliseconds.
     pub(crate) const ENTRY_LIFETIME: Duration = Duration::from_millis(60_000);
 
-    /// Create a cache.
-    pub fn new() -> Self {
+    /// Create a cache holding up to `capacity` neighbors, static ones included.
+    pub fn new(capacity: usize) -> Self {
         Self {
             storage: HashMap::new(),
+            static_storage: HashMap::new(),
+            capacity,
             silent_until: Instant::from_millis(0),
         }
     }
 
+    fn is_full(&self) -> bool {
+        self.storage.len() + self.static_storage.len() >= self.capacity
+    }
+
     pub fn reset_expiry_if_existing(
         &mut self,
         protocol_addr: IpAddress,
@@ -99,55 +122,79 @@ impl Cache {
         debug_assert!(protocol_addr.is_unicast());
         debug_assert!(hardware_addr.is_unicast());
 
+        if self.static_storage.contains_key(&protocol_addr) {
+            return;
+        }
+
         let neighbor = Neighbor {
             expires_at,
             hardware_addr,
         };
+        if !self.storage.contains_key(&protocol_addr) && self.is_full() {
+            let Some((&old_protocol_addr, _)) = self
+                .storage
+                .iter()
+                .min_by_key(|(_, neighbor)| neighbor.expires_at)
+            else {
+                net_trace!(
+                    "not filled {} => {} (cache full)",
+                    protocol_addr,
+                    hardware_addr
+                );
+                return;
+            };
+            self.storage.remove(&old_protocol_addr);
+            net_trace!(
+                "filled {} => {} (evicted {})",
+                protocol_addr,
+                hardware_addr,
+                old_protocol_addr
+            );
+        }
         self.storage.insert(protocol_addr, neighbor);
-        // match self.storage.insert(protocol_addr, neighbor) {
-        //     Ok(Some(old_neighbor)) => {
-        //         if old_neighbor.hardware_addr != hardware_addr {
-        //             net_trace!(
-        //                 "replaced {} => {} (was {})",
-        //                 protocol_addr,
-        //                 hardware_addr,
-        //                 old_neighbor.hardware_addr
-        //             );
-        //         }
-        //     }
-        //     Ok(None) => {
-        //         net_trace!("filled {} => {} (was empty)", protocol_addr, hardware_addr);
-        //     }
-        //     Err((protocol_addr, neighbor)) => {
-        //         // If we're going down this branch, it means the cache is full, and we need to evict an entry.
-        //         let old_protocol_addr = *self
-        //             .storage
-        //             .iter()
-        //             .min_by_key(|(_, neighbor)| neighbor.expires_at)
-        //             .expect("empty neighbor cache storage")
-        //             .0;
-
-        //         let _old_neighbor = self.storage.remove(&old_protocol_addr).unwrap();
-        //         match self.storage.insert(protocol_addr, neighbor) {
-        //             Ok(None) => {
-        //                 net_trace!(
-        //                     "filled {} => {} (evicted {} => {})",
-        //                     protocol_addr,
-        //                     hardware_addr,
-        //                     old_protocol_addr,
-        //                     _old_neighbor.hardware_addr
-        //                 );
-        //             }
-        //             // We've covered everything else above.
-        //             _ => unreachable!(),
-        //         }
-        //     }
-        // }
+    }
+
+    /// Add a static neighbor, returning the hardware address it had, if any.
+    ///
+    /// Discovered neighbors are evicted to make room, but static ones never are.
+    pub(crate) fn fill_static(
+        &mut self,
+        protocol_addr: IpAddress,
+        hardware_addr: HardwareAddress,
+    ) -> Result<Option<HardwareAddress>, NeighborCacheFull> {
+        debug_assert!(protocol_addr.is_unicast());
+        debug_assert!(hardware_addr.is_unicast());
+
+        self.storage.remove(&protocol_addr);
+        if !self.static_storage.contains_key(&protocol_addr) {
+            if self.static_storage.len() >= self.capacity {
+                return Err(NeighborCacheFull);
+            }
+            if self.is_full() {
+                let old_protocol_addr = *self
+                    .storage
+                    .iter()
+                    .min_by_key(|(_, neighbor)| neighbor.expires_at)
+                    .expect("empty neighbor cache storage")
+                    .0;
+                self.storage.remove(&old_protocol_addr);
+            }
+        }
+        Ok(self.static_storage.insert(protocol_addr, hardware_addr))
+    }
+
+    /// Remove a static neighbor, returning its hardware address.
+    pub(crate) fn remove_static(&mut self, protocol_addr: &IpAddress) -> Option<HardwareAddress> {
+        self.static_storage.remove(protocol_addr)
     }
 
     pub(crate) fn lookup(&self, protocol_addr: &IpAddress, timestamp: Instant) -> Answer {
         assert!(protocol_addr.is_unicast());
 
+        if let Some(&hardware_addr) = self.static_storage.get(protocol_addr) {
+            return Answer::Found(hardware_addr);
+        }
+
         if let Some(&Neighbor {
             expires_at,
             hardware_addr,
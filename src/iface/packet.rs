@@ -1,6 +1,10 @@
+use core::hash::{Hash, Hasher};
+
 use crate::phy::DeviceCapabilities;
 use crate::wire::*;
 
+use super::route::FlowHasher;
+
 #[allow(clippy::large_enum_variant)]
 #[derive(Debug, PartialEq)]
 pub(crate) enum EthernetPacket<'a> {
@@ -11,12 +15,14 @@ pub(crate) enum EthernetPacket<'a> {
 #[derive(Debug, PartialEq)]
 pub(crate) enum Packet<'p> {
     Ipv4(PacketV4<'p>),
+    Ipv6(PacketV6<'p>),
 }
 
 impl<'p> Packet<'p> {
     pub(crate) fn new(ip_repr: IpRepr, payload: IpPayload<'p>) -> Self {
         match ip_repr {
             IpRepr::Ipv4(header) => Self::new_ipv4(header, payload),
+            IpRepr::Ipv6(header) => Self::new_ipv6(header, payload),
         }
     }
 
@@ -27,18 +33,62 @@ impl<'p> Packet<'p> {
         })
     }
 
+    pub(crate) fn new_ipv6(ip_repr: Ipv6Repr, payload: IpPayload<'p>) -> Self {
+        Self::Ipv6(PacketV6 {
+            header: ip_repr,
+            payload,
+        })
+    }
+
     pub(crate) fn ip_repr(&self) -> IpRepr {
         match self {
             Packet::Ipv4(p) => IpRepr::Ipv4(p.header),
+            Packet::Ipv6(p) => IpRepr::Ipv6(p.header),
         }
     }
 
     pub(crate) fn payload(&self) -> &IpPayload<'p> {
         match self {
             Packet::Ipv4(p) => &p.payload,
+            Packet::Ipv6(p) => &p.payload,
         }
     }
 
+    /// Hash the addresses, protocol and ports of the packet, or the identifier
+    /// of an ICMP echo message, to pick its route among equal-cost ones.
+    pub(crate) fn flow_hash(&self) -> u32 {
+        let ip_repr = self.ip_repr();
+        let ports = match self.payload() {
+            IpPayload::Udp(udp_repr, _) | IpPayload::Dhcpv4(udp_repr, _) => {
+                (udp_repr.src_port, udp_repr.dst_port)
+            }
+            IpPayload::Icmpv4(
+                Icmpv4Repr::EchoRequest { ident, .. } | Icmpv4Repr::EchoReply { ident, .. },
+            ) => (*ident, *ident),
+            IpPayload::Raw(data) => match (ip_repr.next_header(), data) {
+                (IpProtocol::Tcp | IpProtocol::Udp, [a, b, c, d, ..]) => {
+                    (u16::from_be_bytes([*a, *b]), u16::from_be_bytes([*c, *d]))
+                }
+                (IpProtocol::Icmp, [0 | 8, _, _, _, a, b, ..]) => {
+                    let ident = u16::from_be_bytes([*a, *b]);
+                    (ident, ident)
+                }
+                _ => (0, 0),
+            },
+            _ => (0, 0),
+        };
+
+        let mut hasher = FlowHasher::new();
+        (
+            ip_repr.next_header(),
+            ip_repr.src_addr(),
+            ip_repr.dst_addr(),
+            ports,
+        )
+            .hash(&mut hasher);
+        hasher.finish() as u32
+    }
+
     pub(crate) fn emit_payload(
         &self,
         _ip_repr: &IpRepr,
@@ -49,15 +99,37 @@ impl<'p> Packet<'p> {
             IpPayload::Icmpv4(icmpv4_repr) => {
                 icmpv4_repr.emit(&mut Icmpv4Packet::new_unchecked(payload), &caps.checksum)
             }
+            IpPayload::Icmpv6(icmpv6_repr) => {
+                let ipv6_repr = match _ip_repr {
+                    IpRepr::Ipv4(_) => unreachable!(),
+                    IpRepr::Ipv6(repr) => repr,
+                };
+
+                icmpv6_repr.emit(
+                    &ipv6_repr.src_addr,
+                    &ipv6_repr.dst_addr,
+                    &mut Icmpv6Packet::new_unchecked(payload),
+                    &caps.checksum,
+                )
+            }
+            IpPayload::Igmp(igmp_repr) => igmp_repr.emit(&mut IgmpPacket::new_unchecked(payload)),
             IpPayload::Raw(raw_packet) => payload.copy_from_slice(raw_packet),
-            // IpPayload::Udp(udp_repr, inner_payload) => udp_repr.emit(
-            //     &mut UdpPacket::new_unchecked(payload),
-            //     &_ip_repr.src_addr(),
-            //     &_ip_repr.dst_addr(),
-            //     inner_payload.len(),
-            //     |buf| buf.copy_from_slice(inner_payload),
-            //     &caps.checksum,
-            // ),
+            IpPayload::Dhcpv4(udp_repr, dhcp_repr) => udp_repr.emit(
+                &mut UdpPacket::new_unchecked(payload),
+                &_ip_repr.src_addr(),
+                &_ip_repr.dst_addr(),
+                dhcp_repr.buffer_len(),
+                |buf| dhcp_repr.emit(&mut DhcpPacket::new_unchecked(buf)).unwrap(),
+                &caps.checksum,
+            ),
+            IpPayload::Udp(udp_repr, inner_payload) => udp_repr.emit(
+                &mut UdpPacket::new_unchecked(payload),
+                &_ip_repr.src_addr(),
+                &_ip_repr.dst_addr(),
+                inner_payload.len(),
+                |buf| buf.copy_from_slice(inner_payload),
+                &caps.checksum,
+            ),
             // IpPayload::Tcp(mut tcp_repr) => {
             //     // This is a terrible hack to make TCP performance more acceptable on systems
             //     // where the TCP buffers are significantly larger than network buffers,
@@ -94,11 +166,20 @@ pub(crate) struct PacketV4<'p> {
     payload: IpPayload<'p>,
 }
 
+#[derive(Debug, PartialEq)]
+pub(crate) struct PacketV6<'p> {
+    header: Ipv6Repr,
+    payload: IpPayload<'p>,
+}
+
 #[derive(Debug, PartialEq)]
 pub(crate) enum IpPayload<'p> {
     Icmpv4(Icmpv4Repr<'p>),
+    Icmpv6(Icmpv6Repr<'p>),
+    Igmp(IgmpRepr),
     Raw(&'p [u8]),
-    // Udp(UdpRepr, &'p [u8]),
+    Dhcpv4(UdpRepr, DhcpRepr<'p>),
+    Udp(UdpRepr, &'p [u8]),
     // Tcp(TcpRepr<'p>),
 }
 
@@ -0,0 +1,159 @@
+// Heads up! Before working on this file you should read, at least,
+// RFC 1191 and RFC 8201.
+
+use std::collections::HashMap;
+
+use crate::time::{Duration, Instant};
+use crate::wire::IpAddress;
+
+/// A cached path MTU.
+#[derive(Debug, Clone, Copy)]
+struct Entry {
+    mtu: usize,
+    expires_at: Instant,
+}
+
+/// A path MTU cache backed by a map.
+///
+/// The cache only ever lowers the path MTU of a destination below the MTU of
+/// the link. Entries are aged out so that a larger path MTU is tried again
+/// once the route may have changed.
+#[derive(Debug)]
+pub struct Cache {
+    storage: HashMap<IpAddress, Entry>,
+    capacity: usize,
+}
+
+impl Cache {
+    /// Path MTU entry lifetime (RFC 1191 § 6.3).
+    pub(crate) const ENTRY_LIFETIME: Duration = Duration::from_secs(600);
+
+    /// Create a cache holding up to `capacity` destinations. A cache without
+    /// capacity never records anything.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            storage: HashMap::new(),
+            capacity,
+        }
+    }
+
+    /// Record the path MTU to `dst_addr`.
+    ///
+    /// Returns `false` if a lower path MTU is already known.
+    pub(crate) fn fill(&mut self, dst_addr: IpAddress, mtu: usize, timestamp: Instant) -> bool {
+        if self.capacity == 0 {
+            return false;
+        }
+        if let Some(entry) = self.storage.get(&dst_addr) {
+            if timestamp < entry.expires_at && entry.mtu <= mtu {
+                return false;
+            }
+        }
+
+        if !self.storage.contains_key(&dst_addr) && self.storage.len() >= self.capacity {
+            self.storage.retain(|_, entry| timestamp < entry.expires_at);
+        }
+        if !self.storage.contains_key(&dst_addr) && self.storage.len() >= self.capacity {
+            let oldest = *self
+                .storage
+                .iter()
+                .min_by_key(|(_, entry)| entry.expires_at)
+                .expect("empty path MTU cache storage")
+                .0;
+            self.storage.remove(&oldest);
+            net_trace!("path MTU cache full, evicted {}", oldest);
+        }
+
+        self.storage.insert(
+            dst_addr,
+            Entry {
+                mtu,
+                expires_at: timestamp + Self::ENTRY_LIFETIME,
+            },
+        );
+        true
+    }
+
+    /// Return the path MTU to `dst_addr`, if known.
+    pub(crate) fn lookup(&self, dst_addr: &IpAddress, timestamp: Instant) -> Option<usize> {
+        match self.storage.get(dst_addr) {
+            Some(entry) if timestamp < entry.expires_at => Some(entry.mtu),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const ADDR_1: IpAddress = IpAddress::v4(192, 168, 1, 1);
+    const ADDR_2: IpAddress = IpAddress::v4(192, 168, 1, 2);
+    const ADDR_3: IpAddress = IpAddress::v4(192, 168, 1, 3);
+
+    #[test]
+    fn test_lower_only() {
+        let mut cache = Cache::new(2);
+        assert!(cache.fill(ADDR_1, 1400, Instant::ZERO));
+        assert!(!cache.fill(ADDR_1, 1500, Instant::from_secs(1)));
+        assert!(cache.fill(ADDR_1, 1280, Instant::from_secs(1)));
+        assert_eq!(cache.lookup(&ADDR_1, Instant::from_secs(1)), Some(1280));
+    }
+
+    #[test]
+    fn test_expire() {
+        let mut cache = Cache::new(2);
+        assert!(cache.fill(ADDR_1, 1400, Instant::ZERO));
+        let expires_at = Instant::ZERO + Cache::ENTRY_LIFETIME;
+        assert_eq!(
+            cache.lookup(&ADDR_1, expires_at - Duration::from_millis(1)),
+            Some(1400)
+        );
+        assert_eq!(cache.lookup(&ADDR_1, expires_at), None);
+
+        // Once expired, a larger path MTU is recorded again.
+        assert!(cache.fill(ADDR_1, 1500, expires_at));
+        assert_eq!(cache.lookup(&ADDR_1, expires_at), Some(1500));
+    }
+
+    #[test]
+    fn test_evict_oldest() {
+        let mut cache = Cache::new(2);
+        assert!(cache.fill(ADDR_1, 1400, Instant::ZERO));
+        assert!(cache.fill(ADDR_2, 1400, Instant::from_secs(1)));
+        assert!(cache.fill(ADDR_3, 1400, Instant::from_secs(2)));
+        assert_eq!(cache.lookup(&ADDR_1, Instant::from_secs(2)), None);
+        assert_eq!(cache.lookup(&ADDR_2, Instant::from_secs(2)), Some(1400));
+        assert_eq!(cache.lookup(&ADDR_3, Instant::from_secs(2)), Some(1400));
+    }
+
+    #[test]
+    fn test_evict_refreshed() {
+        let mut cache = Cache::new(2);
+        assert!(cache.fill(ADDR_1, 1400, Instant::ZERO));
+        assert!(cache.fill(ADDR_2, 1400, Instant::from_secs(1)));
+        // Refreshing the entry of ADDR_1 makes ADDR_2 the oldest one.
+        assert!(cache.fill(ADDR_1, 1300, Instant::from_secs(2)));
+        let now = Instant::from_secs(1) + Cache::ENTRY_LIFETIME;
+        assert!(cache.fill(ADDR_3, 1400, now));
+        assert_eq!(cache.lookup(&ADDR_1, now), Some(1300));
+        assert_eq!(cache.lookup(&ADDR_2, now), None);
+        assert_eq!(cache.lookup(&ADDR_3, now), Some(1400));
+    }
+
+    #[test]
+    fn test_one_capacity() {
+        let mut cache = Cache::new(1);
+        assert!(cache.fill(ADDR_1, 1400, Instant::ZERO));
+        assert!(cache.fill(ADDR_2, 1300, Instant::from_secs(1)));
+        assert_eq!(cache.lookup(&ADDR_1, Instant::from_secs(1)), None);
+        assert_eq!(cache.lookup(&ADDR_2, Instant::from_secs(1)), Some(1300));
+    }
+
+    #[test]
+    fn test_zero_capacity() {
+        let mut cache = Cache::new(0);
+        assert!(!cache.fill(ADDR_1, 1280, Instant::ZERO));
+        assert_eq!(cache.lookup(&ADDR_1, Instant::ZERO), None);
+    }
+}
@@ -0,0 +1,230 @@
+use std::collections::HashMap;
+
+use crate::time::Instant;
+use crate::wire::IpAddress;
+
+/// Parameters of a token bucket.
+///
+/// The bucket holds up to `burst` tokens, and is refilled with `rate` tokens
+/// per second. Sending a message takes one token.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimit {
+    /// Tokens added per second.
+    pub rate: u32,
+    /// Capacity of the bucket.
+    pub burst: u32,
+}
+
+impl RateLimit {
+    pub const fn new(rate: u32, burst: u32) -> Self {
+        RateLimit { rate, burst }
+    }
+}
+
+/// Tokens are counted in thousandths, so that buckets refill every millisecond.
+const TOKEN: u64 = 1000;
+
+#[derive(Debug, Clone, Copy)]
+struct Bucket {
+    tokens: u64,
+    updated_at: Instant,
+}
+
+impl Bucket {
+    fn new(limit: RateLimit, timestamp: Instant) -> Self {
+        Bucket {
+            tokens: limit.burst as u64 * TOKEN,
+            updated_at: timestamp,
+        }
+    }
+
+    fn refill(&mut self, limit: RateLimit, timestamp: Instant) {
+        if timestamp > self.updated_at {
+            let elapsed = (timestamp - self.updated_at).total_millis();
+            self.tokens = self
+                .tokens
+                .saturating_add(elapsed.saturating_mul(limit.rate as u64))
+                .min(limit.burst as u64 * TOKEN);
+            self.updated_at = timestamp;
+        }
+    }
+
+    fn is_full(&self, limit: RateLimit) -> bool {
+        self.tokens >= limit.burst as u64 * TOKEN
+    }
+}
+
+/// An ICMP error rate limiter (RFC 1812 § 4.3.2.8).
+///
+/// A message is permitted when both the global bucket and the bucket of its
+/// destination have a token left. Without room for any destination bucket, only
+/// the global bucket applies.
+#[derive(Debug)]
+pub(crate) struct IcmpRateLimiter {
+    global: Option<(RateLimit, Bucket)>,
+    per_dst_limit: Option<RateLimit>,
+    per_dst: HashMap<IpAddress, Bucket>,
+    per_dst_capacity: usize,
+}
+
+impl IcmpRateLimiter {
+    pub(crate) fn new(
+        global_limit: Option<RateLimit>,
+        per_dst_limit: Option<RateLimit>,
+        per_dst_capacity: usize,
+        timestamp: Instant,
+    ) -> Self {
+        IcmpRateLimiter {
+            global: global_limit.map(|limit| (limit, Bucket::new(limit, timestamp))),
+            per_dst_limit,
+            per_dst: HashMap::new(),
+            per_dst_capacity,
+        }
+    }
+
+    pub(crate) fn global_limit(&self) -> Option<RateLimit> {
+        self.global.map(|(limit, _)| limit)
+    }
+
+    pub(crate) fn per_dst_limit(&self) -> Option<RateLimit> {
+        self.per_dst_limit
+    }
+
+    pub(crate) fn set_global_limit(&mut self, limit: Option<RateLimit>, timestamp: Instant) {
+        self.global = limit.map(|limit| (limit, Bucket::new(limit, timestamp)));
+    }
+
+    pub(crate) fn set_per_dst_limit(&mut self, limit: Option<RateLimit>) {
+        self.per_dst_limit = limit;
+        self.per_dst.clear();
+    }
+
+    /// Take a token for a message to `dst_addr`, returning whether it may be sent.
+    pub(crate) fn permit(&mut self, dst_addr: IpAddress, timestamp: Instant) -> bool {
+        if let Some((limit, bucket)) = &mut self.global {
+            bucket.refill(*limit, timestamp);
+            if bucket.tokens < TOKEN {
+                return false;
+            }
+        }
+
+        if let Some(limit) = self.per_dst_limit.filter(|_| self.per_dst_capacity > 0) {
+            if !self.per_dst.contains_key(&dst_addr) && self.per_dst.len() >= self.per_dst_capacity
+            {
+                self.evict(limit, timestamp);
+            }
+            let bucket = self
+                .per_dst
+                .entry(dst_addr)
+                .or_insert_with(|| Bucket::new(limit, timestamp));
+            bucket.refill(limit, timestamp);
+            if bucket.tokens < TOKEN {
+                return false;
+            }
+            bucket.tokens -= TOKEN;
+        }
+
+        if let Some((_, bucket)) = &mut self.global {
+            bucket.tokens -= TOKEN;
+        }
+        true
+    }
+
+    /// Make room for a new destination, preferring buckets that refilled completely,
+    /// since forgetting them changes nothing.
+    fn evict(&mut self, limit: RateLimit, timestamp: Instant) {
+        self.per_dst.retain(|_, bucket| {
+            bucket.refill(limit, timestamp);
+            !bucket.is_full(limit)
+        });
+        if self.per_dst.len() >= self.per_dst_capacity {
+            let fullest = *self
+                .per_dst
+                .iter()
+                .max_by_key(|(_, bucket)| bucket.tokens)
+                .expect("empty ICMP rate limiter storage")
+                .0;
+            self.per_dst.remove(&fullest);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const ADDR_1: IpAddress = IpAddress::v4(192, 168, 1, 1);
+    const ADDR_2: IpAddress = IpAddress::v4(192, 168, 1, 2);
+    const ADDR_3: IpAddress = IpAddress::v4(192, 168, 1, 3);
+
+    #[test]
+    fn test_global_refill() {
+        let mut limiter = IcmpRateLimiter::new(Some(RateLimit::new(10, 2)), None, 4, Instant::ZERO);
+        assert!(limiter.permit(ADDR_1, Instant::ZERO));
+        assert!(limiter.permit(ADDR_2, Instant::ZERO));
+        assert!(!limiter.permit(ADDR_3, Instant::ZERO));
+
+        // One token comes back every 100 ms.
+        assert!(!limiter.permit(ADDR_1, Instant::from_millis(99)));
+        assert!(limiter.permit(ADDR_1, Instant::from_millis(100)));
+        assert!(!limiter.permit(ADDR_1, Instant::from_millis(100)));
+
+        // The bucket doesn't fill beyond its burst.
+        assert!(limiter.permit(ADDR_1, Instant::from_secs(10)));
+        assert!(limiter.permit(ADDR_1, Instant::from_secs(10)));
+        assert!(!limiter.permit(ADDR_1, Instant::from_secs(10)));
+    }
+
+    #[test]
+    fn test_per_dst_buckets() {
+        let mut limiter = IcmpRateLimiter::new(None, Some(RateLimit::new(1, 1)), 4, Instant::ZERO);
+        assert!(limiter.permit(ADDR_1, Instant::ZERO));
+        assert!(!limiter.permit(ADDR_1, Instant::ZERO));
+        assert!(limiter.permit(ADDR_2, Instant::ZERO));
+        assert!(limiter.permit(ADDR_1, Instant::from_secs(1)));
+    }
+
+    #[test]
+    fn test_per_dst_denial_keeps_global_token() {
+        let mut limiter = IcmpRateLimiter::new(
+            Some(RateLimit::new(0, 2)),
+            Some(RateLimit::new(0, 1)),
+            4,
+            Instant::ZERO,
+        );
+        assert!(limiter.permit(ADDR_1, Instant::ZERO));
+        assert!(!limiter.permit(ADDR_1, Instant::ZERO));
+        assert!(limiter.permit(ADDR_2, Instant::ZERO));
+        assert!(!limiter.permit(ADDR_3, Instant::ZERO));
+    }
+
+    #[test]
+    fn test_evict_fullest() {
+        let mut limiter = IcmpRateLimiter::new(None, Some(RateLimit::new(1, 2)), 2, Instant::ZERO);
+        assert!(limiter.permit(ADDR_1, Instant::ZERO));
+        assert!(limiter.permit(ADDR_1, Instant::ZERO));
+        assert!(limiter.permit(ADDR_2, Instant::ZERO));
+
+        // ADDR_2 has a token left, so its bucket makes room for ADDR_3, and
+        // ADDR_1 stays limited.
+        assert!(limiter.permit(ADDR_3, Instant::ZERO));
+        assert!(!limiter.permit(ADDR_1, Instant::ZERO));
+    }
+
+    #[test]
+    fn test_one_per_dst_capacity() {
+        let mut limiter = IcmpRateLimiter::new(None, Some(RateLimit::new(0, 1)), 1, Instant::ZERO);
+        assert!(limiter.permit(ADDR_1, Instant::ZERO));
+        assert!(!limiter.permit(ADDR_1, Instant::ZERO));
+        // A new destination takes the only bucket, and ADDR_1 is forgotten.
+        assert!(limiter.permit(ADDR_2, Instant::ZERO));
+        assert!(limiter.permit(ADDR_1, Instant::ZERO));
+    }
+
+    #[test]
+    fn test_zero_per_dst_capacity() {
+        let mut limiter = IcmpRateLimiter::new(None, Some(RateLimit::new(1, 1)), 0, Instant::ZERO);
+        assert!(limiter.permit(ADDR_1, Instant::ZERO));
+        assert!(limiter.permit(ADDR_1, Instant::ZERO));
+    }
+}
@@ -1,6 +1,12 @@
+use core::cmp::Reverse;
+use core::hash::{Hash, Hasher};
+use core::iter;
+
+use crate::config::{IFACE_MAX_ROUTE_COUNT, IFACE_MAX_ROUTING_RULE_COUNT};
 use crate::time::Instant;
 use crate::wire::{IpAddress, IpCidr};
 use crate::wire::{Ipv4Address, Ipv4Cidr};
+use crate::wire::{Ipv6Address, Ipv6Cidr};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct RouteTableFull;
@@ -14,7 +20,7 @@ impl core::fmt::Display for RouteTableFull {
 impl std::error::Error for RouteTableFull {}
 
 /// A prefix of addresses that should be routed via a router
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Route {
     pub cidr: IpCidr,
     pub via_router: IpAddress,
@@ -22,39 +28,185 @@ pub struct Route {
     pub preferred_until: Option<Instant>,
     /// `None` means "forever".
     pub expires_at: Option<Instant>,
+    /// Among the routes of the same prefix length, the lowest metric wins.
+    pub metric: u32,
+    /// Only packets sent from this prefix use the route. `None` matches any source,
+    /// and the constraint is ignored when the source is being selected.
+    pub src_cidr: Option<IpCidr>,
+    /// Source address of the packets sent via this route, if it's one of ours.
+    pub pref_src: Option<IpAddress>,
+    /// The routing table of the route, [`Routes::MAIN_TABLE`] unless selected by a
+    /// [`RoutingRule`].
+    pub table: u32,
+}
+
+/// A rule selecting the routing table used for some packets (ie. "ip rule add").
+///
+/// Fields set to `None` match any packet. The source constraint doesn't match
+/// packets whose source is being selected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RoutingRule {
+    /// Rules are evaluated by increasing priority.
+    pub priority: u32,
+    pub src_cidr: Option<IpCidr>,
+    pub dst_cidr: Option<IpCidr>,
+    pub table: u32,
+}
+
+impl RoutingRule {
+    fn matches(&self, dst_addr: &IpAddress, src_addr: Option<&IpAddress>) -> bool {
+        self.dst_cidr
+            .map_or(true, |cidr| cidr.contains_addr(dst_addr))
+            && self.src_cidr.map_or(true, |cidr| {
+                src_addr.is_some_and(|addr| cidr.contains_addr(addr))
+            })
+    }
 }
 
 const IPV4_DEFAULT: IpCidr = IpCidr::Ipv4(Ipv4Cidr::new(Ipv4Address::new(0, 0, 0, 0), 0));
+const IPV6_DEFAULT: IpCidr =
+    IpCidr::Ipv6(Ipv6Cidr::new(Ipv6Address::new(0, 0, 0, 0, 0, 0, 0, 0), 0));
 
 impl Route {
-    /// Returns a route to 0.0.0.0/0 via the `gateway`, with no expiry.
-    pub fn new_ipv4_gateway(gateway: Ipv4Address) -> Route {
+    /// Returns a route to `cidr` via `via_router` in the main table, with no expiry.
+    pub fn new(cidr: IpCidr, via_router: IpAddress) -> Route {
         Route {
-            cidr: IPV4_DEFAULT,
-            via_router: gateway.into(),
+            cidr,
+            via_router,
             preferred_until: None,
             expires_at: None,
+            metric: 0,
+            src_cidr: None,
+            pref_src: None,
+            table: Routes::MAIN_TABLE,
         }
     }
+
+    /// Returns a route to 0.0.0.0/0 via the `gateway`, with no expiry.
+    pub fn new_ipv4_gateway(gateway: Ipv4Address) -> Route {
+        Route::new(IPV4_DEFAULT, gateway.into())
+    }
+
+    /// Returns a route to ::/0 via the `gateway`, with no expiry.
+    pub fn new_ipv6_gateway(gateway: Ipv6Address) -> Route {
+        Route::new(IPV6_DEFAULT, gateway.into())
+    }
 }
 
-/// A routing table.
+/// The routing tables, and the rules selecting them.
+///
+/// A destination is looked up in the tables of the matching rules in order, then
+/// in the main table, and the first table with a matching route wins. Within a
+/// table, the most specific route wins, then the one still within its preferred
+/// lifetime, then the one with the lowest metric.
+///
+/// Packets are spread over routes that tie by hashing their flow, so that all the
+/// packets of a flow take the same route. Adding or removing one of these routes
+/// only moves the flows going to or through it.
 #[derive(Debug)]
 pub struct Routes {
     storage: Vec<Route>,
+    rules: Vec<RoutingRule>,
+    max_routes: usize,
+    max_rules: usize,
 }
 
 impl Routes {
-    /// Creates a new empty routing table.
+    /// The table used when no rule selects another one.
+    pub const MAIN_TABLE: u32 = 254;
+
+    /// Creates a new empty routing table, with the default limits.
     pub fn new() -> Self {
+        Self::with_limits(IFACE_MAX_ROUTE_COUNT, IFACE_MAX_ROUTING_RULE_COUNT)
+    }
+
+    /// Creates a new empty routing table holding up to `max_routes` routes and
+    /// `max_rules` rules.
+    pub fn with_limits(max_routes: usize, max_rules: usize) -> Self {
         Self {
             storage: Vec::new(),
+            rules: Vec::new(),
+            max_routes,
+            max_rules,
+        }
+    }
+
+    fn push(&mut self, route: Route) -> Result<(), RouteTableFull> {
+        if self.storage.len() >= self.max_routes {
+            return Err(RouteTableFull);
         }
+        self.storage.push(route);
+        Ok(())
+    }
+
+    /// Add a route (ie. "ip route add `cidr` via `via_router` table `table`").
+    ///
+    /// On success, returns the previous route of the same table, prefix, source
+    /// and metric, if any.
+    pub fn add_route(&mut self, route: Route) -> Result<Option<Route>, RouteTableFull> {
+        let old = self
+            .storage
+            .iter()
+            .position(|r| {
+                r.table == route.table
+                    && r.cidr == route.cidr
+                    && r.src_cidr == route.src_cidr
+                    && r.metric == route.metric
+            })
+            .map(|i| self.storage.remove(i));
+        self.push(route)?;
+        Ok(old)
+    }
+
+    /// Remove the first route to `cidr` in `table`, if any.
+    pub fn remove_route(&mut self, table: u32, cidr: IpCidr) -> Option<Route> {
+        let i = self
+            .storage
+            .iter()
+            .position(|r| r.table == table && r.cidr == cidr)?;
+        Some(self.storage.remove(i))
+    }
+
+    /// Return the routes of all the tables.
+    pub fn iter(&self) -> impl Iterator<Item = &Route> + '_ {
+        self.storage.iter()
+    }
+
+    /// Return the rules, by increasing priority.
+    pub fn rules(&self) -> &[RoutingRule] {
+        &self.rules
+    }
+
+    /// Add a rule, after the rules of the same priority (ie. "ip rule add").
+    ///
+    /// Returns `RouteTableFull` if the rule table is full.
+    pub fn add_rule(&mut self, rule: RoutingRule) -> Result<(), RouteTableFull> {
+        if self.rules.len() >= self.max_rules {
+            return Err(RouteTableFull);
+        }
+        let i = self.rules.partition_point(|r| r.priority <= rule.priority);
+        self.rules.insert(i, rule);
+        Ok(())
+    }
+
+    /// Remove the first rule of the given priority, if any.
+    pub fn remove_rule(&mut self, priority: u32) -> Option<RoutingRule> {
+        let i = self.rules.iter().position(|r| r.priority == priority)?;
+        Some(self.rules.remove(i))
     }
 
     /// Update the routes of this node.
-    pub fn update<F: FnOnce(&mut Vec<Route>)>(&mut self, f: F) {
-        f(&mut self.storage);
+    ///
+    /// If the routes don't fit in the table anymore, they are left unchanged and
+    /// `RouteTableFull` is returned.
+    pub fn update<F: FnOnce(&mut Vec<Route>)>(&mut self, f: F) -> Result<(), RouteTableFull> {
+        let mut storage = self.storage.clone();
+        f(&mut storage);
+        if storage.len() > self.max_routes {
+            return Err(RouteTableFull);
+        }
+        self.storage = storage;
+        Ok(())
     }
 
     /// Add a default ipv4 gateway (ie. "ip route add 0.0.0.0/0 via `gateway`").
@@ -65,7 +217,41 @@ impl Routes {
         gateway: Ipv4Address,
     ) -> Result<Option<Route>, RouteTableFull> {
         let old = self.remove_default_ipv4_route();
-        self.storage.push(Route::new_ipv4_gateway(gateway));
+        self.push(Route::new_ipv4_gateway(gateway))?;
+        Ok(old)
+    }
+
+    /// Add a default ipv6 gateway (ie. "ip -6 route add ::/0 via `gateway`").
+    ///
+    /// On success, returns the previous default route, if any.
+    pub fn add_default_ipv6_route(
+        &mut self,
+        gateway: Ipv6Address,
+    ) -> Result<Option<Route>, RouteTableFull> {
+        let old = self.remove_default_ipv6_route();
+        self.push(Route::new_ipv6_gateway(gateway))?;
+        Ok(old)
+    }
+
+    /// Add a host route to `addr` via `gateway` (ie. "ip route add `addr`/32 via `gateway`").
+    ///
+    /// On success, returns the previous route to that host, if any.
+    pub fn add_ipv4_host_route(
+        &mut self,
+        addr: Ipv4Address,
+        gateway: Ipv4Address,
+        expires_at: Option<Instant>,
+    ) -> Result<Option<Route>, RouteTableFull> {
+        let cidr = IpCidr::Ipv4(Ipv4Cidr::new(addr, 32));
+        let old = self
+            .storage
+            .iter()
+            .position(|r| r.table == Self::MAIN_TABLE && r.cidr == cidr)
+            .map(|i| self.storage.remove(i));
+        self.push(Route {
+            expires_at,
+            ..Route::new(cidr, gateway.into())
+        })?;
         Ok(old)
     }
 
@@ -77,7 +263,23 @@ impl Routes {
             .storage
             .iter()
             .enumerate()
-            .find(|(_, r)| r.cidr == IPV4_DEFAULT)
+            .find(|(_, r)| r.table == Self::MAIN_TABLE && r.cidr == IPV4_DEFAULT)
+        {
+            Some(self.storage.remove(i))
+        } else {
+            None
+        }
+    }
+
+    /// Remove the default ipv6 gateway
+    ///
+    /// On success, returns the previous default route, if any.
+    pub fn remove_default_ipv6_route(&mut self) -> Option<Route> {
+        if let Some((i, _)) = self
+            .storage
+            .iter()
+            .enumerate()
+            .find(|(_, r)| r.table == Self::MAIN_TABLE && r.cidr == IPV6_DEFAULT)
         {
             Some(self.storage.remove(i))
         } else {
@@ -85,22 +287,112 @@ impl Routes {
         }
     }
 
-    pub(crate) fn lookup(&self, addr: &IpAddress, timestamp: Instant) -> Option<IpAddress> {
+    /// Remove the routes that expired before `timestamp`.
+    pub(crate) fn remove_expired(&mut self, timestamp: Instant) {
+        self.storage.retain(|route| {
+            route
+                .expires_at
+                .map_or(true, |expires_at| timestamp <= expires_at)
+        });
+    }
+
+    /// Return the route to `addr` for packets sent from `src_addr`, or from an
+    /// address yet to be selected if `None`.
+    ///
+    /// `flow_hash` picks among equal-cost routes, see [`Packet::flow_hash`](super::packet::Packet::flow_hash).
+    pub(crate) fn lookup(
+        &self,
+        addr: &IpAddress,
+        src_addr: Option<&IpAddress>,
+        flow_hash: u32,
+        timestamp: Instant,
+    ) -> Option<&Route> {
         assert!(addr.is_unicast());
 
-        self.storage
+        self.rules
             .iter()
-            // Keep only matching routes
-            .filter(|route| {
-                if let Some(expires_at) = route.expires_at {
-                    if timestamp > expires_at {
-                        return false;
+            .filter(|rule| rule.matches(addr, src_addr))
+            .map(|rule| rule.table)
+            .chain(iter::once(Self::MAIN_TABLE))
+            .find_map(|table| self.lookup_table(table, addr, src_addr, flow_hash, timestamp))
+    }
+
+    fn lookup_table(
+        &self,
+        table: u32,
+        addr: &IpAddress,
+        src_addr: Option<&IpAddress>,
+        flow_hash: u32,
+        timestamp: Instant,
+    ) -> Option<&Route> {
+        let routes = || {
+            self.storage
+                .iter()
+                // Keep only matching routes
+                .filter(|route| {
+                    if let Some(expires_at) = route.expires_at {
+                        if timestamp > expires_at {
+                            return false;
+                        }
                     }
-                }
-                route.cidr.contains_addr(addr)
+                    route.table == table
+                        && route.cidr.contains_addr(addr)
+                        && route.src_cidr.map_or(true, |cidr| {
+                            src_addr.map_or(true, |src_addr| cidr.contains_addr(src_addr))
+                        })
+                })
+        };
+        // pick the most specific one (highest prefix_len), and among those
+        // prefer a route that is still within its preferred lifetime, then
+        // the one with the lowest metric
+        let cost = |route: &Route| {
+            let preferred = route
+                .preferred_until
+                .map_or(true, |preferred_until| timestamp <= preferred_until);
+            (route.cidr.prefix_len(), preferred, Reverse(route.metric))
+        };
+        let best = routes().map(cost).max()?;
+
+        // then spread the flows over the equal-cost routes (rendezvous hashing)
+        routes()
+            .filter(|route| cost(route) == best)
+            .max_by_key(|route| {
+                let mut hasher = FlowHasher::new();
+                (flow_hash, route.via_router).hash(&mut hasher);
+                hasher.finish()
             })
-            // pick the most specific one (highest prefix_len)
-            .max_by_key(|route| route.cidr.prefix_len())
-            .map(|route| route.via_router)
+    }
+}
+
+/// The 32-bit FNV-1a hash followed by the MurmurHash3 finalizer, used to hash
+/// flows and spread them over routes.
+///
+/// Unlike the standard library hasher, it's stable across builds and runs. The
+/// finalizer mixes the last bytes written, like the address of a route, into all
+/// the bits of the hash.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct FlowHasher(u32);
+
+impl FlowHasher {
+    pub(crate) fn new() -> Self {
+        FlowHasher(0x811c_9dc5)
+    }
+}
+
+impl Hasher for FlowHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 = (self.0 ^ byte as u32).wrapping_mul(0x0100_0193);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        let mut hash = self.0;
+        hash ^= hash >> 16;
+        hash = hash.wrapping_mul(0x85eb_ca6b);
+        hash ^= hash >> 13;
+        hash = hash.wrapping_mul(0xc2b2_ae35);
+        hash ^= hash >> 16;
+        hash as u64
     }
 }
@@ -15,7 +15,7 @@ enum NeighborState {
     #[default]
     Active,
     /// Socket should not be polled until either `silent_until` passes or
-    /// `neighbor` appears in the neighbor cache.
+    /// `neighbor`, the next hop of its last packet, appears in the neighbor cache.
     Waiting {
         neighbor: IpAddress,
         silent_until: Instant,
@@ -11,10 +11,15 @@ use crate::socket::{AnySocket, Socket};
 #[derive(Debug, Default)]
 pub struct SocketStorage<'a> {
     inner: Option<Item<'a>>,
+    /// Bumped every time a socket is removed, so that its handle goes stale.
+    generation: u32,
 }
 
 impl<'a> SocketStorage<'a> {
-    pub const EMPTY: Self = Self { inner: None };
+    pub const EMPTY: Self = Self {
+        inner: None,
+        generation: 0,
+    };
 }
 
 /// An item of a socket set.
@@ -25,15 +30,42 @@ pub(crate) struct Item<'a> {
 }
 
 /// A handle, identifying a socket in an Interface.
+///
+/// The handle of a removed socket is stale: it doesn't refer to the socket
+/// added next into the same slot.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Hash)]
-pub struct SocketHandle(usize);
+pub struct SocketHandle {
+    index: usize,
+    generation: u32,
+}
 
 impl fmt::Display for SocketHandle {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "#{}", self.0)
+        write!(f, "#{}.{}", self.index, self.generation)
+    }
+}
+
+/// Error returned when a handle can't be used to access a socket.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HandleError {
+    /// The handle doesn't refer to a socket of this set, usually because the
+    /// socket was removed.
+    Stale,
+    /// The socket has another type than the one requested.
+    WrongType,
+}
+
+impl fmt::Display for HandleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            HandleError::Stale => write!(f, "handle does not refer to a valid socket"),
+            HandleError::WrongType => write!(f, "handle refers to a socket of a wrong type"),
+        }
     }
 }
 
+impl std::error::Error for HandleError {}
+
 /// An extensible set of sockets.
 ///
 /// The lifetime `'a` is used when storing a `Socket<'a>`.  If you're using
@@ -61,12 +93,13 @@ impl<'a> SocketSet<'a> {
     pub fn add<T: AnySocket<'a>>(&mut self, socket: T) -> SocketHandle {
         fn put<'a>(index: usize, slot: &mut SocketStorage<'a>, socket: Socket<'a>) -> SocketHandle {
             net_trace!("[{}]: adding", index);
-            let handle = SocketHandle(index);
+            let handle = SocketHandle {
+                index,
+                generation: slot.generation,
+            };
             let mut meta = Meta::default();
             meta.handle = handle;
-            *slot = SocketStorage {
-                inner: Some(Item { meta, socket }),
-            };
+            slot.inner = Some(Item { meta, socket });
             handle
         }
 
@@ -81,52 +114,99 @@ impl<'a> SocketSet<'a> {
         match &mut self.sockets {
             ManagedSlice::Borrowed(_) => panic!("adding a socket to a full SocketSet"),
             ManagedSlice::Owned(sockets) => {
-                sockets.push(SocketStorage { inner: None });
+                sockets.push(SocketStorage::EMPTY);
                 let index = sockets.len() - 1;
                 put(index, &mut sockets[index], socket)
             }
         }
     }
 
-    /// Get a socket from the set by its handle, as mutable.
+    fn item(&self, handle: SocketHandle) -> Result<&Item<'a>, HandleError> {
+        match self.sockets.get(handle.index) {
+            Some(slot) if slot.generation == handle.generation => {
+                slot.inner.as_ref().ok_or(HandleError::Stale)
+            }
+            _ => Err(HandleError::Stale),
+        }
+    }
+
+    fn item_mut(&mut self, handle: SocketHandle) -> Result<&mut Item<'a>, HandleError> {
+        match self.sockets.get_mut(handle.index) {
+            Some(slot) if slot.generation == handle.generation => {
+                slot.inner.as_mut().ok_or(HandleError::Stale)
+            }
+            _ => Err(HandleError::Stale),
+        }
+    }
+
+    /// Get a socket from the set by its handle.
     ///
     /// # Panics
-    /// This function may panic if the handle does not belong to this socket set
-    /// or the socket has the wrong type.
+    /// This function panics if the handle is stale or the socket has the wrong type.
+    /// See [try_get](#method.try_get) for a non-panicking version.
     pub fn get<T: AnySocket<'a>>(&self, handle: SocketHandle) -> &T {
-        match self.sockets[handle.0].inner.as_ref() {
-            Some(item) => {
-                T::downcast(&item.socket).expect("handle refers to a socket of a wrong type")
-            }
-            None => panic!("handle does not refer to a valid socket"),
+        match self.try_get(handle) {
+            Ok(socket) => socket,
+            Err(err) => panic!("{}: {}", handle, err),
         }
     }
 
-    /// Get a mutable socket from the set by its handle, as mutable.
+    /// Get a mutable socket from the set by its handle.
     ///
     /// # Panics
-    /// This function may panic if the handle does not belong to this socket set
-    /// or the socket has the wrong type.
+    /// This function panics if the handle is stale or the socket has the wrong type.
+    /// See [try_get_mut](#method.try_get_mut) for a non-panicking version.
     pub fn get_mut<T: AnySocket<'a>>(&mut self, handle: SocketHandle) -> &mut T {
-        match self.sockets[handle.0].inner.as_mut() {
-            Some(item) => T::downcast_mut(&mut item.socket)
-                .expect("handle refers to a socket of a wrong type"),
-            None => panic!("handle does not refer to a valid socket"),
+        match self.try_get_mut(handle) {
+            Ok(socket) => socket,
+            Err(err) => panic!("{}: {}", handle, err),
         }
     }
 
+    /// Get a socket from the set by its handle, or an error if the handle is stale
+    /// or the socket has the wrong type.
+    pub fn try_get<T: AnySocket<'a>>(&self, handle: SocketHandle) -> Result<&T, HandleError> {
+        let item = self.item(handle)?;
+        T::downcast(&item.socket).ok_or(HandleError::WrongType)
+    }
+
+    /// Get a mutable socket from the set by its handle, or an error if the handle is
+    /// stale or the socket has the wrong type.
+    pub fn try_get_mut<T: AnySocket<'a>>(
+        &mut self,
+        handle: SocketHandle,
+    ) -> Result<&mut T, HandleError> {
+        let item = self.item_mut(handle)?;
+        T::downcast_mut(&mut item.socket).ok_or(HandleError::WrongType)
+    }
+
     /// Remove a socket from the set, without changing its state.
     ///
     /// # Panics
-    /// This function may panic if the handle does not belong to this socket set.
+    /// This function panics if the handle is stale.
+    /// See [try_remove](#method.try_remove) for a non-panicking version.
     pub fn remove(&mut self, handle: SocketHandle) -> Socket<'a> {
-        net_trace!("[{}]: removing", handle.0);
-        match self.sockets[handle.0].inner.take() {
-            Some(item) => item.socket,
-            None => panic!("handle does not refer to a valid socket"),
+        match self.try_remove(handle) {
+            Ok(socket) => socket,
+            Err(err) => panic!("{}: {}", handle, err),
         }
     }
 
+    /// Remove a socket from the set, without changing its state, or return an error
+    /// if the handle is stale.
+    ///
+    /// The handle of the socket, and its copies, are stale afterwards.
+    pub fn try_remove(&mut self, handle: SocketHandle) -> Result<Socket<'a>, HandleError> {
+        let slot = match self.sockets.get_mut(handle.index) {
+            Some(slot) if slot.generation == handle.generation => slot,
+            _ => return Err(HandleError::Stale),
+        };
+        let item = slot.inner.take().ok_or(HandleError::Stale)?;
+        net_trace!("[{}]: removing", handle.index);
+        slot.generation = slot.generation.wrapping_add(1);
+        Ok(item.socket)
+    }
+
     /// Get an iterator to the inner sockets.
     pub fn iter(&self) -> impl Iterator<Item = (SocketHandle, &Socket<'a>)> {
         self.items().map(|i| (i.meta.handle, &i.socket))
@@ -147,3 +227,87 @@ impl<'a> SocketSet<'a> {
         self.sockets.iter_mut().filter_map(|x| x.inner.as_mut())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::socket::{dhcpv4, udp};
+
+    fn udp_socket() -> udp::Socket<'static> {
+        udp::Socket::new(
+            udp::PacketBuffer::new(vec![udp::PacketMetadata::EMPTY; 1], vec![0; 64]),
+            udp::PacketBuffer::new(vec![udp::PacketMetadata::EMPTY; 1], vec![0; 64]),
+        )
+    }
+
+    #[test]
+    fn test_removed_handle() {
+        let mut sockets = SocketSet::new(vec![]);
+        let handle = sockets.add(udp_socket());
+        assert!(sockets.try_get::<udp::Socket>(handle).is_ok());
+
+        sockets.remove(handle);
+        assert_eq!(
+            sockets.try_get::<udp::Socket>(handle).err(),
+            Some(HandleError::Stale)
+        );
+        assert_eq!(
+            sockets.try_get_mut::<udp::Socket>(handle).err(),
+            Some(HandleError::Stale)
+        );
+        assert_eq!(sockets.try_remove(handle).err(), Some(HandleError::Stale));
+    }
+
+    #[test]
+    fn test_reused_slot() {
+        let mut sockets = SocketSet::new(vec![]);
+        let old_handle = sockets.add(udp_socket());
+        sockets.remove(old_handle);
+
+        // The new socket takes the slot of the removed one, with a new handle.
+        let new_handle = sockets.add(udp_socket());
+        assert_eq!(new_handle.index, old_handle.index);
+        assert_ne!(new_handle, old_handle);
+        assert_eq!(
+            sockets.try_get::<udp::Socket>(old_handle).err(),
+            Some(HandleError::Stale)
+        );
+        assert_eq!(
+            sockets.try_remove(old_handle).err(),
+            Some(HandleError::Stale)
+        );
+        assert!(sockets.try_get::<udp::Socket>(new_handle).is_ok());
+        assert_eq!(
+            sockets.iter().map(|(handle, _)| handle).collect::<Vec<_>>(),
+            [new_handle]
+        );
+    }
+
+    #[test]
+    fn test_wrong_type() {
+        let mut sockets = SocketSet::new(vec![]);
+        let handle = sockets.add(udp_socket());
+        assert_eq!(
+            sockets.try_get::<dhcpv4::Socket>(handle).err(),
+            Some(HandleError::WrongType)
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "handle does not refer to a valid socket")]
+    fn test_get_stale() {
+        let mut sockets = SocketSet::new(vec![]);
+        let handle = sockets.add(udp_socket());
+        sockets.remove(handle);
+        sockets.get::<udp::Socket>(handle);
+    }
+
+    #[test]
+    #[should_panic(expected = "handle does not refer to a valid socket")]
+    fn test_get_mut_stale() {
+        let mut sockets = SocketSet::new(vec![]);
+        let handle = sockets.add(udp_socket());
+        sockets.remove(handle);
+        sockets.get_mut::<udp::Socket>(handle);
+    }
+}
@@ -0,0 +1,296 @@
+use core::str::FromStr;
+
+use crate::wire::{EthernetAddress, HardwareAddress, IpAddress, IpCidr, IpVersion};
+use crate::wire::{Ipv4Address, Ipv4Cidr, Ipv6Address, Ipv6Cidr};
+
+use super::neighbor::NeighborCacheFull;
+use super::route::{Route, RouteTableFull, Routes, RoutingRule};
+use super::{AddrTableFull, Interface};
+
+/// Why a line of a static configuration could not be parsed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseErrorKind {
+    /// The line starts with an unknown keyword.
+    UnknownDirective,
+    /// An option is not known by the directive.
+    UnknownOption,
+    /// An argument or a mandatory option is missing.
+    MissingValue,
+    /// An argument is not a valid address, prefix or number, or doesn't match the
+    /// IP version of the others.
+    InvalidValue,
+}
+
+impl core::fmt::Display for ParseErrorKind {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            ParseErrorKind::UnknownDirective => write!(f, "unknown directive"),
+            ParseErrorKind::UnknownOption => write!(f, "unknown option"),
+            ParseErrorKind::MissingValue => write!(f, "missing value"),
+            ParseErrorKind::InvalidValue => write!(f, "invalid value"),
+        }
+    }
+}
+
+/// An error in a static configuration.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParseError {
+    /// The line of the error, starting at 1.
+    pub line: usize,
+    pub kind: ParseErrorKind,
+}
+
+impl core::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "line {}: {}", self.line, self.kind)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// An error applying a static configuration to an interface.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ApplyError {
+    AddrTableFull,
+    /// The routes or the rules don't fit in the routing table.
+    RouteTableFull,
+    /// The static neighbors don't fit in the neighbor cache.
+    NeighborCacheFull,
+}
+
+impl core::fmt::Display for ApplyError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            ApplyError::AddrTableFull => write!(f, "{AddrTableFull}"),
+            ApplyError::RouteTableFull => write!(f, "{RouteTableFull}"),
+            ApplyError::NeighborCacheFull => write!(f, "{NeighborCacheFull}"),
+        }
+    }
+}
+
+impl std::error::Error for ApplyError {}
+
+impl From<AddrTableFull> for ApplyError {
+    fn from(_: AddrTableFull) -> Self {
+        ApplyError::AddrTableFull
+    }
+}
+
+impl From<RouteTableFull> for ApplyError {
+    fn from(_: RouteTableFull) -> Self {
+        ApplyError::RouteTableFull
+    }
+}
+
+impl From<NeighborCacheFull> for ApplyError {
+    fn from(_: NeighborCacheFull) -> Self {
+        ApplyError::NeighborCacheFull
+    }
+}
+
+type Result<T> = core::result::Result<T, ParseErrorKind>;
+
+/// A static interface configuration, parsed from text.
+///
+/// Each line holds one directive, and `#` starts a comment:
+///
+/// ```text
+/// hwaddr 02:00:00:00:00:01
+/// address 192.168.69.1/24
+/// address fdaa::1/64
+/// route default via 192.168.69.100
+/// route 10.0.0.0/8 via 192.168.69.254 metric 10 table 100 from 192.168.69.0/24 src 192.168.69.1
+/// rule 100 from 192.168.69.0/24 to 10.0.0.0/8 table 100
+/// neighbor 192.168.69.100 lladdr 02:00:00:00:00:02
+/// ```
+///
+/// The `route` and `rule` options may come in any order, and mirror the fields of
+/// [Route] and [RoutingRule]; `via` and `table` are mandatory, respectively.
+/// `default` is the IPv4 or IPv6 default prefix, following the gateway.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StaticConfig {
+    pub hardware_addr: Option<HardwareAddress>,
+    pub ip_addrs: Vec<IpCidr>,
+    pub routes: Vec<Route>,
+    pub rules: Vec<RoutingRule>,
+    /// Static neighbors, as protocol and hardware addresses.
+    pub neighbors: Vec<(IpAddress, HardwareAddress)>,
+}
+
+impl StaticConfig {
+    /// Parse a static configuration.
+    pub fn parse(text: &str) -> core::result::Result<StaticConfig, ParseError> {
+        let mut config = StaticConfig::default();
+        for (index, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default();
+            config.parse_line(line).map_err(|kind| ParseError {
+                line: index + 1,
+                kind,
+            })?;
+        }
+        Ok(config)
+    }
+
+    fn parse_line(&mut self, line: &str) -> Result<()> {
+        let mut words = line.split_whitespace();
+        let Some(directive) = words.next() else {
+            return Ok(());
+        };
+
+        match directive {
+            "hwaddr" => {
+                let addr: EthernetAddress = parse(words.next())?;
+                if !addr.is_unicast() {
+                    return Err(ParseErrorKind::InvalidValue);
+                }
+                self.hardware_addr = Some(addr.into());
+            }
+            "address" => {
+                let cidr: IpCidr = parse(words.next())?;
+                if !cidr.address().is_unicast() {
+                    return Err(ParseErrorKind::InvalidValue);
+                }
+                self.ip_addrs.push(cidr);
+            }
+            "route" => {
+                let route = parse_route(&mut words)?;
+                self.routes.push(route);
+            }
+            "rule" => {
+                let rule = parse_rule(&mut words)?;
+                self.rules.push(rule);
+            }
+            "neighbor" => {
+                let protocol_addr: IpAddress = parse(words.next())?;
+                expect(words.next(), "lladdr")?;
+                let hardware_addr: EthernetAddress = parse(words.next())?;
+                if !protocol_addr.is_unicast() || !hardware_addr.is_unicast() {
+                    return Err(ParseErrorKind::InvalidValue);
+                }
+                self.neighbors.push((protocol_addr, hardware_addr.into()));
+            }
+            _ => return Err(ParseErrorKind::UnknownDirective),
+        }
+
+        match words.next() {
+            Some(_) => Err(ParseErrorKind::UnknownOption),
+            None => Ok(()),
+        }
+    }
+
+    /// Apply the configuration to an interface: set its hardware address, add the
+    /// IP addresses it doesn't have yet, then the routes, rules and static neighbors.
+    ///
+    /// Stops at the first table that is full, leaving the previous entries applied.
+    ///
+    /// # Panics
+    /// This function panics if the configuration has a hardware address and the
+    /// medium of the interface is not Ethernet.
+    pub fn apply(&self, iface: &mut Interface) -> core::result::Result<(), ApplyError> {
+        if let Some(hardware_addr) = self.hardware_addr {
+            iface.set_hardware_addr(hardware_addr);
+        }
+        iface.update_ip_addrs(|ip_addrs| {
+            for cidr in &self.ip_addrs {
+                if !ip_addrs.contains(cidr) {
+                    ip_addrs.push(*cidr);
+                }
+            }
+        })?;
+        for route in &self.routes {
+            iface.routes_mut().add_route(*route)?;
+        }
+        for rule in &self.rules {
+            iface.routes_mut().add_rule(*rule)?;
+        }
+        for &(protocol_addr, hardware_addr) in &self.neighbors {
+            iface.add_static_neighbor(protocol_addr, hardware_addr)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for StaticConfig {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> core::result::Result<StaticConfig, ParseError> {
+        StaticConfig::parse(s)
+    }
+}
+
+fn parse<T: FromStr>(word: Option<&str>) -> Result<T> {
+    word.ok_or(ParseErrorKind::MissingValue)?
+        .parse()
+        .map_err(|_| ParseErrorKind::InvalidValue)
+}
+
+fn expect(word: Option<&str>, keyword: &str) -> Result<()> {
+    match word {
+        Some(word) if word == keyword => Ok(()),
+        Some(_) => Err(ParseErrorKind::UnknownOption),
+        None => Err(ParseErrorKind::MissingValue),
+    }
+}
+
+fn parse_route<'a>(words: &mut impl Iterator<Item = &'a str>) -> Result<Route> {
+    let cidr = match words.next() {
+        Some("default") => None,
+        word => Some(parse::<IpCidr>(word)?),
+    };
+
+    let mut via_router = None;
+    let mut route = Route::new(IPV4_DEFAULT.into(), Ipv4Address::UNSPECIFIED.into());
+    while let Some(option) = words.next() {
+        match option {
+            "via" => via_router = Some(parse::<IpAddress>(words.next())?),
+            "metric" => route.metric = parse(words.next())?,
+            "table" => route.table = parse(words.next())?,
+            "from" => route.src_cidr = Some(parse(words.next())?),
+            "src" => route.pref_src = Some(parse(words.next())?),
+            _ => return Err(ParseErrorKind::UnknownOption),
+        }
+    }
+
+    route.via_router = via_router.ok_or(ParseErrorKind::MissingValue)?;
+    route.cidr = match (cidr, route.via_router.version()) {
+        (Some(cidr), _) => cidr,
+        (None, IpVersion::Ipv4) => IPV4_DEFAULT.into(),
+        (None, IpVersion::Ipv6) => IPV6_DEFAULT.into(),
+    };
+
+    let version = route.cidr.address().version();
+    if route.via_router.version() != version
+        || route
+            .src_cidr
+            .is_some_and(|cidr| cidr.address().version() != version)
+        || route.pref_src.is_some_and(|addr| addr.version() != version)
+    {
+        return Err(ParseErrorKind::InvalidValue);
+    }
+    Ok(route)
+}
+
+fn parse_rule<'a>(words: &mut impl Iterator<Item = &'a str>) -> Result<RoutingRule> {
+    let mut rule = RoutingRule {
+        priority: parse(words.next())?,
+        src_cidr: None,
+        dst_cidr: None,
+        table: Routes::MAIN_TABLE,
+    };
+
+    let mut table = None;
+    while let Some(option) = words.next() {
+        match option {
+            "from" => rule.src_cidr = Some(parse(words.next())?),
+            "to" => rule.dst_cidr = Some(parse(words.next())?),
+            "table" => table = Some(parse(words.next())?),
+            _ => return Err(ParseErrorKind::UnknownOption),
+        }
+    }
+
+    rule.table = table.ok_or(ParseErrorKind::MissingValue)?;
+    Ok(rule)
+}
+
+const IPV4_DEFAULT: Ipv4Cidr = Ipv4Cidr::new(Ipv4Address::UNSPECIFIED, 0);
+const IPV6_DEFAULT: Ipv6Cidr = Ipv6Cidr::new(Ipv6Address::UNSPECIFIED, 0);
@@ -92,23 +92,38 @@ mod macros;
 mod parsers;
 mod rand;
 
+/// Default sizes of the buffers and tables of the stack.
+///
+/// The `IFACE_*` limits are the defaults of [`iface::Limits`], which sets them
+/// for each interface.
 pub mod config {
     #![allow(unused)]
     pub const ASSEMBLER_MAX_SEGMENT_COUNT: usize = 4;
+    pub const BRIDGE_FDB_COUNT: usize = 64;
+    pub const BRIDGE_LOCAL_QUEUE_COUNT: usize = 16;
     pub const DNS_MAX_NAME_SIZE: usize = 255;
     pub const DNS_MAX_RESULT_COUNT: usize = 1;
-    pub const DNS_MAX_SERVER_COUNT: usize = 1;
+    pub const DNS_MAX_SERVER_COUNT: usize = 3;
     pub const FRAGMENTATION_BUFFER_SIZE: usize = 4096;
+    pub const IFACE_CONNTRACK_COUNT: usize = 64;
+    pub const IFACE_ICMP_RATE_LIMIT_DST_COUNT: usize = 16;
+    pub const IFACE_LOOPBACK_QUEUE_COUNT: usize = 16;
     pub const IFACE_MAX_ADDR_COUNT: usize = 8;
+    pub const IFACE_MAX_FIREWALL_RULE_COUNT: usize = 32;
     pub const IFACE_MAX_MULTICAST_GROUP_COUNT: usize = 4;
-    pub const IFACE_MAX_ROUTE_COUNT: usize = 4;
+    pub const IFACE_MAX_PORT_FORWARD_COUNT: usize = 8;
+    pub const IFACE_MAX_ROUTE_COUNT: usize = 16;
+    pub const IFACE_MAX_ROUTING_RULE_COUNT: usize = 8;
     pub const IFACE_MAX_SIXLOWPAN_ADDRESS_CONTEXT_COUNT: usize = 4;
+    pub const IFACE_NAT_MAPPING_COUNT: usize = 64;
     pub const IFACE_NEIGHBOR_CACHE_COUNT: usize = 3;
+    pub const IFACE_PATH_MTU_CACHE_COUNT: usize = 8;
     pub const REASSEMBLY_BUFFER_COUNT: usize = 4;
     pub const REASSEMBLY_BUFFER_SIZE: usize = 1500;
     pub const RPL_RELATIONS_BUFFER_COUNT: usize = 16;
     pub const RPL_PARENTS_BUFFER_COUNT: usize = 8;
     pub const IPV6_HBH_MAX_OPTIONS: usize = 4;
+    pub const VLAN_TRUNK_QUEUE_COUNT: usize = 16;
 }
 
 pub mod iface;
@@ -0,0 +1,362 @@
+use std::collections::{HashMap, VecDeque};
+
+use crate::config::{BRIDGE_FDB_COUNT, BRIDGE_LOCAL_QUEUE_COUNT};
+use crate::phy::{self, Device, DeviceCapabilities, Medium};
+use crate::time::{Duration, Instant};
+use crate::wire::{EthernetAddress, EthernetFrame};
+
+/// A port of a bridge.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PortId {
+    Device(usize),
+    Local,
+}
+
+/// A learned station.
+#[derive(Debug, Clone, Copy)]
+struct Entry {
+    port: PortId,
+    expires_at: Instant,
+}
+
+/// A software Ethernet bridge.
+///
+/// A bridge forwards frames between its devices, learning on which device every
+/// station lives from the source address of the frames it receives. Frames to
+/// unknown or group addresses are flooded to all the other devices.
+///
+/// An [Interface] can take part as one more bridge port, using the device returned
+/// by [local](#method.local):
+///
+/// ```no_run
+/// use tapip_rs::iface::{Config, Interface, SocketSet};
+/// use tapip_rs::phy::{Bridge, Loopback, Medium};
+/// use tapip_rs::time::Instant;
+/// use tapip_rs::wire::EthernetAddress;
+///
+/// let mut bridge = Bridge::new(vec![
+///     Loopback::new(Medium::Ethernet),
+///     Loopback::new(Medium::Ethernet),
+/// ]);
+/// let config = Config::new(EthernetAddress([0x02, 0, 0, 0, 0, 0x01]).into());
+/// let mut iface = Interface::new(config, &mut bridge.local(), Instant::now());
+/// let mut sockets = SocketSet::new(vec![]);
+///
+/// loop {
+///     iface.poll(Instant::now(), &mut bridge.local(), &mut sockets);
+/// }
+/// ```
+///
+/// Polling the local port also forwards the frames between the devices. Without a
+/// local port, call [poll](#method.poll) instead.
+///
+/// [Interface]: crate::iface::Interface
+#[derive(Debug)]
+pub struct Bridge<D: Device> {
+    devices: Vec<D>,
+    fdb: HashMap<EthernetAddress, Entry>,
+    aging_time: Duration,
+    /// Frames waiting to be received by the local port, if there is one.
+    local: Option<VecDeque<Vec<u8>>>,
+    /// The device to receive from first, so that a busy device can't starve the others.
+    next_device: usize,
+}
+
+impl<D: Device> Bridge<D> {
+    /// Default lifetime of a learned station (IEEE 802.1D § 7.9.2).
+    pub const DEFAULT_AGING_TIME: Duration = Duration::from_secs(300);
+
+    /// Create a bridge between the given devices.
+    ///
+    /// # Panics
+    /// This function panics if one of the devices is not an Ethernet device.
+    pub fn new(devices: Vec<D>) -> Bridge<D> {
+        for device in devices.iter() {
+            assert_eq!(
+                device.capabilities().medium,
+                Medium::Ethernet,
+                "only Ethernet devices can be bridged"
+            );
+        }
+
+        Bridge {
+            devices,
+            fdb: HashMap::new(),
+            aging_time: Self::DEFAULT_AGING_TIME,
+            local: None,
+            next_device: 0,
+        }
+    }
+
+    /// Return the bridged devices.
+    pub fn devices(&self) -> &[D] {
+        &self.devices
+    }
+
+    /// Return the bridged devices, mutably.
+    ///
+    /// It is inadvisable to directly read or write frames from the devices.
+    pub fn devices_mut(&mut self) -> &mut [D] {
+        &mut self.devices
+    }
+
+    /// Return the lifetime of a learned station.
+    pub fn aging_time(&self) -> Duration {
+        self.aging_time
+    }
+
+    /// Set the lifetime of a learned station.
+    pub fn set_aging_time(&mut self, aging_time: Duration) {
+        self.aging_time = aging_time;
+    }
+
+    /// Return the learned stations, with the index of their device, or `None`
+    /// for the local port.
+    pub fn stations(
+        &self,
+        timestamp: Instant,
+    ) -> impl Iterator<Item = (EthernetAddress, Option<usize>)> + '_ {
+        self.fdb
+            .iter()
+            .filter(move |(_, entry)| timestamp < entry.expires_at)
+            .map(|(addr, entry)| match entry.port {
+                PortId::Device(index) => (*addr, Some(index)),
+                PortId::Local => (*addr, None),
+            })
+    }
+
+    /// Forget all the learned stations.
+    pub fn flush(&mut self) {
+        self.fdb.clear()
+    }
+
+    /// Return the local port of the bridge.
+    ///
+    /// Once this is called, the bridge keeps the frames for the local port until
+    /// it is polled.
+    pub fn local(&mut self) -> LocalPort<'_, D> {
+        self.local.get_or_insert_with(VecDeque::new);
+        LocalPort { bridge: self }
+    }
+
+    /// Forward the frames received by the devices.
+    ///
+    /// Returns whether any frame was received.
+    pub fn poll(&mut self, timestamp: Instant) -> bool {
+        let mut received = false;
+        while self.poll_single(timestamp) {
+            received = true;
+        }
+        received
+    }
+
+    /// Forward one frame received by a device, returning whether there was one.
+    fn poll_single(&mut self, timestamp: Instant) -> bool {
+        for i in 0..self.devices.len() {
+            let index = (self.next_device + i) % self.devices.len();
+            let Some((rx_token, _)) = self.devices[index].receive(timestamp) else {
+                continue;
+            };
+            let frame = phy::RxToken::consume(rx_token, |frame| frame.to_vec());
+
+            self.next_device = (index + 1) % self.devices.len();
+            self.forward(PortId::Device(index), frame, timestamp);
+            return true;
+        }
+        false
+    }
+
+    fn forward(&mut self, src_port: PortId, frame: Vec<u8>, timestamp: Instant) {
+        let Ok(eth_frame) = EthernetFrame::new_checked(&frame[..]) else {
+            net_debug!("bridge: dropping malformed frame");
+            return;
+        };
+        let src_addr = eth_frame.src_addr();
+        let dst_addr = eth_frame.dst_addr();
+
+        if src_addr.is_unicast() {
+            self.learn(src_addr, src_port, timestamp);
+        }
+
+        // Frames to the bridge group addresses are never forwarded (IEEE 802.1D § 7.12.6).
+        if dst_addr.0[..5] == [0x01, 0x80, 0xc2, 0x00, 0x00] && dst_addr.0[5] <= 0x0f {
+            net_trace!(
+                "bridge: not forwarding frame to reserved address {}",
+                dst_addr
+            );
+            return;
+        }
+
+        let dst_port = match self.fdb.get(&dst_addr) {
+            Some(entry) if dst_addr.is_unicast() && timestamp < entry.expires_at => {
+                Some(entry.port)
+            }
+            _ => None,
+        };
+
+        match dst_port {
+            // The destination is on the same segment as the source.
+            Some(dst_port) if dst_port == src_port => (),
+            Some(dst_port) => self.send(dst_port, &frame, timestamp),
+            None => {
+                for index in 0..self.devices.len() {
+                    if src_port != PortId::Device(index) {
+                        self.send(PortId::Device(index), &frame, timestamp);
+                    }
+                }
+                if src_port != PortId::Local {
+                    self.send(PortId::Local, &frame, timestamp);
+                }
+            }
+        }
+    }
+
+    fn learn(&mut self, addr: EthernetAddress, port: PortId, timestamp: Instant) {
+        if !self.fdb.contains_key(&addr) && self.fdb.len() >= BRIDGE_FDB_COUNT {
+            self.fdb.retain(|_, entry| timestamp < entry.expires_at);
+        }
+        if !self.fdb.contains_key(&addr) && self.fdb.len() >= BRIDGE_FDB_COUNT {
+            let oldest = *self
+                .fdb
+                .iter()
+                .min_by_key(|(_, entry)| entry.expires_at)
+                .expect("empty bridge forwarding table")
+                .0;
+            self.fdb.remove(&oldest);
+            net_trace!("bridge: forwarding table full, evicted {}", oldest);
+        }
+
+        match self.fdb.get(&addr) {
+            Some(entry) if entry.port != port => {
+                net_trace!("bridge: station {} moved to {:?}", addr, port)
+            }
+            None => net_trace!("bridge: learned station {} on {:?}", addr, port),
+            _ => (),
+        }
+        self.fdb.insert(
+            addr,
+            Entry {
+                port,
+                expires_at: timestamp + self.aging_time,
+            },
+        );
+    }
+
+    fn send(&mut self, port: PortId, frame: &[u8], timestamp: Instant) {
+        match port {
+            PortId::Device(index) => {
+                let device = &mut self.devices[index];
+                if frame.len() > device.capabilities().max_transmission_unit {
+                    net_debug!("bridge: frame too large for device {}, dropping", index);
+                    return;
+                }
+                match device.transmit(timestamp) {
+                    Some(tx_token) => phy::TxToken::consume(tx_token, frame.len(), |buffer| {
+                        buffer.copy_from_slice(frame)
+                    }),
+                    None => net_debug!("bridge: device {} exhausted, dropping frame", index),
+                }
+            }
+            PortId::Local => match &mut self.local {
+                Some(queue) if queue.len() < BRIDGE_LOCAL_QUEUE_COUNT => {
+                    queue.push_back(frame.to_vec())
+                }
+                Some(_) => net_debug!("bridge: local queue full, dropping frame"),
+                None => (),
+            },
+        }
+    }
+}
+
+/// The local port of a [Bridge], through which an [Interface] takes part in it.
+///
+/// [Interface]: crate::iface::Interface
+#[derive(Debug)]
+pub struct LocalPort<'a, D: Device> {
+    bridge: &'a mut Bridge<D>,
+}
+
+impl<'a, D: Device> Device for LocalPort<'a, D> {
+    type RxToken<'b>
+        = RxToken
+    where
+        Self: 'b;
+    type TxToken<'b>
+        = TxToken<'b, D>
+    where
+        Self: 'b;
+
+    fn capabilities(&self) -> DeviceCapabilities {
+        DeviceCapabilities {
+            max_transmission_unit: self
+                .bridge
+                .devices
+                .iter()
+                .map(|device| device.capabilities().max_transmission_unit)
+                .min()
+                .unwrap_or(EthernetFrame::<&[u8]>::buffer_len(1500)),
+            medium: Medium::Ethernet,
+            ..DeviceCapabilities::default()
+        }
+    }
+
+    fn receive(&mut self, timestamp: Instant) -> Option<(Self::RxToken<'_>, Self::TxToken<'_>)> {
+        loop {
+            if let Some(buffer) = self
+                .bridge
+                .local
+                .as_mut()
+                .and_then(|queue| queue.pop_front())
+            {
+                let tx = TxToken {
+                    bridge: self.bridge,
+                    timestamp,
+                };
+                return Some((RxToken { buffer }, tx));
+            }
+
+            if !self.bridge.poll_single(timestamp) {
+                return None;
+            }
+        }
+    }
+
+    fn transmit(&mut self, timestamp: Instant) -> Option<Self::TxToken<'_>> {
+        Some(TxToken {
+            bridge: self.bridge,
+            timestamp,
+        })
+    }
+}
+
+#[doc(hidden)]
+pub struct RxToken {
+    buffer: Vec<u8>,
+}
+
+impl phy::RxToken for RxToken {
+    fn consume<R, F>(self, f: F) -> R
+    where
+        F: FnOnce(&[u8]) -> R,
+    {
+        f(&self.buffer)
+    }
+}
+
+#[doc(hidden)]
+pub struct TxToken<'a, D: Device> {
+    bridge: &'a mut Bridge<D>,
+    timestamp: Instant,
+}
+
+impl<'a, D: Device> phy::TxToken for TxToken<'a, D> {
+    fn consume<R, F>(self, len: usize, f: F) -> R
+    where
+        F: FnOnce(&mut [u8]) -> R,
+    {
+        let mut buffer = vec![0; len];
+        let result = f(&mut buffer);
+        self.bridge.forward(PortId::Local, buffer, self.timestamp);
+        result
+    }
+}
@@ -9,7 +9,10 @@ and implementations of it:
     [FaultInjector](struct.FaultInjector.html), to facilitate debugging;
   * _adapters_ [RawSocket](struct.RawSocket.html) and
     [TunTapInterface](struct.TunTapInterface.html), to transmit and receive frames
-    on the host OS.
+    on the host OS;
+  * the VLAN [Trunk](struct.Trunk.html), to split a device carrying tagged frames
+    into one device per VLAN;
+  * the [Bridge](struct.Bridge.html), to forward frames between devices.
 */
 //! # Examples
 //!
@@ -87,13 +90,17 @@ use crate::time::Instant;
 
 mod sys;
 
+mod bridge;
 mod loopback;
 mod tuntap_interface;
+mod vlan;
 
 pub use self::sys::wait;
 
+pub use self::bridge::{Bridge, LocalPort as BridgeLocalPort};
 pub use self::loopback::Loopback;
 pub use self::tuntap_interface::TunTapInterface;
+pub use self::vlan::{Port as TrunkPort, Trunk};
 
 /// Metadata associated to a packet.
 ///
@@ -165,6 +172,7 @@ pub struct ChecksumCapabilities {
     pub udp: Checksum,
     pub tcp: Checksum,
     pub icmpv4: Checksum,
+    pub icmpv6: Checksum,
 }
 
 impl ChecksumCapabilities {
@@ -176,6 +184,7 @@ impl ChecksumCapabilities {
             udp: Checksum::None,
             tcp: Checksum::None,
             icmpv4: Checksum::None,
+            icmpv6: Checksum::None,
         }
     }
 }
@@ -0,0 +1,305 @@
+use byteorder::{ByteOrder, NetworkEndian};
+use std::collections::{HashMap, VecDeque};
+
+use crate::config::VLAN_TRUNK_QUEUE_COUNT;
+use crate::phy::{self, Device, DeviceCapabilities};
+use crate::time::Instant;
+use crate::wire::{
+    EthernetFrame, EthernetProtocol, VlanPacket, VlanRepr, ETHERNET_HEADER_LEN, VLAN_HEADER_LEN,
+    VLAN_VID_PRIORITY_TAGGED, VLAN_VID_RESERVED,
+};
+
+/// The VLAN tags of a frame, outermost first.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+struct Tags {
+    /// 802.1ad service VLAN identifier.
+    service: Option<u16>,
+    /// 802.1Q customer VLAN identifier.
+    customer: Option<u16>,
+}
+
+impl Tags {
+    fn len(&self) -> usize {
+        (self.service.is_some() as usize + self.customer.is_some() as usize) * VLAN_HEADER_LEN
+    }
+
+    /// Split a received frame into its tags and the untagged frame.
+    ///
+    /// Priority-tagged frames belong to the native VLAN.
+    fn strip(frame: &[u8]) -> Option<(Tags, Vec<u8>)> {
+        let eth_frame = EthernetFrame::new_checked(frame).ok()?;
+
+        let mut tags = Tags::default();
+        let mut ethertype = eth_frame.ethertype();
+        let mut payload = eth_frame.payload();
+        if ethertype == EthernetProtocol::Dot1Ad {
+            let packet = VlanPacket::new_checked(payload).ok()?;
+            let repr = VlanRepr::parse(&packet).ok()?;
+            tags.service = Some(repr.vid);
+            ethertype = repr.ethertype;
+            payload = packet.payload();
+        }
+        if ethertype == EthernetProtocol::Dot1Q {
+            let packet = VlanPacket::new_checked(payload).ok()?;
+            let repr = VlanRepr::parse(&packet).ok()?;
+            if repr.vid != VLAN_VID_PRIORITY_TAGGED {
+                tags.customer = Some(repr.vid);
+            }
+            ethertype = repr.ethertype;
+            payload = packet.payload();
+        }
+
+        let mut untagged = vec![0; EthernetFrame::<&[u8]>::buffer_len(payload.len())];
+        let mut untagged_frame = EthernetFrame::new_unchecked(&mut untagged[..]);
+        untagged_frame.set_dst_addr(eth_frame.dst_addr());
+        untagged_frame.set_src_addr(eth_frame.src_addr());
+        untagged_frame.set_ethertype(ethertype);
+        untagged_frame.payload_mut().copy_from_slice(payload);
+        Some((tags, untagged))
+    }
+
+    /// Insert the tags into a frame that has `self.len()` spare octets in front of it.
+    fn insert(&self, buffer: &mut [u8]) {
+        let len = self.len();
+        let ethertype = EthernetFrame::new_unchecked(&buffer[len..]).ethertype();
+        buffer.copy_within(len..len + ETHERTYPE_OFFSET, 0);
+
+        let tags: Vec<(EthernetProtocol, u16)> = [
+            (EthernetProtocol::Dot1Ad, self.service),
+            (EthernetProtocol::Dot1Q, self.customer),
+        ]
+        .into_iter()
+        .filter_map(|(tpid, vid)| Some((tpid, vid?)))
+        .collect();
+
+        let mut offset = ETHERTYPE_OFFSET;
+        for (i, &(tpid, vid)) in tags.iter().enumerate() {
+            NetworkEndian::write_u16(&mut buffer[offset..offset + 2], tpid.into());
+            let repr = VlanRepr {
+                pcp: 0,
+                dei: false,
+                vid,
+                ethertype: tags.get(i + 1).map_or(ethertype, |&(tpid, _)| tpid),
+            };
+            repr.emit(&mut VlanPacket::new_unchecked(
+                &mut buffer[offset + 2..offset + 2 + VLAN_HEADER_LEN],
+            ));
+            offset += VLAN_HEADER_LEN;
+        }
+    }
+}
+
+/// Offset of the EtherType field, right after the addresses of an Ethernet frame.
+const ETHERTYPE_OFFSET: usize = ETHERNET_HEADER_LEN - 2;
+
+/// A VLAN trunk.
+///
+/// A trunk splits the traffic of a device carrying tagged frames into ports, one
+/// per VLAN, each of which is a [Device] carrying untagged frames. An [Interface]
+/// is created for every port, with its own addresses and neighbor cache, and all
+/// of them are polled with the port of their VLAN:
+///
+/// ```no_run
+/// use tapip_rs::iface::{Config, Interface, SocketSet};
+/// use tapip_rs::phy::{Loopback, Medium, Trunk};
+/// use tapip_rs::time::Instant;
+/// use tapip_rs::wire::EthernetAddress;
+///
+/// let mut trunk = Trunk::new(Loopback::new(Medium::Ethernet));
+/// let config = Config::new(EthernetAddress([0x02, 0, 0, 0, 0, 0x0a]).into());
+/// let mut iface_10 = Interface::new(config, &mut trunk.port(10), Instant::now());
+/// let config = Config::new(EthernetAddress([0x02, 0, 0, 0, 0, 0x14]).into());
+/// let mut iface_20 = Interface::new(config, &mut trunk.port(20), Instant::now());
+/// let mut sockets_10 = SocketSet::new(vec![]);
+/// let mut sockets_20 = SocketSet::new(vec![]);
+///
+/// loop {
+///     let timestamp = Instant::now();
+///     iface_10.poll(timestamp, &mut trunk.port(10), &mut sockets_10);
+///     iface_20.poll(timestamp, &mut trunk.port(20), &mut sockets_20);
+/// }
+/// ```
+///
+/// Frames received by a port on behalf of another one are queued until the other
+/// port is polled. Frames of VLANs that no port was ever created for are dropped.
+///
+/// [Interface]: crate::iface::Interface
+#[derive(Debug)]
+pub struct Trunk<D: Device> {
+    inner: D,
+    queues: HashMap<Tags, VecDeque<Vec<u8>>>,
+}
+
+impl<D: Device> Trunk<D> {
+    /// Create a trunk on top of the given device.
+    pub fn new(inner: D) -> Trunk<D> {
+        Trunk {
+            inner,
+            queues: HashMap::new(),
+        }
+    }
+
+    /// Get a reference to the underlying device.
+    pub fn get_ref(&self) -> &D {
+        &self.inner
+    }
+
+    /// Get a mutable reference to the underlying device.
+    ///
+    /// It is inadvisable to directly read or write frames from the underlying device.
+    pub fn get_mut(&mut self) -> &mut D {
+        &mut self.inner
+    }
+
+    /// Return the underlying device, consuming the trunk.
+    pub fn into_inner(self) -> D {
+        self.inner
+    }
+
+    /// Return the port of the native VLAN, carrying untagged and priority-tagged frames.
+    pub fn native(&mut self) -> Port<'_, D> {
+        self.port_for(Tags::default())
+    }
+
+    /// Return the port of an 802.1Q VLAN.
+    ///
+    /// # Panics
+    /// This function panics if `vid` is not a valid VLAN identifier, from 1 to 4094.
+    pub fn port(&mut self, vid: u16) -> Port<'_, D> {
+        Self::check_vid(vid);
+        self.port_for(Tags {
+            service: None,
+            customer: Some(vid),
+        })
+    }
+
+    /// Return the port of a customer VLAN inside an 802.1ad service VLAN.
+    ///
+    /// # Panics
+    /// This function panics if either identifier is not a valid VLAN identifier,
+    /// from 1 to 4094.
+    pub fn qinq_port(&mut self, service_vid: u16, vid: u16) -> Port<'_, D> {
+        Self::check_vid(service_vid);
+        Self::check_vid(vid);
+        self.port_for(Tags {
+            service: Some(service_vid),
+            customer: Some(vid),
+        })
+    }
+
+    fn check_vid(vid: u16) {
+        assert!(
+            vid != VLAN_VID_PRIORITY_TAGGED && vid < VLAN_VID_RESERVED,
+            "invalid VLAN identifier {vid}"
+        );
+    }
+
+    fn port_for(&mut self, tags: Tags) -> Port<'_, D> {
+        self.queues.entry(tags).or_default();
+        Port { trunk: self, tags }
+    }
+}
+
+/// A port of a [Trunk], carrying the untagged frames of one VLAN.
+#[derive(Debug)]
+pub struct Port<'a, D: Device> {
+    trunk: &'a mut Trunk<D>,
+    tags: Tags,
+}
+
+impl<'a, D: Device> Device for Port<'a, D> {
+    type RxToken<'b>
+        = RxToken
+    where
+        Self: 'b;
+    type TxToken<'b>
+        = TxToken<'b, D>
+    where
+        Self: 'b;
+
+    fn capabilities(&self) -> DeviceCapabilities {
+        let mut caps = self.trunk.inner.capabilities();
+        caps.max_transmission_unit -= self.tags.len();
+        caps
+    }
+
+    fn receive(&mut self, timestamp: Instant) -> Option<(Self::RxToken<'_>, Self::TxToken<'_>)> {
+        loop {
+            if let Some(buffer) = self
+                .trunk
+                .queues
+                .get_mut(&self.tags)
+                .and_then(|queue| queue.pop_front())
+            {
+                let tx = TxToken {
+                    inner: &mut self.trunk.inner,
+                    tags: self.tags,
+                    timestamp,
+                };
+                return Some((RxToken { buffer }, tx));
+            }
+
+            let (rx_token, _) = self.trunk.inner.receive(timestamp)?;
+            let Some((tags, buffer)) = phy::RxToken::consume(rx_token, Tags::strip) else {
+                net_debug!("vlan: dropping malformed frame");
+                continue;
+            };
+            match self.trunk.queues.get_mut(&tags) {
+                Some(queue) if queue.len() < VLAN_TRUNK_QUEUE_COUNT => queue.push_back(buffer),
+                Some(_) => net_debug!("vlan: queue full, dropping frame"),
+                None => net_trace!("vlan: no port for {:?}, dropping frame", tags),
+            }
+        }
+    }
+
+    fn transmit(&mut self, timestamp: Instant) -> Option<Self::TxToken<'_>> {
+        Some(TxToken {
+            inner: &mut self.trunk.inner,
+            tags: self.tags,
+            timestamp,
+        })
+    }
+}
+
+#[doc(hidden)]
+pub struct RxToken {
+    buffer: Vec<u8>,
+}
+
+impl phy::RxToken for RxToken {
+    fn consume<R, F>(self, f: F) -> R
+    where
+        F: FnOnce(&[u8]) -> R,
+    {
+        f(&self.buffer)
+    }
+}
+
+#[doc(hidden)]
+pub struct TxToken<'a, D: Device> {
+    inner: &'a mut D,
+    tags: Tags,
+    timestamp: Instant,
+}
+
+impl<'a, D: Device> phy::TxToken for TxToken<'a, D> {
+    fn consume<R, F>(self, len: usize, f: F) -> R
+    where
+        F: FnOnce(&mut [u8]) -> R,
+    {
+        let tags_len = self.tags.len();
+        match self.inner.transmit(self.timestamp) {
+            Some(token) => token.consume(len + tags_len, |buffer| {
+                let result = f(&mut buffer[tags_len..]);
+                self.tags.insert(buffer);
+                result
+            }),
+            None => {
+                // The frame still has to be built for the caller to get its result.
+                net_debug!("vlan: device exhausted, dropping frame");
+                let mut buffer = vec![0; len];
+                f(&mut buffer)
+            }
+        }
+    }
+}
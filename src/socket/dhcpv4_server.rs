@@ -0,0 +1,591 @@
+use std::collections::VecDeque;
+
+use crate::iface::Context;
+use crate::time::{Duration, Instant};
+use crate::wire::{
+    DhcpMessageType, DhcpPacket, DhcpRepr, EthernetAddress, IpProtocol, Ipv4Address,
+    Ipv4AddressExt, Ipv4Cidr, Ipv4Repr, UdpRepr, DHCP_CLIENT_PORT, DHCP_MAX_DNS_SERVER_COUNT,
+    DHCP_SERVER_PORT,
+};
+
+use super::PollAt;
+
+const DEFAULT_LEASE_DURATION: Duration = Duration::from_secs(3600);
+
+/// How long an offered address is held for a client before it is returned to the pool.
+const OFFER_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Server configuration.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct Config {
+    /// Address of the server on the served subnet. It is used as the server
+    /// identifier, and its prefix determines the subnet mask handed to clients.
+    pub address: Ipv4Cidr,
+    /// First address of the pool (inclusive).
+    pub pool_start: Ipv4Address,
+    /// Last address of the pool (inclusive).
+    pub pool_end: Ipv4Address,
+    /// Router address, also known as default gateway.
+    pub router: Option<Ipv4Address>,
+    /// DNS servers
+    pub dns_servers: Vec<Ipv4Address>,
+    /// Duration of the leases handed out.
+    pub lease_duration: Duration,
+}
+
+impl Config {
+    /// Create a configuration serving the given pool, with no router, no DNS servers
+    /// and the default lease duration.
+    pub fn new(address: Ipv4Cidr, pool_start: Ipv4Address, pool_end: Ipv4Address) -> Config {
+        Config {
+            address,
+            pool_start,
+            pool_end,
+            router: None,
+            dns_servers: Vec::new(),
+            lease_duration: DEFAULT_LEASE_DURATION,
+        }
+    }
+
+    fn pool_contains(&self, addr: Ipv4Address) -> bool {
+        (u32::from(self.pool_start)..=u32::from(self.pool_end)).contains(&u32::from(addr))
+    }
+}
+
+/// Error returned by [`Socket::add_reservation`].
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ReservationError {
+    /// The address is not a unicast address of the served subnet.
+    InvalidAddress,
+    /// The address is already reserved for another hardware address.
+    AddressInUse,
+}
+
+impl core::fmt::Display for ReservationError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            ReservationError::InvalidAddress => write!(f, "Invalid address"),
+            ReservationError::AddressInUse => write!(f, "Address in use"),
+        }
+    }
+}
+
+impl std::error::Error for ReservationError {}
+
+/// State of a lease.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum LeaseState {
+    /// The address was offered and the server is waiting for a REQUEST.
+    Offered,
+    /// The address was acknowledged and is in use by the client.
+    Bound,
+    /// The client reported the address as already in use by some other host.
+    /// It is kept out of the pool until the lease expires.
+    Declined,
+}
+
+/// An entry of the lease table.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Lease {
+    pub hardware_addr: EthernetAddress,
+    pub address: Ipv4Address,
+    pub state: LeaseState,
+    pub expires_at: Instant,
+}
+
+/// A reply waiting to be sent by `dispatch`.
+#[derive(Debug)]
+struct Reply {
+    message_type: DhcpMessageType,
+    transaction_id: u32,
+    client_hardware_address: EthernetAddress,
+    client_ip: Ipv4Address,
+    your_ip: Ipv4Address,
+    broadcast: bool,
+    dst_addr: Ipv4Address,
+}
+
+/// DHCP server socket.
+///
+/// The socket answers DISCOVER, REQUEST, DECLINE, RELEASE and INFORM messages
+/// from clients on the directly attached subnet, handing out addresses from
+/// the configured pool. Addresses reserved with [`Socket::add_reservation`] are
+/// only ever given to the matching hardware address.
+///
+/// Replies to clients without an address are always broadcast, since the
+/// stack cannot deliver to a hardware address that doesn't answer ARP yet.
+#[derive(Debug)]
+pub struct Socket {
+    config: Config,
+    leases: Vec<Lease>,
+    reservations: Vec<(EthernetAddress, Ipv4Address)>,
+    replies: VecDeque<Reply>,
+
+    /// Server port config
+    pub(crate) server_port: u16,
+
+    /// Client port config
+    pub(crate) client_port: u16,
+}
+
+impl Socket {
+    /// Create a DHCPv4 server socket.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if the pool is empty or not contained in the subnet
+    /// of `config.address`.
+    pub fn new(config: Config) -> Socket {
+        let subnet = config.address.network();
+        if u32::from(config.pool_start) > u32::from(config.pool_end)
+            || !subnet.contains_addr(&config.pool_start)
+            || !subnet.contains_addr(&config.pool_end)
+        {
+            panic!("the DHCP pool must be a non-empty range inside the served subnet")
+        }
+
+        Socket {
+            config,
+            leases: Vec::new(),
+            reservations: Vec::new(),
+            replies: VecDeque::new(),
+            server_port: DHCP_SERVER_PORT,
+            client_port: DHCP_CLIENT_PORT,
+        }
+    }
+
+    /// Return the server configuration.
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Reserve `address` for the client with hardware address `hardware_addr`.
+    ///
+    /// The address does not need to be part of the pool, but must belong to
+    /// the served subnet. Replaces any previous reservation for `hardware_addr`.
+    pub fn add_reservation(
+        &mut self,
+        hardware_addr: EthernetAddress,
+        address: Ipv4Address,
+    ) -> Result<(), ReservationError> {
+        let subnet = self.config.address.network();
+        if !address.x_is_unicast()
+            || !subnet.contains_addr(&address)
+            || address == self.config.address.address()
+            || Some(address) == subnet.broadcast()
+        {
+            return Err(ReservationError::InvalidAddress);
+        }
+        if self
+            .reservations
+            .iter()
+            .any(|&(mac, addr)| addr == address && mac != hardware_addr)
+        {
+            return Err(ReservationError::AddressInUse);
+        }
+
+        self.remove_reservation(hardware_addr);
+        self.reservations.push((hardware_addr, address));
+        // Any lease that conflicts with the reservation is revoked.
+        self.leases.retain(|lease| {
+            (lease.address == address) == (lease.hardware_addr == hardware_addr)
+                || lease.state == LeaseState::Declined
+        });
+        Ok(())
+    }
+
+    /// Remove the reservation for `hardware_addr`, returning the reserved address.
+    pub fn remove_reservation(&mut self, hardware_addr: EthernetAddress) -> Option<Ipv4Address> {
+        let i = self
+            .reservations
+            .iter()
+            .position(|&(mac, _)| mac == hardware_addr)?;
+        Some(self.reservations.remove(i).1)
+    }
+
+    /// Iterate over the current reservations.
+    pub fn reservations(&self) -> impl Iterator<Item = (EthernetAddress, Ipv4Address)> + '_ {
+        self.reservations.iter().copied()
+    }
+
+    /// Iterate over the lease table.
+    ///
+    /// Expired leases are removed the next time the socket is polled.
+    pub fn leases(&self) -> impl Iterator<Item = &Lease> {
+        self.leases.iter()
+    }
+
+    /// Return the active lease of the client with hardware address `hardware_addr`.
+    pub fn lease(&self, hardware_addr: EthernetAddress) -> Option<&Lease> {
+        self.leases.iter().find(|lease| {
+            lease.hardware_addr == hardware_addr && lease.state != LeaseState::Declined
+        })
+    }
+
+    /// Remove the lease of the client with hardware address `hardware_addr`,
+    /// returning its address to the pool.
+    pub fn revoke_lease(&mut self, hardware_addr: EthernetAddress) -> Option<Lease> {
+        let i = self.leases.iter().position(|lease| {
+            lease.hardware_addr == hardware_addr && lease.state != LeaseState::Declined
+        })?;
+        Some(self.leases.remove(i))
+    }
+
+    /// Set the server/client port
+    ///
+    /// Allows you to specify the ports used by DHCP.
+    pub fn set_ports(&mut self, server_port: u16, client_port: u16) {
+        self.server_port = server_port;
+        self.client_port = client_port;
+    }
+
+    pub(crate) fn poll_at(&self, _cx: &mut Context) -> PollAt {
+        if !self.replies.is_empty() {
+            return PollAt::Now;
+        }
+
+        self.leases
+            .iter()
+            .map(|lease| PollAt::Time(lease.expires_at))
+            .min()
+            .unwrap_or(PollAt::Ingress)
+    }
+
+    fn expire_leases(&mut self, now: Instant) {
+        self.leases.retain(|lease| {
+            if lease.expires_at <= now {
+                net_debug!(
+                    "DHCP server lease of {} for {} expired",
+                    lease.address,
+                    lease.hardware_addr
+                );
+                false
+            } else {
+                true
+            }
+        });
+    }
+
+    fn reserved_address(&self, hardware_addr: EthernetAddress) -> Option<Ipv4Address> {
+        self.reservations
+            .iter()
+            .find(|&&(mac, _)| mac == hardware_addr)
+            .map(|&(_, addr)| addr)
+    }
+
+    /// Whether `address` can be handed out to `hardware_addr`.
+    fn is_available(&self, hardware_addr: EthernetAddress, address: Ipv4Address) -> bool {
+        if let Some(reserved) = self.reserved_address(hardware_addr) {
+            return address == reserved;
+        }
+
+        self.config.pool_contains(address)
+            && address != self.config.address.address()
+            && !self
+                .reservations
+                .iter()
+                .any(|&(_, reserved)| reserved == address)
+            && !self
+                .leases
+                .iter()
+                .any(|lease| lease.address == address && lease.hardware_addr != hardware_addr)
+            && !self
+                .leases
+                .iter()
+                .any(|lease| lease.address == address && lease.state == LeaseState::Declined)
+    }
+
+    /// Pick an address for a DISCOVER from `hardware_addr`.
+    fn select_address(
+        &self,
+        hardware_addr: EthernetAddress,
+        requested_ip: Option<Ipv4Address>,
+    ) -> Option<Ipv4Address> {
+        if let Some(reserved) = self.reserved_address(hardware_addr) {
+            return Some(reserved);
+        }
+        if let Some(lease) = self.lease(hardware_addr) {
+            return Some(lease.address);
+        }
+        if let Some(requested_ip) = requested_ip {
+            if self.is_available(hardware_addr, requested_ip) {
+                return Some(requested_ip);
+            }
+        }
+
+        (u32::from(self.config.pool_start)..=u32::from(self.config.pool_end))
+            .map(Ipv4Address::from)
+            .find(|&addr| self.is_available(hardware_addr, addr))
+    }
+
+    fn set_lease(
+        &mut self,
+        hardware_addr: EthernetAddress,
+        address: Ipv4Address,
+        state: LeaseState,
+        expires_at: Instant,
+    ) {
+        self.leases.retain(|lease| {
+            lease.state == LeaseState::Declined || lease.hardware_addr != hardware_addr
+        });
+        self.leases.push(Lease {
+            hardware_addr,
+            address,
+            state,
+            expires_at,
+        });
+    }
+
+    pub(crate) fn accepts(&self, ip_repr: &Ipv4Repr, udp_repr: &UdpRepr) -> bool {
+        udp_repr.src_port == self.client_port
+            && udp_repr.dst_port == self.server_port
+            && (ip_repr.dst_addr.is_broadcast()
+                || ip_repr.dst_addr == self.config.address.address())
+    }
+
+    pub(crate) fn process(
+        &mut self,
+        cx: &mut Context,
+        ip_repr: &Ipv4Repr,
+        udp_repr: &UdpRepr,
+        payload: &[u8],
+    ) {
+        debug_assert!(self.accepts(ip_repr, udp_repr));
+
+        let src_ip = ip_repr.src_addr;
+
+        let dhcp_packet = match DhcpPacket::new_checked(payload) {
+            Ok(dhcp_packet) => dhcp_packet,
+            Err(e) => {
+                net_debug!("DHCP server invalid pkt from {}: {:?}", src_ip, e);
+                return;
+            }
+        };
+        let dhcp_repr = match DhcpRepr::parse(&dhcp_packet) {
+            Ok(dhcp_repr) => dhcp_repr,
+            Err(e) => {
+                net_debug!("DHCP server error parsing pkt from {}: {:?}", src_ip, e);
+                return;
+            }
+        };
+
+        net_debug!(
+            "DHCP server recv {:?} from {}: {:?}",
+            dhcp_repr.message_type,
+            src_ip,
+            dhcp_repr
+        );
+
+        let now = cx.now();
+        self.expire_leases(now);
+
+        let our_addr = self.config.address.address();
+        let hardware_addr = dhcp_repr.client_hardware_address;
+        let mut reply = Reply {
+            message_type: DhcpMessageType::Ack,
+            transaction_id: dhcp_repr.transaction_id,
+            client_hardware_address: hardware_addr,
+            client_ip: Ipv4Address::UNSPECIFIED,
+            your_ip: Ipv4Address::UNSPECIFIED,
+            broadcast: dhcp_repr.broadcast,
+            dst_addr: Ipv4Address::BROADCAST,
+        };
+
+        match dhcp_repr.message_type {
+            DhcpMessageType::Discover => {
+                let Some(address) = self.select_address(hardware_addr, dhcp_repr.requested_ip)
+                else {
+                    net_debug!("DHCP server pool exhausted, ignoring DISCOVER");
+                    return;
+                };
+
+                // A client that is already bound keeps its lease while it rediscovers.
+                match self.lease(hardware_addr) {
+                    Some(lease) if lease.address == address && lease.state == LeaseState::Bound => {
+                    }
+                    _ => self.set_lease(
+                        hardware_addr,
+                        address,
+                        LeaseState::Offered,
+                        now + OFFER_TIMEOUT,
+                    ),
+                }
+
+                reply.message_type = DhcpMessageType::Offer;
+                reply.your_ip = address;
+            }
+            DhcpMessageType::Request => {
+                if let Some(server_identifier) = dhcp_repr.server_identifier {
+                    if server_identifier != our_addr {
+                        // The client accepted an offer from another server.
+                        if let Some(lease) = self.lease(hardware_addr) {
+                            if lease.state == LeaseState::Offered {
+                                self.revoke_lease(hardware_addr);
+                            }
+                        }
+                        return;
+                    }
+                }
+
+                let requested_ip = match dhcp_repr.requested_ip {
+                    Some(requested_ip) => requested_ip,
+                    None if !dhcp_repr.client_ip.is_unspecified() => dhcp_repr.client_ip,
+                    None => {
+                        net_debug!("DHCP server ignoring REQUEST without an address");
+                        return;
+                    }
+                };
+
+                if !self.is_available(hardware_addr, requested_ip) {
+                    net_debug!(
+                        "DHCP server NAK {} for {}: not available",
+                        requested_ip,
+                        hardware_addr
+                    );
+                    reply.message_type = DhcpMessageType::Nak;
+                    reply.broadcast = true;
+                } else {
+                    self.set_lease(
+                        hardware_addr,
+                        requested_ip,
+                        LeaseState::Bound,
+                        now + self.config.lease_duration,
+                    );
+
+                    reply.message_type = DhcpMessageType::Ack;
+                    reply.your_ip = requested_ip;
+                    reply.client_ip = dhcp_repr.client_ip;
+                    // Renewing clients can be answered directly.
+                    if !dhcp_repr.client_ip.is_unspecified() && !dhcp_repr.broadcast {
+                        reply.dst_addr = dhcp_repr.client_ip;
+                    }
+                }
+            }
+            DhcpMessageType::Decline => {
+                if dhcp_repr.server_identifier != Some(our_addr) {
+                    return;
+                }
+                let Some(address) = dhcp_repr.requested_ip else {
+                    return;
+                };
+                net_debug!("DHCP server {} declined {}", hardware_addr, address);
+                self.leases.retain(|lease| {
+                    !(lease.hardware_addr == hardware_addr && lease.address == address)
+                });
+                self.leases.push(Lease {
+                    hardware_addr,
+                    address,
+                    state: LeaseState::Declined,
+                    expires_at: now + self.config.lease_duration,
+                });
+                return;
+            }
+            DhcpMessageType::Release => {
+                if dhcp_repr.server_identifier != Some(our_addr) {
+                    return;
+                }
+                if self
+                    .lease(hardware_addr)
+                    .is_some_and(|lease| lease.address == dhcp_repr.client_ip)
+                {
+                    net_debug!(
+                        "DHCP server {} released {}",
+                        hardware_addr,
+                        dhcp_repr.client_ip
+                    );
+                    self.revoke_lease(hardware_addr);
+                }
+                return;
+            }
+            DhcpMessageType::Inform => {
+                if dhcp_repr.client_ip.is_unspecified() {
+                    return;
+                }
+                reply.message_type = DhcpMessageType::Ack;
+                reply.client_ip = dhcp_repr.client_ip;
+                reply.dst_addr = dhcp_repr.client_ip;
+            }
+            _ => {
+                net_debug!(
+                    "DHCP server ignoring {:?}: unexpected message type",
+                    dhcp_repr.message_type
+                );
+                return;
+            }
+        }
+
+        self.replies.push_back(reply);
+    }
+
+    pub(crate) fn dispatch<F, E>(&mut self, cx: &mut Context, emit: F) -> Result<(), E>
+    where
+        F: FnOnce(&mut Context, (Ipv4Repr, UdpRepr, DhcpRepr)) -> Result<(), E>,
+    {
+        self.expire_leases(cx.now());
+
+        // Replies are sent at most once: if the destination can't be reached
+        // the client will retransmit its request, and keeping the reply around
+        // would hold back the replies queued behind it.
+        let Some(reply) = self.replies.pop_front() else {
+            return Ok(());
+        };
+
+        let our_addr = self.config.address.address();
+        let offers_config = matches!(reply.message_type, DhcpMessageType::Offer)
+            || matches!(reply.message_type, DhcpMessageType::Ack);
+
+        let dhcp_repr = DhcpRepr {
+            message_type: reply.message_type,
+            transaction_id: reply.transaction_id,
+            secs: 0,
+            client_hardware_address: reply.client_hardware_address,
+            client_ip: reply.client_ip,
+            your_ip: reply.your_ip,
+            server_ip: Ipv4Address::UNSPECIFIED,
+            router: self.config.router.filter(|_| offers_config),
+            subnet_mask: offers_config.then(|| self.config.address.netmask()),
+            relay_agent_ip: Ipv4Address::UNSPECIFIED,
+            broadcast: reply.broadcast,
+            requested_ip: None,
+            client_identifier: None,
+            server_identifier: Some(our_addr),
+            parameter_request_list: None,
+            max_size: None,
+            // INFORM replies must not carry a lease time.
+            lease_duration: (offers_config && !reply.your_ip.is_unspecified())
+                .then(|| self.config.lease_duration.secs() as u32),
+            renew_duration: None,
+            rebind_duration: None,
+            dns_servers: (offers_config && !self.config.dns_servers.is_empty()).then(|| {
+                self.config
+                    .dns_servers
+                    .iter()
+                    .copied()
+                    .take(DHCP_MAX_DNS_SERVER_COUNT)
+                    .collect()
+            }),
+            additional_options: &[],
+        };
+
+        let udp_repr = UdpRepr {
+            src_port: self.server_port,
+            dst_port: self.client_port,
+        };
+
+        let ipv4_repr = Ipv4Repr {
+            src_addr: our_addr,
+            dst_addr: reply.dst_addr,
+            next_header: IpProtocol::Udp,
+            payload_len: udp_repr.header_len() + dhcp_repr.buffer_len(),
+            hop_limit: 64,
+        };
+
+        net_debug!(
+            "DHCP server send {:?} to {}: {:?}",
+            dhcp_repr.message_type,
+            ipv4_repr.dst_addr,
+            dhcp_repr
+        );
+        emit(cx, (ipv4_repr, udp_repr, dhcp_repr))
+    }
+}
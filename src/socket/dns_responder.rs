@@ -0,0 +1,408 @@
+use std::collections::VecDeque;
+
+use crate::config::DNS_MAX_NAME_SIZE;
+use crate::iface::Context;
+use crate::socket::PollAt;
+use crate::time::{Duration, Instant};
+use crate::wire::dns::{Flags, Opcode, Packet, Question, Rcode, Record, RecordData, Type};
+use crate::wire::{self, IpAddress, IpEndpoint, IpProtocol, IpRepr, Ipv4Address, UdpRepr};
+
+/// Unicast DNS port.
+pub const DNS_PORT: u16 = 53;
+/// Multicast DNS port (RFC 6762).
+pub const MDNS_PORT: u16 = 5353;
+/// Multicast DNS IPv4 group (RFC 6762).
+pub const MDNS_IPV4_ADDR: Ipv4Address = Ipv4Address::new(224, 0, 0, 251);
+
+const DEFAULT_TTL: u32 = 120;
+
+/// Maximum TTL of answers to "legacy unicast" mDNS queries (RFC 6762 § 6.7).
+const LEGACY_UNICAST_MAX_TTL: u32 = 10;
+
+/// Maximum size of a unicast DNS response over UDP (RFC 1035 § 4.2.1).
+const MAX_UNICAST_RESPONSE_SIZE: usize = 512;
+
+/// Maximum size of a multicast DNS response (RFC 6762 § 17).
+const MAX_MDNS_RESPONSE_SIZE: usize = 9000;
+
+/// How long a response may wait for the neighbor to be resolved before being dropped.
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// The `ANY` query type.
+const TYPE_ANY: u16 = 255;
+
+/// Error returned by [`Socket::add_record`]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ZoneError {
+    InvalidName,
+    NameTooLong,
+}
+
+impl core::fmt::Display for ZoneError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            ZoneError::InvalidName => write!(f, "Invalid name"),
+            ZoneError::NameTooLong => write!(f, "Name too long"),
+        }
+    }
+}
+
+impl std::error::Error for ZoneError {}
+
+/// An A record of the zone.
+#[derive(Debug)]
+struct Entry {
+    /// Lowercased name, without trailing dot.
+    name: String,
+    /// Wire-format name.
+    raw_name: Vec<u8>,
+    address: Ipv4Address,
+}
+
+impl Entry {
+    fn is_local(&self) -> bool {
+        self.name.rsplit('.').next() == Some("local")
+    }
+
+    fn matches<'a>(&self, mut name: impl Iterator<Item = wire::Result<&'a [u8]>>) -> bool {
+        let mut labels = self.name.split('.');
+        loop {
+            match (labels.next(), name.next()) {
+                (None, None) => return true,
+                (Some(a), Some(Ok(b))) if a.as_bytes().eq_ignore_ascii_case(b) => {}
+                _ => return false,
+            }
+        }
+    }
+}
+
+/// A response waiting to be sent by `dispatch`.
+#[derive(Debug)]
+struct Response {
+    endpoint: IpEndpoint,
+    src_port: u16,
+    hop_limit: u8,
+    payload: Vec<u8>,
+    expires_at: Instant,
+}
+
+/// A DNS responder socket.
+///
+/// The socket answers A queries for the names of its zone, both as a unicast
+/// DNS server on port 53 and as a multicast DNS responder for `.local` names.
+///
+/// Multicast DNS queries are sent to [`MDNS_IPV4_ADDR`], so the interface must
+/// have joined that group with `Interface::join_multicast_group` for them to
+/// be received.
+#[derive(Debug)]
+pub struct Socket {
+    zone: Vec<Entry>,
+    ttl: u32,
+    unicast: bool,
+    mdns: bool,
+    responses: VecDeque<Response>,
+}
+
+impl Socket {
+    /// Create a DNS responder socket with an empty zone.
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> Socket {
+        Socket {
+            zone: Vec::new(),
+            ttl: DEFAULT_TTL,
+            unicast: true,
+            mdns: true,
+            responses: VecDeque::new(),
+        }
+    }
+
+    /// Add an A record to the zone.
+    ///
+    /// `name` is specified in human-friendly format, such as `"printer.local"`,
+    /// and is matched case-insensitively. A name may have several addresses.
+    pub fn add_record(&mut self, name: &str, address: Ipv4Address) -> Result<(), ZoneError> {
+        let name = name.strip_suffix('.').unwrap_or(name).to_ascii_lowercase();
+
+        let mut raw_name = Vec::new();
+        for label in name.split('.') {
+            if label.is_empty() || label.len() > 63 {
+                return Err(ZoneError::InvalidName);
+            }
+            raw_name.push(label.len() as u8);
+            raw_name.extend_from_slice(label.as_bytes());
+        }
+        raw_name.push(0x00);
+
+        if raw_name.len() > DNS_MAX_NAME_SIZE {
+            return Err(ZoneError::NameTooLong);
+        }
+
+        if !self
+            .zone
+            .iter()
+            .any(|entry| entry.name == name && entry.address == address)
+        {
+            self.zone.push(Entry {
+                name,
+                raw_name,
+                address,
+            });
+        }
+        Ok(())
+    }
+
+    /// Remove all the records of `name` from the zone, returning how many were removed.
+    pub fn remove_records(&mut self, name: &str) -> usize {
+        let name = name.strip_suffix('.').unwrap_or(name).to_ascii_lowercase();
+        let len = self.zone.len();
+        self.zone.retain(|entry| entry.name != name);
+        len - self.zone.len()
+    }
+
+    /// Remove all records from the zone.
+    pub fn clear_zone(&mut self) {
+        self.zone.clear();
+    }
+
+    /// Iterate over the records of the zone.
+    pub fn records(&self) -> impl Iterator<Item = (&str, Ipv4Address)> {
+        self.zone
+            .iter()
+            .map(|entry| (entry.name.as_str(), entry.address))
+    }
+
+    /// Return the TTL of the answers, in seconds.
+    pub fn ttl(&self) -> u32 {
+        self.ttl
+    }
+
+    /// Set the TTL of the answers, in seconds.
+    pub fn set_ttl(&mut self, ttl: u32) {
+        self.ttl = ttl;
+    }
+
+    /// Return whether unicast DNS queries on port 53 are answered.
+    pub fn unicast_enabled(&self) -> bool {
+        self.unicast
+    }
+
+    /// Set whether unicast DNS queries on port 53 are answered.
+    pub fn set_unicast_enabled(&mut self, enabled: bool) {
+        self.unicast = enabled;
+    }
+
+    /// Return whether multicast DNS queries on port 5353 are answered.
+    pub fn mdns_enabled(&self) -> bool {
+        self.mdns
+    }
+
+    /// Set whether multicast DNS queries on port 5353 are answered.
+    pub fn set_mdns_enabled(&mut self, enabled: bool) {
+        self.mdns = enabled;
+    }
+
+    pub(crate) fn accepts(&self, ip_repr: &IpRepr, udp_repr: &UdpRepr) -> bool {
+        if !matches!(ip_repr, IpRepr::Ipv4(_)) {
+            return false;
+        }
+
+        match udp_repr.dst_port {
+            DNS_PORT => self.unicast && !ip_repr.dst_addr().is_multicast(),
+            MDNS_PORT => self.mdns,
+            _ => false,
+        }
+    }
+
+    pub(crate) fn process(
+        &mut self,
+        cx: &mut Context,
+        ip_repr: &IpRepr,
+        udp_repr: &UdpRepr,
+        payload: &[u8],
+    ) {
+        debug_assert!(self.accepts(ip_repr, udp_repr));
+
+        let p = match Packet::new_checked(payload) {
+            Ok(x) => x,
+            Err(_) => {
+                net_trace!("dns query malformed");
+                return;
+            }
+        };
+        if p.flags().contains(Flags::RESPONSE) {
+            return;
+        }
+        if p.opcode() != Opcode::Query {
+            net_trace!("unwanted opcode {:?}", p.opcode());
+            return;
+        }
+
+        let mdns = udp_repr.dst_port == MDNS_PORT;
+        // Queries not sent from port 5353 are "legacy unicast" queries, answered
+        // like unicast DNS (RFC 6762 § 6.7).
+        let multicast_response = mdns && udp_repr.src_port == MDNS_PORT;
+
+        // (offset of the question name, index of the zone entry)
+        let mut answers = Vec::new();
+        let mut name_found = false;
+
+        let mut rest = p.payload();
+        for _ in 0..p.question_count() {
+            let name_offset = payload.len() - rest.len();
+            let (rest2, question) = match Question::parse(rest) {
+                Ok(x) => x,
+                Err(_) => {
+                    net_trace!("dns question malformed");
+                    return;
+                }
+            };
+            rest = rest2;
+
+            for (i, entry) in self.zone.iter().enumerate() {
+                if (mdns && !entry.is_local()) || !entry.matches(p.parse_name(question.name)) {
+                    continue;
+                }
+                name_found = true;
+                if question.type_ == Type::A || u16::from(question.type_) == TYPE_ANY {
+                    answers.push((name_offset, i));
+                }
+            }
+        }
+        let questions = &payload[12..payload.len() - rest.len()];
+
+        if mdns && answers.is_empty() {
+            // Multicast DNS responders stay silent when they have nothing to say.
+            return;
+        }
+
+        let mut ttl = self.ttl;
+        let (endpoint, max_size) = if multicast_response {
+            (
+                IpEndpoint::new(MDNS_IPV4_ADDR.into(), MDNS_PORT),
+                MAX_MDNS_RESPONSE_SIZE,
+            )
+        } else {
+            if mdns {
+                ttl = ttl.min(LEGACY_UNICAST_MAX_TTL);
+            }
+            (
+                IpEndpoint::new(ip_repr.src_addr(), udp_repr.src_port),
+                MAX_UNICAST_RESPONSE_SIZE,
+            )
+        };
+
+        let mut response = vec![0; 12];
+        let mut flags = Flags::RESPONSE | Flags::AUTHORITATIVE;
+        if multicast_response {
+            // Multicast responses carry no question section and a zero ID (RFC 6762 § 6, § 18.1).
+        } else {
+            flags |= p.flags() & Flags::RECURSION_DESIRED;
+            response.extend_from_slice(questions);
+        }
+
+        let mut answer_count = 0;
+        for &(name_offset, i) in answers.iter() {
+            let entry = &self.zone[i];
+            let pointer = [0xc0 | (name_offset >> 8) as u8, name_offset as u8];
+            let record = Record {
+                name: if multicast_response {
+                    &entry.raw_name
+                } else {
+                    &pointer
+                },
+                ttl,
+                data: RecordData::A(entry.address),
+            };
+            if response.len() + record.buffer_len() > max_size {
+                flags |= Flags::TRUNCATED;
+                break;
+            }
+            let start = response.len();
+            response.resize(start + record.buffer_len(), 0);
+            record.emit(&mut response[start..]);
+            answer_count += 1;
+        }
+
+        let mut packet = Packet::new_unchecked(&mut response[..]);
+        if !multicast_response {
+            packet.set_transaction_id(p.transaction_id());
+            packet.set_question_count(p.question_count());
+        }
+        packet.set_flags(flags);
+        packet.set_opcode(Opcode::Query);
+        packet.set_rcode(if name_found || mdns {
+            Rcode::NoError
+        } else {
+            Rcode::NXDomain
+        });
+        packet.set_answer_record_count(answer_count);
+
+        net_trace!(
+            "dns responder answering {} records to {}",
+            answer_count,
+            endpoint
+        );
+        self.responses.push_back(Response {
+            endpoint,
+            src_port: udp_repr.dst_port,
+            // Multicast DNS packets are sent with an IP TTL of 255 (RFC 6762 § 11).
+            hop_limit: if mdns { 255 } else { 64 },
+            payload: response,
+            expires_at: cx.now() + RESPONSE_TIMEOUT,
+        });
+    }
+
+    pub(crate) fn dispatch<F, E>(&mut self, cx: &mut Context, emit: F) -> Result<(), E>
+    where
+        F: FnOnce(&mut Context, (IpRepr, UdpRepr, &[u8])) -> Result<(), E>,
+    {
+        // A response stays queued while the neighbor of its destination is being
+        // resolved, but the querier retransmits anyway, so don't keep it forever.
+        while let Some(response) = self.responses.front() {
+            if response.expires_at > cx.now() {
+                break;
+            }
+            net_trace!("dns response to {} expired", response.endpoint);
+            self.responses.pop_front();
+        }
+        let Some(response) = self.responses.front() else {
+            return Ok(());
+        };
+
+        let src_addr = match cx.get_source_address(&response.endpoint.addr) {
+            Some(IpAddress::Ipv4(src_addr)) => src_addr,
+            _ => {
+                net_trace!(
+                    "no source address for destination {}",
+                    response.endpoint.addr
+                );
+                self.responses.pop_front();
+                return Ok(());
+            }
+        };
+
+        let udp_repr = UdpRepr {
+            src_port: response.src_port,
+            dst_port: response.endpoint.port,
+        };
+        let ip_repr = IpRepr::new(
+            src_addr.into(),
+            response.endpoint.addr,
+            IpProtocol::Udp,
+            udp_repr.header_len() + response.payload.len(),
+            response.hop_limit,
+        );
+
+        emit(cx, (ip_repr, udp_repr, &response.payload))?;
+        self.responses.pop_front();
+        Ok(())
+    }
+
+    pub(crate) fn poll_at(&self, _cx: &mut Context) -> PollAt {
+        if self.responses.is_empty() {
+            PollAt::Ingress
+        } else {
+            PollAt::Now
+        }
+    }
+}
@@ -6,6 +6,7 @@ use crate::socket::{Context, PollAt};
 use crate::storage::Empty;
 use crate::wire::IcmpRepr;
 use crate::wire::{Icmpv4Packet, Icmpv4Repr, Ipv4Repr};
+use crate::wire::{Icmpv6Packet, Icmpv6Repr, Ipv6Repr};
 use crate::wire::{IpAddress, IpListenEndpoint, IpProtocol, IpRepr};
 use crate::wire::{UdpPacket, UdpRepr};
 
@@ -182,7 +183,9 @@ impl<'a> Socket<'a> {
     /// To [send] and [recv] ICMP packets that are not associated with a specific UDP
     /// port, the socket may be bound to a specific ICMP identifier using
     /// [IcmpEndpoint::Ident]. This is useful for sending and receiving Echo Request/Reply
-    /// messages.
+    /// messages. Error messages quoting an Echo Request with the bound identifier, such
+    /// as the Time Exceeded messages returned to traceroute probes, are received as well;
+    /// see `Icmpv4Repr::quoted_echo_request` to match them to the probe.
     ///
     /// ```
     /// use smoltcp::wire::IpListenEndpoint;
@@ -359,6 +362,56 @@ impl<'a> Socket<'a> {
         cx: &mut Context,
         ip_repr: &Ipv4Repr,
         icmp_repr: &Icmpv4Repr,
+    ) -> bool {
+        match (&self.endpoint, icmp_repr) {
+            // If we are bound to ICMP errors associated to a UDP port, only
+            // accept Destination Unreachable, Time Exceeded or Parameter Problem
+            // messages with the data containing a UDP packet send from the local
+            // port we are bound to.
+            (
+                &Endpoint::Udp(endpoint),
+                &Icmpv4Repr::DstUnreachable { data, header, .. }
+                | &Icmpv4Repr::TimeExceeded { data, header, .. }
+                | &Icmpv4Repr::ParamProblem { data, header, .. },
+            ) if endpoint.addr.is_none() || endpoint.addr == Some(ip_repr.dst_addr.into()) => {
+                let packet = UdpPacket::new_unchecked(data);
+                match UdpRepr::parse(
+                    &packet,
+                    &header.src_addr.into(),
+                    &header.dst_addr.into(),
+                    &cx.checksum_caps(),
+                ) {
+                    Ok(repr) => endpoint.port == repr.src_port,
+                    Err(_) => false,
+                }
+            }
+            // If we are bound to a specific ICMP identifier value, only accept an
+            // Echo, Timestamp or Address Mask Request/Reply with the identifier
+            // field matching the endpoint port.
+            (&Endpoint::Ident(bound_ident), &Icmpv4Repr::EchoRequest { ident, .. })
+            | (&Endpoint::Ident(bound_ident), &Icmpv4Repr::EchoReply { ident, .. })
+            | (&Endpoint::Ident(bound_ident), &Icmpv4Repr::TimestampRequest { ident, .. })
+            | (&Endpoint::Ident(bound_ident), &Icmpv4Repr::TimestampReply { ident, .. })
+            | (&Endpoint::Ident(bound_ident), &Icmpv4Repr::AddressMaskRequest { ident, .. })
+            | (&Endpoint::Ident(bound_ident), &Icmpv4Repr::AddressMaskReply { ident, .. }) => {
+                ident == bound_ident
+            }
+            // Also accept errors quoting an Echo Request with our identifier, so
+            // that probes sent with a limited hop count can be traced.
+            (&Endpoint::Ident(bound_ident), _) => {
+                matches!(icmp_repr.quoted_echo_request(), Some((ident, _)) if ident == bound_ident)
+            }
+            _ => false,
+        }
+    }
+
+    /// Fitler determining whether the socket accepts a given ICMPv6 packet.
+    /// Accepted packets are enqueued into the socket's receive buffer.
+    pub(crate) fn accepts_v6(
+        &self,
+        cx: &mut Context,
+        ip_repr: &Ipv6Repr,
+        icmp_repr: &Icmpv6Repr,
     ) -> bool {
         match (&self.endpoint, icmp_repr) {
             // If we are bound to ICMP errors associated to a UDP port, only
@@ -367,8 +420,8 @@ impl<'a> Socket<'a> {
             // are bound to.
             (
                 &Endpoint::Udp(endpoint),
-                &Icmpv4Repr::DstUnreachable { data, header, .. }
-                | &Icmpv4Repr::TimeExceeded { data, header, .. },
+                &Icmpv6Repr::DstUnreachable { data, header, .. }
+                | &Icmpv6Repr::TimeExceeded { data, header, .. },
             ) if endpoint.addr.is_none() || endpoint.addr == Some(ip_repr.dst_addr.into()) => {
                 let packet = UdpPacket::new_unchecked(data);
                 match UdpRepr::parse(
@@ -384,9 +437,14 @@ impl<'a> Socket<'a> {
             // If we are bound to a specific ICMP identifier value, only accept an
             // Echo Request/Reply with the identifier field matching the endpoint
             // port.
-            (&Endpoint::Ident(bound_ident), &Icmpv4Repr::EchoRequest { ident, .. })
-            | (&Endpoint::Ident(bound_ident), &Icmpv4Repr::EchoReply { ident, .. }) => {
-                ident == bound_ident
+            (
+                &Endpoint::Ident(bound_ident),
+                &Icmpv6Repr::EchoRequest { ident, .. } | &Icmpv6Repr::EchoReply { ident, .. },
+            ) => ident == bound_ident,
+            // Also accept errors quoting an Echo Request with our identifier, so
+            // that probes sent with a limited hop count can be traced.
+            (&Endpoint::Ident(bound_ident), _) => {
+                matches!(icmp_repr.quoted_echo_request(), Some((ident, _)) if ident == bound_ident)
             }
             _ => false,
         }
@@ -414,6 +472,28 @@ impl<'a> Socket<'a> {
         }
     }
 
+    pub(crate) fn process_v6(
+        &mut self,
+        _cx: &mut Context,
+        ip_repr: &Ipv6Repr,
+        icmp_repr: &Icmpv6Repr,
+    ) {
+        net_trace!("icmp: receiving {} octets", icmp_repr.buffer_len());
+
+        match self
+            .rx_buffer
+            .enqueue(icmp_repr.buffer_len(), ip_repr.src_addr.into())
+        {
+            Ok(packet_buf) => icmp_repr.emit(
+                &ip_repr.src_addr,
+                &ip_repr.dst_addr,
+                &mut Icmpv6Packet::new_unchecked(packet_buf),
+                &ChecksumCapabilities::default(),
+            ),
+            Err(_) => net_trace!("icmp: buffer full, dropped incoming packet"),
+        }
+    }
+
     pub(crate) fn dispatch<F, E>(&mut self, cx: &mut Context, emit: F) -> Result<(), E>
     where
         F: FnOnce(&mut Context, (IpRepr, IcmpRepr)) -> Result<(), E>,
@@ -457,6 +537,34 @@ impl<'a> Socket<'a> {
                     });
                     emit(cx, (ip_repr, IcmpRepr::Ipv4(repr)))
                 }
+                IpAddress::Ipv6(dst_addr) => {
+                    let src_addr = cx.get_source_address_ipv6(&dst_addr);
+
+                    let packet = Icmpv6Packet::new_unchecked(&*packet_buf);
+                    let repr = match Icmpv6Repr::parse(
+                        &src_addr,
+                        &dst_addr,
+                        &packet,
+                        &ChecksumCapabilities::ignored(),
+                    ) {
+                        Ok(x) => x,
+                        Err(_) => {
+                            net_trace!(
+                                "icmp:{}: malformed packet in queue, dropping",
+                                remote_endpoint
+                            );
+                            return Ok(());
+                        }
+                    };
+                    let ip_repr = IpRepr::Ipv6(Ipv6Repr {
+                        src_addr,
+                        dst_addr,
+                        next_header: IpProtocol::Icmpv6,
+                        payload_len: repr.buffer_len(),
+                        hop_limit,
+                    });
+                    emit(cx, (ip_repr, IcmpRepr::Ipv6(repr)))
+                }
             }
         });
         match res {
@@ -14,10 +14,14 @@ size for a buffer, allocate it, and let the networking stack use it.
 use crate::iface::Context;
 use crate::time::Instant;
 
+pub mod dhcpv4;
+pub mod dhcpv4_server;
+pub mod dns;
+pub mod dns_responder;
 pub mod icmp;
 pub mod raw;
 // pub mod tcp;
-// pub mod udp;
+pub mod udp;
 
 /// Gives an indication on the next time the socket should be polled.
 #[derive(Debug, PartialOrd, Ord, PartialEq, Eq, Clone, Copy)]
@@ -44,7 +48,11 @@ pub(crate) enum PollAt {
 pub enum Socket<'a> {
     Raw(raw::Socket<'a>),
     Icmp(icmp::Socket<'a>),
-    // Udp(udp::Socket<'a>),
+    Dhcpv4(dhcpv4::Socket<'a>),
+    Dhcpv4Server(dhcpv4_server::Socket),
+    Dns(dns::Socket<'a>),
+    DnsResponder(dns_responder::Socket),
+    Udp(udp::Socket<'a>),
     // Tcp(tcp::Socket<'a>),
 }
 
@@ -53,7 +61,11 @@ impl<'a> Socket<'a> {
         match self {
             Socket::Raw(s) => s.poll_at(cx),
             Socket::Icmp(s) => s.poll_at(cx),
-            // Socket::Udp(s) => s.poll_at(cx),
+            Socket::Dhcpv4(s) => s.poll_at(cx),
+            Socket::Dhcpv4Server(s) => s.poll_at(cx),
+            Socket::Dns(s) => s.poll_at(cx),
+            Socket::DnsResponder(s) => s.poll_at(cx),
+            Socket::Udp(s) => s.poll_at(cx),
             // Socket::Tcp(s) => s.poll_at(cx),
         }
     }
@@ -98,5 +110,9 @@ macro_rules! from_socket {
 
 from_socket!(raw::Socket<'a>, Raw);
 from_socket!(icmp::Socket<'a>, Icmp);
-// from_socket!(udp::Socket<'a>, Udp);
+from_socket!(dhcpv4::Socket<'a>, Dhcpv4);
+from_socket!(dhcpv4_server::Socket, Dhcpv4Server);
+from_socket!(dns::Socket<'a>, Dns);
+from_socket!(dns_responder::Socket, DnsResponder);
+from_socket!(udp::Socket<'a>, Udp);
 // from_socket!(tcp::Socket<'a>, Tcp);
@@ -0,0 +1,686 @@
+use core::cmp::min;
+
+use crate::iface::Context;
+use crate::phy::PacketMeta;
+use crate::socket::PollAt;
+use crate::storage::Empty;
+use crate::wire::{
+    Icmpv4DstUnreachable, Icmpv6DstUnreachable, IpAddress, IpEndpoint, IpListenEndpoint,
+    IpProtocol, IpRepr, UdpRepr,
+};
+
+/// Metadata for a sent or received UDP packet.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct UdpMetadata {
+    /// The IP endpoint from which an incoming datagram was received, or to which an outgoing
+    /// datagram will be sent.
+    pub endpoint: IpEndpoint,
+    /// The IP address to which an incoming datagram was sent, or from which an outgoing datagram
+    /// will be sent. Incoming datagrams always have this set. On outgoing datagrams, if it is not
+    /// set, and the socket is not bound to a single address anyway, a suitable address will be
+    /// determined using the algorithms of RFC 6724 (candidate source address selection) or some
+    /// heuristic (for IPv4).
+    pub local_address: Option<IpAddress>,
+    pub meta: PacketMeta,
+}
+
+impl<T: Into<IpEndpoint>> From<T> for UdpMetadata {
+    fn from(value: T) -> Self {
+        Self {
+            endpoint: value.into(),
+            local_address: None,
+            meta: PacketMeta::default(),
+        }
+    }
+}
+
+impl core::fmt::Display for UdpMetadata {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}", self.endpoint)
+    }
+}
+
+/// A UDP packet metadata.
+pub type PacketMetadata = crate::storage::PacketMetadata<UdpMetadata>;
+
+/// A UDP packet ring buffer.
+pub type PacketBuffer<'a> = crate::storage::PacketBuffer<'a, UdpMetadata>;
+
+/// Error returned by [`Socket::bind`]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum BindError {
+    InvalidState,
+    Unaddressable,
+}
+
+impl core::fmt::Display for BindError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            BindError::InvalidState => write!(f, "invalid state"),
+            BindError::Unaddressable => write!(f, "unaddressable"),
+        }
+    }
+}
+
+impl std::error::Error for BindError {}
+
+/// Error returned by [`Socket::send`]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum SendError {
+    Unaddressable,
+    BufferFull,
+}
+
+impl core::fmt::Display for SendError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            SendError::Unaddressable => write!(f, "unaddressable"),
+            SendError::BufferFull => write!(f, "buffer full"),
+        }
+    }
+}
+
+impl std::error::Error for SendError {}
+
+/// Error returned by [`Socket::recv`]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum RecvError {
+    Exhausted,
+    Truncated,
+}
+
+impl core::fmt::Display for RecvError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            RecvError::Exhausted => write!(f, "exhausted"),
+            RecvError::Truncated => write!(f, "truncated"),
+        }
+    }
+}
+
+impl std::error::Error for RecvError {}
+
+/// The kind of an [`IcmpError`].
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum IcmpErrorKind {
+    /// Port or protocol unreachable.
+    ConnectionRefused,
+    NetworkUnreachable,
+    HostUnreachable,
+    /// Communication administratively prohibited.
+    Prohibited,
+    /// Fragmentation needed, or packet too big.
+    MessageTooLong,
+    TimeExceeded,
+    ParameterProblem,
+}
+
+impl core::fmt::Display for IcmpErrorKind {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            IcmpErrorKind::ConnectionRefused => write!(f, "connection refused"),
+            IcmpErrorKind::NetworkUnreachable => write!(f, "network unreachable"),
+            IcmpErrorKind::HostUnreachable => write!(f, "host unreachable"),
+            IcmpErrorKind::Prohibited => write!(f, "administratively prohibited"),
+            IcmpErrorKind::MessageTooLong => write!(f, "message too long"),
+            IcmpErrorKind::TimeExceeded => write!(f, "time exceeded"),
+            IcmpErrorKind::ParameterProblem => write!(f, "parameter problem"),
+        }
+    }
+}
+
+impl From<Icmpv4DstUnreachable> for IcmpErrorKind {
+    fn from(reason: Icmpv4DstUnreachable) -> Self {
+        match reason {
+            Icmpv4DstUnreachable::ProtoUnreachable | Icmpv4DstUnreachable::PortUnreachable => {
+                IcmpErrorKind::ConnectionRefused
+            }
+            Icmpv4DstUnreachable::NetUnreachable
+            | Icmpv4DstUnreachable::DstNetUnknown
+            | Icmpv4DstUnreachable::NetUnreachToS => IcmpErrorKind::NetworkUnreachable,
+            Icmpv4DstUnreachable::FragRequired => IcmpErrorKind::MessageTooLong,
+            Icmpv4DstUnreachable::NetProhibited
+            | Icmpv4DstUnreachable::HostProhibited
+            | Icmpv4DstUnreachable::CommProhibited
+            | Icmpv4DstUnreachable::HostPrecedViol
+            | Icmpv4DstUnreachable::PrecedCutoff => IcmpErrorKind::Prohibited,
+            _ => IcmpErrorKind::HostUnreachable,
+        }
+    }
+}
+
+impl From<Icmpv6DstUnreachable> for IcmpErrorKind {
+    fn from(reason: Icmpv6DstUnreachable) -> Self {
+        match reason {
+            Icmpv6DstUnreachable::PortUnreachable => IcmpErrorKind::ConnectionRefused,
+            Icmpv6DstUnreachable::NoRoute | Icmpv6DstUnreachable::BeyondScope => {
+                IcmpErrorKind::NetworkUnreachable
+            }
+            Icmpv6DstUnreachable::AdminProhibit
+            | Icmpv6DstUnreachable::FailedPolicy
+            | Icmpv6DstUnreachable::RejectRoute => IcmpErrorKind::Prohibited,
+            _ => IcmpErrorKind::HostUnreachable,
+        }
+    }
+}
+
+/// An ICMP error caused by a datagram sent by the socket.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct IcmpError {
+    pub kind: IcmpErrorKind,
+    /// The remote endpoint the datagram was sent to.
+    pub endpoint: IpEndpoint,
+    /// The address of the host or router that reported the error, or the local
+    /// address if the datagram was dropped before being sent.
+    pub reported_by: IpAddress,
+}
+
+impl core::fmt::Display for IcmpError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "{} sending to {} (reported by {})",
+            self.kind, self.endpoint, self.reported_by
+        )
+    }
+}
+
+impl std::error::Error for IcmpError {}
+
+/// Number of remote endpoints for which ICMP errors are accepted.
+const SENT_TO_COUNT: usize = 4;
+
+/// A User Datagram Protocol socket.
+///
+/// A UDP socket is bound to a specific endpoint, and owns transmit and receive
+/// packet buffers.
+///
+/// ICMP errors caused by the datagrams it sent, and datagrams dropped for being
+/// larger than the path MTU, are reported by [`take_error`](#method.take_error).
+/// Only the latest error is kept. Errors are only accepted for the last few remote
+/// endpoints the socket sent to, so that an off-path host can't inject them by
+/// guessing the port.
+#[derive(Debug)]
+pub struct Socket<'a> {
+    endpoint: IpListenEndpoint,
+    rx_buffer: PacketBuffer<'a>,
+    tx_buffer: PacketBuffer<'a>,
+    /// The time-to-live (IPv4) or hop limit (IPv6) value used in outgoing packets.
+    hop_limit: Option<u8>,
+    /// The pending ICMP error, if any.
+    error: Option<IcmpError>,
+    /// The last distinct remote endpoints datagrams were sent to.
+    sent_to: [Option<IpEndpoint>; SENT_TO_COUNT],
+}
+
+impl<'a> Socket<'a> {
+    /// Create an UDP socket with the given buffers.
+    pub fn new(rx_buffer: PacketBuffer<'a>, tx_buffer: PacketBuffer<'a>) -> Socket<'a> {
+        Socket {
+            endpoint: IpListenEndpoint::default(),
+            rx_buffer,
+            tx_buffer,
+            hop_limit: None,
+            error: None,
+            sent_to: [None; SENT_TO_COUNT],
+        }
+    }
+
+    /// Return the bound endpoint.
+    pub fn endpoint(&self) -> IpListenEndpoint {
+        self.endpoint
+    }
+
+    /// Return the time-to-live (IPv4) or hop limit (IPv6) value used in outgoing packets.
+    ///
+    /// See also the [set_hop_limit](#method.set_hop_limit) method
+    pub fn hop_limit(&self) -> Option<u8> {
+        self.hop_limit
+    }
+
+    /// Set the time-to-live (IPv4) or hop limit (IPv6) value used in outgoing packets.
+    ///
+    /// A socket without an explicitly set hop limit value uses the default [IANA recommended]
+    /// value (64).
+    ///
+    /// # Panics
+    ///
+    /// This function panics if a hop limit value of 0 is given. See [RFC 1122 § 3.2.1.7].
+    ///
+    /// [IANA recommended]: https://www.iana.org/assignments/ip-parameters/ip-parameters.xhtml
+    /// [RFC 1122 § 3.2.1.7]: https://tools.ietf.org/html/rfc1122#section-3.2.1.7
+    pub fn set_hop_limit(&mut self, hop_limit: Option<u8>) {
+        // A host MUST NOT send a datagram with a hop limit value of 0
+        if let Some(0) = hop_limit {
+            panic!("the time-to-live value of a packet must not be zero")
+        }
+
+        self.hop_limit = hop_limit
+    }
+
+    /// Bind the socket to the given endpoint.
+    ///
+    /// This function returns `Err(Error::Illegal)` if the socket was open
+    /// (see [is_open](#method.is_open)), and `Err(Error::Unaddressable)`
+    /// if the port in the given endpoint is zero.
+    pub fn bind<T: Into<IpListenEndpoint>>(&mut self, endpoint: T) -> Result<(), BindError> {
+        let endpoint = endpoint.into();
+        if endpoint.port == 0 {
+            return Err(BindError::Unaddressable);
+        }
+
+        if self.is_open() {
+            return Err(BindError::InvalidState);
+        }
+
+        self.endpoint = endpoint;
+
+        Ok(())
+    }
+
+    /// Close the socket.
+    pub fn close(&mut self) {
+        // Clear the bound endpoint of the socket.
+        self.endpoint = IpListenEndpoint::default();
+
+        // Reset the RX and TX buffers of the socket.
+        self.tx_buffer.reset();
+        self.rx_buffer.reset();
+        self.error = None;
+        self.sent_to = [None; SENT_TO_COUNT];
+    }
+
+    /// Check whether the socket is open.
+    pub fn is_open(&self) -> bool {
+        self.endpoint.port != 0
+    }
+
+    /// Check whether the transmit buffer is full.
+    pub fn can_send(&self) -> bool {
+        !self.tx_buffer.is_full()
+    }
+
+    /// Check whether the receive buffer is not empty.
+    pub fn can_recv(&self) -> bool {
+        !self.rx_buffer.is_empty()
+    }
+
+    /// Return and clear the pending ICMP error, if any.
+    pub fn take_error(&mut self) -> Option<IcmpError> {
+        self.error.take()
+    }
+
+    /// Return the maximum number packets the socket can receive.
+    pub fn packet_recv_capacity(&self) -> usize {
+        self.rx_buffer.packet_capacity()
+    }
+
+    /// Return the maximum number packets the socket can transmit.
+    pub fn packet_send_capacity(&self) -> usize {
+        self.tx_buffer.packet_capacity()
+    }
+
+    /// Return the maximum number of bytes inside the recv buffer.
+    pub fn payload_recv_capacity(&self) -> usize {
+        self.rx_buffer.payload_capacity()
+    }
+
+    /// Return the maximum number of bytes inside the transmit buffer.
+    pub fn payload_send_capacity(&self) -> usize {
+        self.tx_buffer.payload_capacity()
+    }
+
+    /// Enqueue a packet to be sent to a given remote endpoint, and return a pointer
+    /// to its payload.
+    ///
+    /// This function returns `Err(Error::Exhausted)` if the transmit buffer is full,
+    /// `Err(Error::Unaddressable)` if local or remote port, or remote address are unspecified,
+    /// and `Err(Error::Truncated)` if there is not enough transmit buffer capacity
+    /// to ever send this packet.
+    pub fn send(
+        &mut self,
+        size: usize,
+        meta: impl Into<UdpMetadata>,
+    ) -> Result<&mut [u8], SendError> {
+        let meta = meta.into();
+        if self.endpoint.port == 0 {
+            return Err(SendError::Unaddressable);
+        }
+        if meta.endpoint.addr.is_unspecified() {
+            return Err(SendError::Unaddressable);
+        }
+        if meta.endpoint.port == 0 {
+            return Err(SendError::Unaddressable);
+        }
+
+        let payload_buf = self
+            .tx_buffer
+            .enqueue(size, meta)
+            .map_err(|_| SendError::BufferFull)?;
+
+        net_trace!(
+            "udp:{}:{}: buffer to send {} octets",
+            self.endpoint,
+            meta.endpoint,
+            size
+        );
+        Ok(payload_buf)
+    }
+
+    /// Enqueue a packet to be send to a given remote endpoint and pass the buffer
+    /// to the provided closure. The closure then returns the size of the data written
+    /// into the buffer.
+    ///
+    /// Also see [send](#method.send).
+    pub fn send_with<F>(
+        &mut self,
+        max_size: usize,
+        meta: impl Into<UdpMetadata>,
+        f: F,
+    ) -> Result<usize, SendError>
+    where
+        F: FnOnce(&mut [u8]) -> usize,
+    {
+        let meta = meta.into();
+        if self.endpoint.port == 0 {
+            return Err(SendError::Unaddressable);
+        }
+        if meta.endpoint.addr.is_unspecified() {
+            return Err(SendError::Unaddressable);
+        }
+        if meta.endpoint.port == 0 {
+            return Err(SendError::Unaddressable);
+        }
+
+        let size = self
+            .tx_buffer
+            .enqueue_with_infallible(max_size, meta, f)
+            .map_err(|_| SendError::BufferFull)?;
+
+        net_trace!(
+            "udp:{}:{}: buffer to send {} octets",
+            self.endpoint,
+            meta.endpoint,
+            size
+        );
+        Ok(size)
+    }
+
+    /// Enqueue a packet to be sent to a given remote endpoint, and fill it from a slice.
+    ///
+    /// See also [send](#method.send).
+    pub fn send_slice(
+        &mut self,
+        data: &[u8],
+        meta: impl Into<UdpMetadata>,
+    ) -> Result<(), SendError> {
+        self.send(data.len(), meta)?.copy_from_slice(data);
+        Ok(())
+    }
+
+    /// Dequeue a packet received from a remote endpoint, and return the endpoint as well
+    /// as a pointer to the payload.
+    ///
+    /// This function returns `Err(Error::Exhausted)` if the receive buffer is empty.
+    pub fn recv(&mut self) -> Result<(&[u8], UdpMetadata), RecvError> {
+        let (remote_endpoint, payload_buf) =
+            self.rx_buffer.dequeue().map_err(|_| RecvError::Exhausted)?;
+
+        net_trace!(
+            "udp:{}:{}: receive {} buffered octets",
+            self.endpoint,
+            remote_endpoint.endpoint,
+            payload_buf.len()
+        );
+        Ok((payload_buf, remote_endpoint))
+    }
+
+    /// Dequeue a packet received from a remote endpoint, copy the payload into the given slice,
+    /// and return the amount of octets copied as well as the endpoint.
+    ///
+    /// **Note**: when the size of the provided buffer is smaller than the size of the payload,
+    /// the packet is dropped and a `RecvError::Truncated` error is returned.
+    ///
+    /// See also [recv](#method.recv).
+    pub fn recv_slice(&mut self, data: &mut [u8]) -> Result<(usize, UdpMetadata), RecvError> {
+        let (buffer, endpoint) = self.recv().map_err(|_| RecvError::Exhausted)?;
+
+        if data.len() < buffer.len() {
+            return Err(RecvError::Truncated);
+        }
+
+        let length = min(data.len(), buffer.len());
+        data[..length].copy_from_slice(&buffer[..length]);
+        Ok((length, endpoint))
+    }
+
+    /// Peek at a packet received from a remote endpoint, and return the endpoint as well
+    /// as a pointer to the payload without removing the packet from the receive buffer.
+    /// This function otherwise behaves identically to [recv](#method.recv).
+    ///
+    /// It returns `Err(Error::Exhausted)` if the receive buffer is empty.
+    pub fn peek(&mut self) -> Result<(&[u8], &UdpMetadata), RecvError> {
+        let endpoint = self.endpoint;
+        self.rx_buffer.peek().map_err(|_| RecvError::Exhausted).map(
+            |(remote_endpoint, payload_buf)| {
+                net_trace!(
+                    "udp:{}:{}: peek {} buffered octets",
+                    endpoint,
+                    remote_endpoint.endpoint,
+                    payload_buf.len()
+                );
+                (payload_buf, remote_endpoint)
+            },
+        )
+    }
+
+    /// Peek at a packet received from a remote endpoint, copy the payload into the given slice,
+    /// and return the amount of octets copied as well as the endpoint without removing the
+    /// packet from the receive buffer.
+    /// This function otherwise behaves identically to [recv_slice](#method.recv_slice).
+    ///
+    /// **Note**: when the size of the provided buffer is smaller than the size of the payload,
+    /// no data is copied into the provided buffer and a `RecvError::Truncated` error is returned.
+    ///
+    /// See also [peek](#method.peek).
+    pub fn peek_slice(&mut self, data: &mut [u8]) -> Result<(usize, &UdpMetadata), RecvError> {
+        let (buffer, endpoint) = self.peek()?;
+
+        if data.len() < buffer.len() {
+            return Err(RecvError::Truncated);
+        }
+
+        let length = min(data.len(), buffer.len());
+        data[..length].copy_from_slice(&buffer[..length]);
+        Ok((length, endpoint))
+    }
+
+    /// Return the amount of octets queued in the transmit buffer.
+    ///
+    /// Note that the Berkeley sockets interface does not have an equivalent of this API.
+    pub fn send_queue(&self) -> usize {
+        self.tx_buffer.payload_bytes_count()
+    }
+
+    /// Return the amount of octets queued in the receive buffer. This value can be larger than
+    /// the slice read by the next `recv` or `peek` call because it includes all queued octets,
+    /// and not only the octets that may be returned as a contiguous slice.
+    ///
+    /// Note that the Berkeley sockets interface does not have an equivalent of this API.
+    pub fn recv_queue(&self) -> usize {
+        self.rx_buffer.payload_bytes_count()
+    }
+
+    pub(crate) fn accepts(&self, cx: &mut Context, ip_repr: &IpRepr, repr: &UdpRepr) -> bool {
+        if self.endpoint.port != repr.dst_port {
+            return false;
+        }
+        if self.endpoint.addr.is_some()
+            && self.endpoint.addr != Some(ip_repr.dst_addr())
+            && !cx.is_broadcast(&ip_repr.dst_addr())
+            && !ip_repr.dst_addr().is_multicast()
+        {
+            return false;
+        }
+
+        true
+    }
+
+    pub(crate) fn process(
+        &mut self,
+        cx: &mut Context,
+        meta: PacketMeta,
+        ip_repr: &IpRepr,
+        repr: &UdpRepr,
+        payload: &[u8],
+    ) {
+        debug_assert!(self.accepts(cx, ip_repr, repr));
+
+        let size = payload.len();
+
+        let remote_endpoint = IpEndpoint {
+            addr: ip_repr.src_addr(),
+            port: repr.src_port,
+        };
+
+        net_trace!(
+            "udp:{}:{}: receiving {} octets",
+            self.endpoint,
+            remote_endpoint,
+            size
+        );
+
+        let metadata = UdpMetadata {
+            endpoint: remote_endpoint,
+            local_address: Some(ip_repr.dst_addr()),
+            meta,
+        };
+
+        match self.rx_buffer.enqueue(size, metadata) {
+            Ok(buf) => buf.copy_from_slice(payload),
+            Err(_) => net_trace!(
+                "udp:{}:{}: buffer full, dropped incoming packet",
+                self.endpoint,
+                remote_endpoint
+            ),
+        }
+    }
+
+    /// Filter determining whether the socket sent the datagram quoted by an ICMP error.
+    pub(crate) fn accepts_error(&self, ip_repr: &IpRepr, repr: &UdpRepr) -> bool {
+        let remote_endpoint = IpEndpoint::new(ip_repr.dst_addr(), repr.dst_port);
+        self.endpoint.port == repr.src_port
+            && self
+                .endpoint
+                .addr
+                .map_or(true, |addr| addr == ip_repr.src_addr())
+            && self.sent_to.contains(&Some(remote_endpoint))
+    }
+
+    /// Record the ICMP error reported by `reported_by` for the datagram quoted
+    /// by `ip_repr` and `repr`.
+    pub(crate) fn process_error(
+        &mut self,
+        reported_by: IpAddress,
+        ip_repr: &IpRepr,
+        repr: &UdpRepr,
+        kind: IcmpErrorKind,
+    ) {
+        debug_assert!(self.accepts_error(ip_repr, repr));
+
+        let error = IcmpError {
+            kind,
+            endpoint: IpEndpoint::new(ip_repr.dst_addr(), repr.dst_port),
+            reported_by,
+        };
+        net_debug!("udp:{}: {}", self.endpoint, error);
+        self.error = Some(error);
+    }
+
+    pub(crate) fn dispatch<F, E>(&mut self, cx: &mut Context, emit: F) -> Result<(), E>
+    where
+        F: FnOnce(&mut Context, PacketMeta, (IpRepr, UdpRepr, &[u8])) -> Result<(), E>,
+    {
+        let endpoint = self.endpoint;
+        let hop_limit = self.hop_limit.unwrap_or(64);
+        let error = &mut self.error;
+        let sent_to = &mut self.sent_to;
+
+        let res = self.tx_buffer.dequeue_with(|packet_meta, payload_buf| {
+            let src_addr = if let Some(s) = packet_meta.local_address {
+                s
+            } else {
+                match endpoint.addr {
+                    Some(addr) => addr,
+                    None => match cx.get_source_address(&packet_meta.endpoint.addr) {
+                        Some(addr) => addr,
+                        None => {
+                            net_trace!(
+                                "udp:{}:{}: cannot find suitable source address, dropping.",
+                                endpoint,
+                                packet_meta.endpoint
+                            );
+                            return Ok(());
+                        }
+                    },
+                }
+            };
+
+            net_trace!(
+                "udp:{}:{}: sending {} octets",
+                endpoint,
+                packet_meta.endpoint,
+                payload_buf.len()
+            );
+
+            let repr = UdpRepr {
+                src_port: endpoint.port,
+                dst_port: packet_meta.endpoint.port,
+            };
+            let ip_repr = IpRepr::new(
+                src_addr,
+                packet_meta.endpoint.addr,
+                IpProtocol::Udp,
+                repr.header_len() + payload_buf.len(),
+                hop_limit,
+            );
+
+            let path_mtu = cx.path_mtu(&packet_meta.endpoint.addr);
+            if ip_repr.buffer_len() > path_mtu {
+                net_debug!(
+                    "udp:{}:{}: {} octets exceed the path MTU of {}, dropping.",
+                    endpoint,
+                    packet_meta.endpoint,
+                    payload_buf.len(),
+                    path_mtu
+                );
+                *error = Some(IcmpError {
+                    kind: IcmpErrorKind::MessageTooLong,
+                    endpoint: packet_meta.endpoint,
+                    reported_by: src_addr,
+                });
+                return Ok(());
+            }
+
+            if !sent_to.contains(&Some(packet_meta.endpoint)) {
+                sent_to.rotate_right(1);
+                sent_to[0] = Some(packet_meta.endpoint);
+            }
+
+            emit(cx, packet_meta.meta, (ip_repr, repr, payload_buf))
+        });
+        match res {
+            Err(Empty) => Ok(()),
+            Ok(Err(e)) => Err(e),
+            Ok(Ok(())) => Ok(()),
+        }
+    }
+
+    pub(crate) fn poll_at(&self, _cx: &mut Context) -> PollAt {
+        if self.tx_buffer.is_empty() {
+            PollAt::Ingress
+        } else {
+            PollAt::Now
+        }
+    }
+}
@@ -6,9 +6,13 @@ use super::{Error, Result};
 enum_with_unknown! {
     /// Ethernet protocol type.
     pub enum EtherType(u16) {
-        Ipv4 = 0x0800,
-        Arp  = 0x0806,
-        Ipv6 = 0x86DD
+        Ipv4   = 0x0800,
+        Arp    = 0x0806,
+        /// IEEE 802.1Q customer VLAN tag.
+        Dot1Q  = 0x8100,
+        Ipv6   = 0x86DD,
+        /// IEEE 802.1ad service VLAN tag.
+        Dot1Ad = 0x88A8
     }
 }
 
@@ -18,6 +22,8 @@ impl fmt::Display for EtherType {
             EtherType::Ipv4 => write!(f, "IPv4"),
             EtherType::Ipv6 => write!(f, "IPv6"),
             EtherType::Arp => write!(f, "ARP"),
+            EtherType::Dot1Q => write!(f, "802.1Q"),
+            EtherType::Dot1Ad => write!(f, "802.1ad"),
             EtherType::Unknown(id) => write!(f, "0x{id:04x}"),
         }
     }
@@ -234,6 +240,14 @@ impl<T: AsRef<[u8]>> PrettyPrint for Frame<T> {
                 indent.increase(f)?;
                 super::Ipv4Packet::<&[u8]>::pretty_print(&frame.payload(), f, indent)
             }
+            EtherType::Ipv6 => {
+                indent.increase(f)?;
+                super::Ipv6Packet::<&[u8]>::pretty_print(&frame.payload(), f, indent)
+            }
+            EtherType::Dot1Q | EtherType::Dot1Ad => {
+                indent.increase(f)?;
+                super::VlanPacket::<&[u8]>::pretty_print(&frame.payload(), f, indent)
+            }
             _ => Ok(()),
         }
     }
@@ -3,8 +3,9 @@ use core::{cmp, fmt};
 
 use super::{Error, Result};
 use crate::phy::ChecksumCapabilities;
+use crate::time::Duration;
 use crate::wire::ip::checksum;
-use crate::wire::{Ipv4Packet, Ipv4Repr};
+use crate::wire::{IpProtocol, Ipv4Address, Ipv4AddressExt, Ipv4Packet, Ipv4Repr, IPV4_HEADER_LEN};
 
 enum_with_unknown! {
     /// Internet protocol control message type.
@@ -28,7 +29,11 @@ enum_with_unknown! {
         /// Timestamp
         Timestamp      = 13,
         /// Timestamp reply
-        TimestampReply = 14
+        TimestampReply = 14,
+        /// Address mask request
+        AddressMaskRequest = 17,
+        /// Address mask reply
+        AddressMaskReply = 18
     }
 }
 
@@ -45,6 +50,8 @@ impl fmt::Display for Message {
             Message::ParamProblem => write!(f, "parameter problem"),
             Message::Timestamp => write!(f, "timestamp"),
             Message::TimestampReply => write!(f, "timestamp reply"),
+            Message::AddressMaskRequest => write!(f, "address mask request"),
+            Message::AddressMaskReply => write!(f, "address mask reply"),
             Message::Unknown(id) => write!(f, "{id}"),
         }
     }
@@ -128,6 +135,18 @@ enum_with_unknown! {
     }
 }
 
+impl fmt::Display for Redirect {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Redirect::Net => write!(f, "redirect for network"),
+            Redirect::Host => write!(f, "redirect for host"),
+            Redirect::NetToS => write!(f, "redirect for type of service and network"),
+            Redirect::HostToS => write!(f, "redirect for type of service and host"),
+            Redirect::Unknown(id) => write!(f, "{id}"),
+        }
+    }
+}
+
 enum_with_unknown! {
     /// Internet protocol control message subtype for type "Time Exceeded".
     pub enum TimeExceeded(u8) {
@@ -160,6 +179,67 @@ enum_with_unknown! {
     }
 }
 
+impl fmt::Display for ParamProblem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ParamProblem::AtPointer => write!(f, "error at pointer"),
+            ParamProblem::MissingOption => write!(f, "missing a required option"),
+            ParamProblem::BadLength => write!(f, "bad length"),
+            ParamProblem::Unknown(id) => write!(f, "{id}"),
+        }
+    }
+}
+
+/// The router addresses of a router advertisement (RFC 1256).
+///
+/// The entries are pairs of a router address and a signed preference level,
+/// eight octets each.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct RouterAddrs<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> RouterAddrs<'a> {
+    /// Size of an entry, in octets.
+    pub const ENTRY_LEN: usize = 8;
+
+    /// Wrap the raw entries of a router advertisement.
+    ///
+    /// Returns `Err(Error)` if `data` is not a whole number of entries, or has
+    /// more entries than fit in the "Num Addrs" field.
+    pub fn new_checked(data: &'a [u8]) -> Result<RouterAddrs<'a>> {
+        if data.len() % Self::ENTRY_LEN != 0 || data.len() / Self::ENTRY_LEN > u8::MAX as usize {
+            return Err(Error);
+        }
+        Ok(RouterAddrs { data })
+    }
+
+    /// Return the number of entries.
+    pub fn len(&self) -> usize {
+        self.data.len() / Self::ENTRY_LEN
+    }
+
+    /// Query whether there are no entries.
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    /// Iterate over the router addresses and their preference levels.
+    pub fn iter(&self) -> impl Iterator<Item = (Ipv4Address, i32)> + 'a {
+        self.data.chunks_exact(Self::ENTRY_LEN).map(|entry| {
+            (
+                Ipv4Address::from_bytes(&entry[0..4]),
+                NetworkEndian::read_i32(&entry[4..8]),
+            )
+        })
+    }
+
+    /// Return the raw entries.
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.data
+    }
+}
+
 /// A read/write wrapper around an Internet Control Message Protocol version 4 packet buffer.
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct Packet<T: AsRef<[u8]>> {
@@ -178,6 +258,22 @@ mod field {
     pub const ECHO_IDENT: Field = 4..6;
     pub const ECHO_SEQNO: Field = 6..8;
 
+    pub const NEXT_HOP_MTU: Field = 6..8;
+
+    pub const REDIRECT_GATEWAY: Field = 4..8;
+
+    pub const PARAM_PROBLEM_POINTER: usize = 4;
+
+    pub const TIMESTAMP_ORIGINATE: Field = 8..12;
+    pub const TIMESTAMP_RECEIVE: Field = 12..16;
+    pub const TIMESTAMP_TRANSMIT: Field = 16..20;
+
+    pub const ADDRESS_MASK: Field = 8..12;
+
+    pub const ROUTER_ADVERT_NUM_ADDRS: usize = 4;
+    pub const ROUTER_ADVERT_ENTRY_SIZE: usize = 5;
+    pub const ROUTER_ADVERT_LIFETIME: Field = 6..8;
+
     pub const HEADER_END: usize = 8;
 }
 
@@ -205,7 +301,7 @@ impl<T: AsRef<[u8]>> Packet<T> {
     /// [set_header_len]: #method.set_header_len
     pub fn check_len(&self) -> Result<()> {
         let len = self.buffer.as_ref().len();
-        if len < field::HEADER_END {
+        if len < field::HEADER_END || len < self.header_len() {
             Err(Error)
         } else {
             Ok(())
@@ -238,7 +334,7 @@ impl<T: AsRef<[u8]>> Packet<T> {
         NetworkEndian::read_u16(&data[field::CHECKSUM])
     }
 
-    /// Return the identifier field (for echo request and reply packets).
+    /// Return the identifier field (for echo, timestamp and address mask packets).
     ///
     /// # Panics
     /// This function may panic if this packet is not an echo request or reply packet.
@@ -248,7 +344,7 @@ impl<T: AsRef<[u8]>> Packet<T> {
         NetworkEndian::read_u16(&data[field::ECHO_IDENT])
     }
 
-    /// Return the sequence number field (for echo request and reply packets).
+    /// Return the sequence number field (for echo, timestamp and address mask packets).
     ///
     /// # Panics
     /// This function may panic if this packet is not an echo request or reply packet.
@@ -258,6 +354,95 @@ impl<T: AsRef<[u8]>> Packet<T> {
         NetworkEndian::read_u16(&data[field::ECHO_SEQNO])
     }
 
+    /// Return the next-hop MTU field (for "fragmentation needed" packets, RFC 1191).
+    ///
+    /// Routers predating RFC 1191 leave this field zero.
+    pub fn next_hop_mtu(&self) -> u16 {
+        let data = self.buffer.as_ref();
+        NetworkEndian::read_u16(&data[field::NEXT_HOP_MTU])
+    }
+
+    /// Return the gateway address field (for redirect packets).
+    ///
+    /// # Panics
+    /// This function may panic if this packet is not a redirect packet.
+    pub fn redirect_gateway(&self) -> Ipv4Address {
+        let data = self.buffer.as_ref();
+        Ipv4Address::from_bytes(&data[field::REDIRECT_GATEWAY])
+    }
+
+    /// Return the pointer field (for parameter problem packets).
+    ///
+    /// # Panics
+    /// This function may panic if this packet is not a parameter problem packet.
+    pub fn param_problem_pointer(&self) -> u8 {
+        let data = self.buffer.as_ref();
+        data[field::PARAM_PROBLEM_POINTER]
+    }
+
+    /// Return the originate timestamp field (for timestamp packets).
+    ///
+    /// # Panics
+    /// This function may panic if this packet is not a timestamp packet.
+    pub fn timestamp_originate(&self) -> u32 {
+        let data = self.buffer.as_ref();
+        NetworkEndian::read_u32(&data[field::TIMESTAMP_ORIGINATE])
+    }
+
+    /// Return the receive timestamp field (for timestamp packets).
+    ///
+    /// # Panics
+    /// This function may panic if this packet is not a timestamp packet.
+    pub fn timestamp_receive(&self) -> u32 {
+        let data = self.buffer.as_ref();
+        NetworkEndian::read_u32(&data[field::TIMESTAMP_RECEIVE])
+    }
+
+    /// Return the transmit timestamp field (for timestamp packets).
+    ///
+    /// # Panics
+    /// This function may panic if this packet is not a timestamp packet.
+    pub fn timestamp_transmit(&self) -> u32 {
+        let data = self.buffer.as_ref();
+        NetworkEndian::read_u32(&data[field::TIMESTAMP_TRANSMIT])
+    }
+
+    /// Return the address mask field (for address mask packets).
+    ///
+    /// # Panics
+    /// This function may panic if this packet is not an address mask packet.
+    pub fn address_mask(&self) -> Ipv4Address {
+        let data = self.buffer.as_ref();
+        Ipv4Address::from_bytes(&data[field::ADDRESS_MASK])
+    }
+
+    /// Return the number of addresses field (for router advertisement packets).
+    ///
+    /// # Panics
+    /// This function may panic if this packet is not a router advertisement packet.
+    pub fn router_advert_num_addrs(&self) -> u8 {
+        let data = self.buffer.as_ref();
+        data[field::ROUTER_ADVERT_NUM_ADDRS]
+    }
+
+    /// Return the address entry size field, in 32-bit words (for router advertisement packets).
+    ///
+    /// # Panics
+    /// This function may panic if this packet is not a router advertisement packet.
+    pub fn router_advert_entry_size(&self) -> u8 {
+        let data = self.buffer.as_ref();
+        data[field::ROUTER_ADVERT_ENTRY_SIZE]
+    }
+
+    /// Return the lifetime field, in seconds (for router advertisement packets).
+    ///
+    /// # Panics
+    /// This function may panic if this packet is not a router advertisement packet.
+    pub fn router_advert_lifetime(&self) -> u16 {
+        let data = self.buffer.as_ref();
+        NetworkEndian::read_u16(&data[field::ROUTER_ADVERT_LIFETIME])
+    }
+
     /// Return the header length.
     /// The result depends on the value of the message type field.
     pub fn header_len(&self) -> usize {
@@ -265,6 +450,9 @@ impl<T: AsRef<[u8]>> Packet<T> {
             Message::EchoRequest => field::ECHO_SEQNO.end,
             Message::EchoReply => field::ECHO_SEQNO.end,
             Message::DstUnreachable => field::UNUSED.end,
+            Message::Redirect => field::REDIRECT_GATEWAY.end,
+            Message::Timestamp | Message::TimestampReply => field::TIMESTAMP_TRANSMIT.end,
+            Message::AddressMaskRequest | Message::AddressMaskReply => field::ADDRESS_MASK.end,
             _ => field::UNUSED.end, // make a conservative assumption
         }
     }
@@ -330,6 +518,94 @@ impl<T: AsRef<[u8]> + AsMut<[u8]>> Packet<T> {
         NetworkEndian::write_u16(&mut data[field::ECHO_SEQNO], value)
     }
 
+    /// Set the next-hop MTU field (for "fragmentation needed" packets, RFC 1191).
+    pub fn set_next_hop_mtu(&mut self, value: u16) {
+        let data = self.buffer.as_mut();
+        NetworkEndian::write_u16(&mut data[field::NEXT_HOP_MTU], value)
+    }
+
+    /// Set the gateway address field (for redirect packets).
+    ///
+    /// # Panics
+    /// This function may panic if this packet is not a redirect packet.
+    pub fn set_redirect_gateway(&mut self, value: Ipv4Address) {
+        let data = self.buffer.as_mut();
+        data[field::REDIRECT_GATEWAY].copy_from_slice(&value.octets())
+    }
+
+    /// Set the pointer field (for parameter problem packets).
+    ///
+    /// # Panics
+    /// This function may panic if this packet is not a parameter problem packet.
+    pub fn set_param_problem_pointer(&mut self, value: u8) {
+        let data = self.buffer.as_mut();
+        data[field::PARAM_PROBLEM_POINTER] = value;
+        data[field::PARAM_PROBLEM_POINTER + 1..field::UNUSED.end].fill(0);
+    }
+
+    /// Set the originate timestamp field (for timestamp packets).
+    ///
+    /// # Panics
+    /// This function may panic if this packet is not a timestamp packet.
+    pub fn set_timestamp_originate(&mut self, value: u32) {
+        let data = self.buffer.as_mut();
+        NetworkEndian::write_u32(&mut data[field::TIMESTAMP_ORIGINATE], value)
+    }
+
+    /// Set the receive timestamp field (for timestamp packets).
+    ///
+    /// # Panics
+    /// This function may panic if this packet is not a timestamp packet.
+    pub fn set_timestamp_receive(&mut self, value: u32) {
+        let data = self.buffer.as_mut();
+        NetworkEndian::write_u32(&mut data[field::TIMESTAMP_RECEIVE], value)
+    }
+
+    /// Set the transmit timestamp field (for timestamp packets).
+    ///
+    /// # Panics
+    /// This function may panic if this packet is not a timestamp packet.
+    pub fn set_timestamp_transmit(&mut self, value: u32) {
+        let data = self.buffer.as_mut();
+        NetworkEndian::write_u32(&mut data[field::TIMESTAMP_TRANSMIT], value)
+    }
+
+    /// Set the address mask field (for address mask packets).
+    ///
+    /// # Panics
+    /// This function may panic if this packet is not an address mask packet.
+    pub fn set_address_mask(&mut self, value: Ipv4Address) {
+        let data = self.buffer.as_mut();
+        data[field::ADDRESS_MASK].copy_from_slice(&value.octets())
+    }
+
+    /// Set the number of addresses field (for router advertisement packets).
+    ///
+    /// # Panics
+    /// This function may panic if this packet is not a router advertisement packet.
+    pub fn set_router_advert_num_addrs(&mut self, value: u8) {
+        let data = self.buffer.as_mut();
+        data[field::ROUTER_ADVERT_NUM_ADDRS] = value
+    }
+
+    /// Set the address entry size field, in 32-bit words (for router advertisement packets).
+    ///
+    /// # Panics
+    /// This function may panic if this packet is not a router advertisement packet.
+    pub fn set_router_advert_entry_size(&mut self, value: u8) {
+        let data = self.buffer.as_mut();
+        data[field::ROUTER_ADVERT_ENTRY_SIZE] = value
+    }
+
+    /// Set the lifetime field, in seconds (for router advertisement packets).
+    ///
+    /// # Panics
+    /// This function may panic if this packet is not a router advertisement packet.
+    pub fn set_router_advert_lifetime(&mut self, value: u16) {
+        let data = self.buffer.as_mut();
+        NetworkEndian::write_u16(&mut data[field::ROUTER_ADVERT_LIFETIME], value)
+    }
+
     /// Compute and fill in the header checksum.
     pub fn fill_checksum(&mut self) {
         self.set_checksum(0);
@@ -357,6 +633,38 @@ impl<T: AsRef<[u8]>> AsRef<[u8]> for Packet<T> {
     }
 }
 
+/// Parse the header and data of the datagram quoted by an error message.
+///
+/// Routers usually quote only the header and the first eight octets of the
+/// datagram, so unlike `Ipv4Packet::new_checked`, the total length field is not
+/// checked against the quoted data.
+fn parse_quoted_datagram(data: &[u8]) -> Result<(Ipv4Repr, &[u8])> {
+    if data.len() < IPV4_HEADER_LEN {
+        return Err(Error);
+    }
+    let ip_packet = Ipv4Packet::new_unchecked(data);
+    let header_len = ip_packet.header_len() as usize;
+    if header_len < IPV4_HEADER_LEN || data.len() < header_len {
+        return Err(Error);
+    }
+
+    let payload = &data[header_len..];
+    // RFC 792 requires exactly eight bytes to be returned.
+    // We allow more, since there isn't a reason not to, but require at least eight.
+    if payload.len() < 8 {
+        return Err(Error);
+    }
+
+    let header = Ipv4Repr {
+        src_addr: ip_packet.src_addr(),
+        dst_addr: ip_packet.dst_addr(),
+        next_header: ip_packet.next_header(),
+        payload_len: payload.len(),
+        hop_limit: ip_packet.hop_limit(),
+    };
+    Ok((header, payload))
+}
+
 /// A high-level representation of an Internet Control Message Protocol version 4 packet header.
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 #[non_exhaustive]
@@ -381,6 +689,47 @@ pub enum Repr<'a> {
         header: Ipv4Repr,
         data: &'a [u8],
     },
+    Redirect {
+        reason: Redirect,
+        gateway: Ipv4Address,
+        header: Ipv4Repr,
+        data: &'a [u8],
+    },
+    ParamProblem {
+        reason: ParamProblem,
+        pointer: u8,
+        header: Ipv4Repr,
+        data: &'a [u8],
+    },
+    /// Timestamps are in milliseconds since midnight UT (RFC 792).
+    TimestampRequest {
+        ident: u16,
+        seq_no: u16,
+        originate: u32,
+        receive: u32,
+        transmit: u32,
+    },
+    TimestampReply {
+        ident: u16,
+        seq_no: u16,
+        originate: u32,
+        receive: u32,
+        transmit: u32,
+    },
+    AddressMaskRequest {
+        ident: u16,
+        seq_no: u16,
+    },
+    AddressMaskReply {
+        ident: u16,
+        seq_no: u16,
+        mask: Ipv4Address,
+    },
+    RouterAdvert {
+        lifetime: Duration,
+        addrs: RouterAddrs<'a>,
+    },
+    RouterSolicit,
 }
 
 impl<'a> Repr<'a> {
@@ -414,51 +763,91 @@ impl<'a> Repr<'a> {
             }),
 
             (Message::DstUnreachable, code) => {
-                let ip_packet = Ipv4Packet::new_checked(packet.data())?;
-
-                let payload = &packet.data()[ip_packet.header_len() as usize..];
-                // RFC 792 requires exactly eight bytes to be returned.
-                // We allow more, since there isn't a reason not to, but require at least eight.
-                if payload.len() < 8 {
-                    return Err(Error);
-                }
+                let (header, payload) = parse_quoted_datagram(packet.data())?;
 
                 Ok(Repr::DstUnreachable {
                     reason: DstUnreachable::from(code),
-                    header: Ipv4Repr {
-                        src_addr: ip_packet.src_addr(),
-                        dst_addr: ip_packet.dst_addr(),
-                        next_header: ip_packet.next_header(),
-                        payload_len: payload.len(),
-                        hop_limit: ip_packet.hop_limit(),
-                    },
+                    header,
                     data: payload,
                 })
             }
 
             (Message::TimeExceeded, code) => {
-                let ip_packet = Ipv4Packet::new_checked(packet.data())?;
-
-                let payload = &packet.data()[ip_packet.header_len() as usize..];
-                // RFC 792 requires exactly eight bytes to be returned.
-                // We allow more, since there isn't a reason not to, but require at least eight.
-                if payload.len() < 8 {
-                    return Err(Error);
-                }
+                let (header, payload) = parse_quoted_datagram(packet.data())?;
 
                 Ok(Repr::TimeExceeded {
                     reason: TimeExceeded::from(code),
-                    header: Ipv4Repr {
-                        src_addr: ip_packet.src_addr(),
-                        dst_addr: ip_packet.dst_addr(),
-                        next_header: ip_packet.next_header(),
-                        payload_len: payload.len(),
-                        hop_limit: ip_packet.hop_limit(),
-                    },
+                    header,
+                    data: payload,
+                })
+            }
+
+            (Message::Redirect, code) => {
+                let (header, payload) = parse_quoted_datagram(packet.data())?;
+
+                Ok(Repr::Redirect {
+                    reason: Redirect::from(code),
+                    gateway: packet.redirect_gateway(),
+                    header,
+                    data: payload,
+                })
+            }
+
+            (Message::ParamProblem, code) => {
+                let (header, payload) = parse_quoted_datagram(packet.data())?;
+
+                Ok(Repr::ParamProblem {
+                    reason: ParamProblem::from(code),
+                    pointer: packet.param_problem_pointer(),
+                    header,
                     data: payload,
                 })
             }
 
+            (Message::Timestamp, 0) => Ok(Repr::TimestampRequest {
+                ident: packet.echo_ident(),
+                seq_no: packet.echo_seq_no(),
+                originate: packet.timestamp_originate(),
+                receive: packet.timestamp_receive(),
+                transmit: packet.timestamp_transmit(),
+            }),
+
+            (Message::TimestampReply, 0) => Ok(Repr::TimestampReply {
+                ident: packet.echo_ident(),
+                seq_no: packet.echo_seq_no(),
+                originate: packet.timestamp_originate(),
+                receive: packet.timestamp_receive(),
+                transmit: packet.timestamp_transmit(),
+            }),
+
+            (Message::AddressMaskRequest, 0) => Ok(Repr::AddressMaskRequest {
+                ident: packet.echo_ident(),
+                seq_no: packet.echo_seq_no(),
+            }),
+
+            (Message::AddressMaskReply, 0) => Ok(Repr::AddressMaskReply {
+                ident: packet.echo_ident(),
+                seq_no: packet.echo_seq_no(),
+                mask: packet.address_mask(),
+            }),
+
+            (Message::RouterAdvert, 0) => {
+                // Only the 2-word entries defined by RFC 1256 are supported.
+                if packet.router_advert_entry_size() != 2 {
+                    return Err(Error);
+                }
+                let len = packet.router_advert_num_addrs() as usize * RouterAddrs::ENTRY_LEN;
+                if packet.data().len() < len {
+                    return Err(Error);
+                }
+                Ok(Repr::RouterAdvert {
+                    lifetime: Duration::from_secs(packet.router_advert_lifetime() as u64),
+                    addrs: RouterAddrs::new_checked(&packet.data()[..len])?,
+                })
+            }
+
+            (Message::RouterSolicit, 0) => Ok(Repr::RouterSolicit),
+
             _ => Err(Error),
         }
     }
@@ -470,9 +859,60 @@ impl<'a> Repr<'a> {
                 field::ECHO_SEQNO.end + data.len()
             }
             &Repr::DstUnreachable { header, data, .. }
-            | &Repr::TimeExceeded { header, data, .. } => {
+            | &Repr::TimeExceeded { header, data, .. }
+            | &Repr::Redirect { header, data, .. }
+            | &Repr::ParamProblem { header, data, .. } => {
                 field::UNUSED.end + header.buffer_len() + data.len()
             }
+            Repr::TimestampRequest { .. } | Repr::TimestampReply { .. } => {
+                field::TIMESTAMP_TRANSMIT.end
+            }
+            Repr::AddressMaskRequest { .. } | Repr::AddressMaskReply { .. } => {
+                field::ADDRESS_MASK.end
+            }
+            Repr::RouterAdvert { addrs, .. } => field::HEADER_END + addrs.data.len(),
+            Repr::RouterSolicit => field::HEADER_END,
+        }
+    }
+
+    /// Return the message type this high-level representation is emitted as.
+    pub fn msg_type(&self) -> Message {
+        match self {
+            Repr::EchoRequest { .. } => Message::EchoRequest,
+            Repr::EchoReply { .. } => Message::EchoReply,
+            Repr::DstUnreachable { .. } => Message::DstUnreachable,
+            Repr::TimeExceeded { .. } => Message::TimeExceeded,
+            Repr::Redirect { .. } => Message::Redirect,
+            Repr::ParamProblem { .. } => Message::ParamProblem,
+            Repr::TimestampRequest { .. } => Message::Timestamp,
+            Repr::TimestampReply { .. } => Message::TimestampReply,
+            Repr::AddressMaskRequest { .. } => Message::AddressMaskRequest,
+            Repr::AddressMaskReply { .. } => Message::AddressMaskReply,
+            Repr::RouterAdvert { .. } => Message::RouterAdvert,
+            Repr::RouterSolicit => Message::RouterSolicit,
+        }
+    }
+
+    /// Return the identifier and sequence number of the echo request quoted by
+    /// a Destination Unreachable, Time Exceeded or Parameter Problem message.
+    ///
+    /// This is how an error triggered by a probe, e.g. a traceroute probe sent
+    /// with a small hop limit, is matched back to the probe.
+    pub fn quoted_echo_request(&self) -> Option<(u16, u16)> {
+        match self {
+            &Repr::DstUnreachable { header, data, .. }
+            | &Repr::TimeExceeded { header, data, .. }
+            | &Repr::ParamProblem { header, data, .. }
+                if header.next_header == IpProtocol::Icmp
+                    && data.len() >= field::ECHO_SEQNO.end =>
+            {
+                let packet = Packet::new_unchecked(data);
+                match packet.msg_type() {
+                    Message::EchoRequest => Some((packet.echo_ident(), packet.echo_seq_no())),
+                    _ => None,
+                }
+            }
+            _ => None,
         }
     }
 
@@ -537,6 +977,95 @@ impl<'a> Repr<'a> {
                 let payload = &mut ip_packet.into_inner()[header.buffer_len()..];
                 payload.copy_from_slice(data)
             }
+
+            Repr::Redirect {
+                reason,
+                gateway,
+                header,
+                data,
+            } => {
+                packet.set_msg_type(Message::Redirect);
+                packet.set_msg_code(reason.into());
+                packet.set_redirect_gateway(gateway);
+
+                let mut ip_packet = Ipv4Packet::new_unchecked(packet.data_mut());
+                header.emit(&mut ip_packet, checksum_caps);
+                let payload = &mut ip_packet.into_inner()[header.buffer_len()..];
+                payload.copy_from_slice(data)
+            }
+
+            Repr::ParamProblem {
+                reason,
+                pointer,
+                header,
+                data,
+            } => {
+                packet.set_msg_type(Message::ParamProblem);
+                packet.set_msg_code(reason.into());
+                packet.set_param_problem_pointer(pointer);
+
+                let mut ip_packet = Ipv4Packet::new_unchecked(packet.data_mut());
+                header.emit(&mut ip_packet, checksum_caps);
+                let payload = &mut ip_packet.into_inner()[header.buffer_len()..];
+                payload.copy_from_slice(data)
+            }
+
+            Repr::TimestampRequest {
+                ident,
+                seq_no,
+                originate,
+                receive,
+                transmit,
+            }
+            | Repr::TimestampReply {
+                ident,
+                seq_no,
+                originate,
+                receive,
+                transmit,
+            } => {
+                packet.set_msg_type(match self {
+                    Repr::TimestampRequest { .. } => Message::Timestamp,
+                    _ => Message::TimestampReply,
+                });
+                packet.set_echo_ident(ident);
+                packet.set_echo_seq_no(seq_no);
+                packet.set_timestamp_originate(originate);
+                packet.set_timestamp_receive(receive);
+                packet.set_timestamp_transmit(transmit);
+            }
+
+            Repr::AddressMaskRequest { ident, seq_no } => {
+                packet.set_msg_type(Message::AddressMaskRequest);
+                packet.set_echo_ident(ident);
+                packet.set_echo_seq_no(seq_no);
+                packet.set_address_mask(Ipv4Address::UNSPECIFIED);
+            }
+
+            Repr::AddressMaskReply {
+                ident,
+                seq_no,
+                mask,
+            } => {
+                packet.set_msg_type(Message::AddressMaskReply);
+                packet.set_echo_ident(ident);
+                packet.set_echo_seq_no(seq_no);
+                packet.set_address_mask(mask);
+            }
+
+            Repr::RouterAdvert { lifetime, addrs } => {
+                packet.set_msg_type(Message::RouterAdvert);
+                packet.set_router_advert_num_addrs(addrs.len() as u8);
+                packet.set_router_advert_entry_size((RouterAddrs::ENTRY_LEN / 4) as u8);
+                packet.set_router_advert_lifetime(lifetime.secs() as u16);
+                packet.data_mut()[..addrs.data.len()].copy_from_slice(addrs.data);
+            }
+
+            Repr::RouterSolicit => {
+                packet.set_msg_type(Message::RouterSolicit);
+                let data = packet.buffer.as_mut();
+                data[field::UNUSED].fill(0);
+            }
         }
 
         if checksum_caps.icmpv4.tx() {
@@ -563,6 +1092,12 @@ impl<'a, T: AsRef<[u8]> + ?Sized> fmt::Display for Packet<&'a T> {
                     Message::TimeExceeded => {
                         write!(f, " code={:?}", TimeExceeded::from(self.msg_code()))
                     }
+                    Message::Redirect => {
+                        write!(f, " code={:?}", Redirect::from(self.msg_code()))
+                    }
+                    Message::ParamProblem => {
+                        write!(f, " code={:?}", ParamProblem::from(self.msg_code()))
+                    }
                     _ => write!(f, " code={}", self.msg_code()),
                 }
             }
@@ -601,6 +1136,53 @@ impl<'a> fmt::Display for Repr<'a> {
             Repr::TimeExceeded { reason, .. } => {
                 write!(f, "ICMPv4 time exceeded ({reason})")
             }
+            Repr::Redirect {
+                reason, gateway, ..
+            } => {
+                write!(f, "ICMPv4 redirect ({reason}) gateway={gateway}")
+            }
+            Repr::ParamProblem {
+                reason, pointer, ..
+            } => {
+                write!(f, "ICMPv4 parameter problem ({reason}) pointer={pointer}")
+            }
+            Repr::TimestampRequest {
+                ident,
+                seq_no,
+                originate,
+                ..
+            } => write!(
+                f,
+                "ICMPv4 timestamp request id={ident} seq={seq_no} originate={originate}"
+            ),
+            Repr::TimestampReply {
+                ident,
+                seq_no,
+                originate,
+                receive,
+                transmit,
+            } => write!(
+                f,
+                "ICMPv4 timestamp reply id={ident} seq={seq_no} originate={originate} receive={receive} transmit={transmit}"
+            ),
+            Repr::AddressMaskRequest { ident, seq_no } => {
+                write!(f, "ICMPv4 address mask request id={ident} seq={seq_no}")
+            }
+            Repr::AddressMaskReply {
+                ident,
+                seq_no,
+                mask,
+            } => write!(
+                f,
+                "ICMPv4 address mask reply id={ident} seq={seq_no} mask={mask}"
+            ),
+            Repr::RouterAdvert { lifetime, addrs } => write!(
+                f,
+                "ICMPv4 router advertisement lifetime={} addrs={}",
+                lifetime,
+                addrs.len()
+            ),
+            Repr::RouterSolicit => write!(f, "ICMPv4 router solicitation"),
         }
     }
 }
@@ -620,7 +1202,10 @@ impl<T: AsRef<[u8]>> PrettyPrint for Packet<T> {
         write!(f, "{indent}{packet}")?;
 
         match packet.msg_type() {
-            Message::DstUnreachable | Message::TimeExceeded => {
+            Message::DstUnreachable
+            | Message::TimeExceeded
+            | Message::Redirect
+            | Message::ParamProblem => {
                 indent.increase(f)?;
                 super::Ipv4Packet::<&[u8]>::pretty_print(&packet.data(), f, indent)
             }
@@ -0,0 +1,643 @@
+use byteorder::{ByteOrder, NetworkEndian};
+use core::fmt;
+
+use super::{Error, Result};
+use crate::time::Duration;
+use crate::wire::ip::checksum;
+
+use crate::wire::{Ipv4Address, Ipv4AddressExt};
+
+enum_with_unknown! {
+    /// Internet Group Management Protocol message version/type.
+    pub enum Message(u8) {
+        /// Membership Query
+        MembershipQuery = 0x11,
+        /// Version 2 Membership Report
+        MembershipReportV2 = 0x16,
+        /// Leave Group
+        LeaveGroup = 0x17,
+        /// Version 1 Membership Report
+        MembershipReportV1 = 0x12,
+        /// Version 3 Membership Report
+        MembershipReportV3 = 0x22
+    }
+}
+
+enum_with_unknown! {
+    /// Internet Group Management Protocol v3 group record type.
+    pub enum RecordType(u8) {
+        /// MODE_IS_INCLUDE
+        ModeIsInclude = 0x01,
+        /// MODE_IS_EXCLUDE
+        ModeIsExclude = 0x02,
+        /// CHANGE_TO_INCLUDE_MODE
+        ChangeToInclude = 0x03,
+        /// CHANGE_TO_EXCLUDE_MODE
+        ChangeToExclude = 0x04,
+        /// ALLOW_NEW_SOURCES
+        AllowNewSources = 0x05,
+        /// BLOCK_OLD_SOURCES
+        BlockOldSources = 0x06
+    }
+}
+
+/// A read/write wrapper around an Internet Group Management Protocol packet buffer.
+#[derive(Debug)]
+pub struct Packet<T: AsRef<[u8]>> {
+    buffer: T,
+}
+
+mod field {
+    use crate::wire::field::*;
+
+    pub const TYPE: usize = 0;
+    pub const MAX_RESP_CODE: usize = 1;
+    pub const CHECKSUM: Field = 2..4;
+    pub const GROUP_ADDRESS: Field = 4..8;
+
+    // IGMPv3 membership query.
+    pub const QUERY_RESV_S_QRV: usize = 8;
+    pub const QUERY_QQIC: usize = 9;
+    pub const QUERY_NUM_SOURCES: Field = 10..12;
+
+    // IGMPv3 membership report.
+    pub const REPORT_NUM_RECORDS: Field = 6..8;
+
+    // First group record of an IGMPv3 membership report.
+    pub const RECORD_TYPE: usize = 8;
+    pub const RECORD_AUX_DATA_LEN: usize = 9;
+    pub const RECORD_NUM_SOURCES: Field = 10..12;
+    pub const RECORD_MCAST_ADDRESS: Field = 12..16;
+}
+
+impl fmt::Display for Message {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Message::MembershipQuery => write!(f, "membership query"),
+            Message::MembershipReportV2 => write!(f, "version 2 membership report"),
+            Message::LeaveGroup => write!(f, "leave group"),
+            Message::MembershipReportV1 => write!(f, "version 1 membership report"),
+            Message::MembershipReportV3 => write!(f, "version 3 membership report"),
+            Message::Unknown(id) => write!(f, "{id}"),
+        }
+    }
+}
+
+impl fmt::Display for RecordType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            RecordType::ModeIsInclude => write!(f, "MODE_IS_INCLUDE"),
+            RecordType::ModeIsExclude => write!(f, "MODE_IS_EXCLUDE"),
+            RecordType::ChangeToInclude => write!(f, "CHANGE_TO_INCLUDE_MODE"),
+            RecordType::ChangeToExclude => write!(f, "CHANGE_TO_EXCLUDE_MODE"),
+            RecordType::AllowNewSources => write!(f, "ALLOW_NEW_SOURCES"),
+            RecordType::BlockOldSources => write!(f, "BLOCK_OLD_SOURCES"),
+            RecordType::Unknown(id) => write!(f, "{id}"),
+        }
+    }
+}
+
+/// Internet Group Management Protocol v1/v2 defined in [RFC 2236], and v3 defined
+/// in [RFC 3376].
+///
+/// [RFC 2236]: https://tools.ietf.org/html/rfc2236
+/// [RFC 3376]: https://tools.ietf.org/html/rfc3376
+impl<T: AsRef<[u8]>> Packet<T> {
+    /// Imbue a raw octet buffer with IGMP packet structure.
+    pub const fn new_unchecked(buffer: T) -> Packet<T> {
+        Packet { buffer }
+    }
+
+    /// Shorthand for a combination of [new_unchecked] and [check_len].
+    ///
+    /// [new_unchecked]: #method.new_unchecked
+    /// [check_len]: #method.check_len
+    pub fn new_checked(buffer: T) -> Result<Packet<T>> {
+        let packet = Self::new_unchecked(buffer);
+        packet.check_len()?;
+        Ok(packet)
+    }
+
+    /// Ensure that no accessor method will panic if called.
+    /// Returns `Err(Error)` if the buffer is too short.
+    ///
+    /// The IGMPv3 accessors additionally require [is_v3_query] or
+    /// [is_v3_report] to hold.
+    ///
+    /// [is_v3_query]: #method.is_v3_query
+    /// [is_v3_report]: #method.is_v3_report
+    pub fn check_len(&self) -> Result<()> {
+        let len = self.buffer.as_ref().len();
+        if len < field::GROUP_ADDRESS.end {
+            Err(Error)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Consume the packet, returning the underlying buffer.
+    pub fn into_inner(self) -> T {
+        self.buffer
+    }
+
+    /// Return the message type field.
+    #[inline]
+    pub fn msg_type(&self) -> Message {
+        let data = self.buffer.as_ref();
+        Message::from(data[field::TYPE])
+    }
+
+    /// Return the maximum response time, using the encoding specified in
+    /// [RFC 3376]: 4.1.1. Max Resp Code.
+    ///
+    /// [RFC 3376]: https://tools.ietf.org/html/rfc3376
+    #[inline]
+    pub fn max_resp_code(&self) -> u8 {
+        let data = self.buffer.as_ref();
+        data[field::MAX_RESP_CODE]
+    }
+
+    /// Return the checksum field.
+    #[inline]
+    pub fn checksum(&self) -> u16 {
+        let data = self.buffer.as_ref();
+        NetworkEndian::read_u16(&data[field::CHECKSUM])
+    }
+
+    /// Return the source address field.
+    #[inline]
+    pub fn group_addr(&self) -> Ipv4Address {
+        let data = self.buffer.as_ref();
+        Ipv4Address::from_bytes(&data[field::GROUP_ADDRESS])
+    }
+
+    /// Query whether this is an IGMPv3 membership query.
+    ///
+    /// See [RFC 3376 § 7.1]: a query of at least 12 octets is an IGMPv3 query.
+    ///
+    /// [RFC 3376 § 7.1]: https://tools.ietf.org/html/rfc3376#section-7.1
+    pub fn is_v3_query(&self) -> bool {
+        self.msg_type() == Message::MembershipQuery
+            && self.buffer.as_ref().len() >= field::QUERY_NUM_SOURCES.end
+    }
+
+    /// Query whether this is an IGMPv3 membership report whose first group
+    /// record header is present.
+    pub fn is_v3_report(&self) -> bool {
+        self.msg_type() == Message::MembershipReportV3
+            && self.buffer.as_ref().len() >= field::RECORD_MCAST_ADDRESS.end
+    }
+
+    /// Return the Suppress Router-Side Processing flag of an IGMPv3 query.
+    #[inline]
+    pub fn query_s_flag(&self) -> bool {
+        let data = self.buffer.as_ref();
+        data[field::QUERY_RESV_S_QRV] & 0x08 != 0
+    }
+
+    /// Return the Querier's Robustness Variable of an IGMPv3 query.
+    #[inline]
+    pub fn query_qrv(&self) -> u8 {
+        let data = self.buffer.as_ref();
+        data[field::QUERY_RESV_S_QRV] & 0x07
+    }
+
+    /// Return the Querier's Query Interval Code of an IGMPv3 query.
+    #[inline]
+    pub fn query_qqic(&self) -> u8 {
+        let data = self.buffer.as_ref();
+        data[field::QUERY_QQIC]
+    }
+
+    /// Return the number of source addresses of an IGMPv3 query.
+    #[inline]
+    pub fn query_num_sources(&self) -> u16 {
+        let data = self.buffer.as_ref();
+        NetworkEndian::read_u16(&data[field::QUERY_NUM_SOURCES])
+    }
+
+    /// Return the number of group records of an IGMPv3 membership report.
+    #[inline]
+    pub fn report_num_records(&self) -> u16 {
+        let data = self.buffer.as_ref();
+        NetworkEndian::read_u16(&data[field::REPORT_NUM_RECORDS])
+    }
+
+    /// Return the type of the first group record of an IGMPv3 membership report.
+    #[inline]
+    pub fn record_type(&self) -> RecordType {
+        let data = self.buffer.as_ref();
+        RecordType::from(data[field::RECORD_TYPE])
+    }
+
+    /// Return the auxiliary data length, in 32-bit words, of the first group record
+    /// of an IGMPv3 membership report.
+    #[inline]
+    pub fn record_aux_data_len(&self) -> u8 {
+        let data = self.buffer.as_ref();
+        data[field::RECORD_AUX_DATA_LEN]
+    }
+
+    /// Return the number of sources of the first group record of an IGMPv3
+    /// membership report.
+    #[inline]
+    pub fn record_num_sources(&self) -> u16 {
+        let data = self.buffer.as_ref();
+        NetworkEndian::read_u16(&data[field::RECORD_NUM_SOURCES])
+    }
+
+    /// Return the multicast address of the first group record of an IGMPv3
+    /// membership report.
+    #[inline]
+    pub fn record_mcast_addr(&self) -> Ipv4Address {
+        let data = self.buffer.as_ref();
+        Ipv4Address::from_bytes(&data[field::RECORD_MCAST_ADDRESS])
+    }
+
+    /// Validate the header checksum.
+    pub fn verify_checksum(&self) -> bool {
+        let data = self.buffer.as_ref();
+        checksum::data(data) == !0
+    }
+}
+
+impl<T: AsRef<[u8]> + AsMut<[u8]>> Packet<T> {
+    /// Set the message type field.
+    #[inline]
+    pub fn set_msg_type(&mut self, value: Message) {
+        let data = self.buffer.as_mut();
+        data[field::TYPE] = value.into()
+    }
+
+    /// Set the maximum response time, using the encoding specified in
+    /// [RFC 3376]: 4.1.1. Max Resp Code.
+    #[inline]
+    pub fn set_max_resp_code(&mut self, value: u8) {
+        let data = self.buffer.as_mut();
+        data[field::MAX_RESP_CODE] = value;
+    }
+
+    /// Set the checksum field.
+    #[inline]
+    pub fn set_checksum(&mut self, value: u16) {
+        let data = self.buffer.as_mut();
+        NetworkEndian::write_u16(&mut data[field::CHECKSUM], value)
+    }
+
+    /// Set the group address field
+    #[inline]
+    pub fn set_group_address(&mut self, addr: Ipv4Address) {
+        let data = self.buffer.as_mut();
+        data[field::GROUP_ADDRESS].copy_from_slice(&addr.octets());
+    }
+
+    /// Set the Resv, S and QRV fields of an IGMPv3 query.
+    #[inline]
+    pub fn set_query_s_qrv(&mut self, s_flag: bool, qrv: u8) {
+        let data = self.buffer.as_mut();
+        data[field::QUERY_RESV_S_QRV] = ((s_flag as u8) << 3) | (qrv & 0x07);
+    }
+
+    /// Set the Querier's Query Interval Code of an IGMPv3 query.
+    #[inline]
+    pub fn set_query_qqic(&mut self, value: u8) {
+        let data = self.buffer.as_mut();
+        data[field::QUERY_QQIC] = value;
+    }
+
+    /// Set the number of source addresses of an IGMPv3 query.
+    #[inline]
+    pub fn set_query_num_sources(&mut self, value: u16) {
+        let data = self.buffer.as_mut();
+        NetworkEndian::write_u16(&mut data[field::QUERY_NUM_SOURCES], value)
+    }
+
+    /// Set the number of group records of an IGMPv3 membership report, clearing
+    /// the reserved field that precedes it.
+    #[inline]
+    pub fn set_report_num_records(&mut self, value: u16) {
+        let data = self.buffer.as_mut();
+        data[field::GROUP_ADDRESS.start..field::REPORT_NUM_RECORDS.start].fill(0);
+        NetworkEndian::write_u16(&mut data[field::REPORT_NUM_RECORDS], value)
+    }
+
+    /// Set the type of the first group record of an IGMPv3 membership report.
+    #[inline]
+    pub fn set_record_type(&mut self, value: RecordType) {
+        let data = self.buffer.as_mut();
+        data[field::RECORD_TYPE] = value.into()
+    }
+
+    /// Set the auxiliary data length of the first group record of an IGMPv3
+    /// membership report.
+    #[inline]
+    pub fn set_record_aux_data_len(&mut self, value: u8) {
+        let data = self.buffer.as_mut();
+        data[field::RECORD_AUX_DATA_LEN] = value
+    }
+
+    /// Set the number of sources of the first group record of an IGMPv3
+    /// membership report.
+    #[inline]
+    pub fn set_record_num_sources(&mut self, value: u16) {
+        let data = self.buffer.as_mut();
+        NetworkEndian::write_u16(&mut data[field::RECORD_NUM_SOURCES], value)
+    }
+
+    /// Set the multicast address of the first group record of an IGMPv3
+    /// membership report.
+    #[inline]
+    pub fn set_record_mcast_addr(&mut self, addr: Ipv4Address) {
+        let data = self.buffer.as_mut();
+        data[field::RECORD_MCAST_ADDRESS].copy_from_slice(&addr.octets());
+    }
+
+    /// Compute and fill in the header checksum.
+    pub fn fill_checksum(&mut self) {
+        self.set_checksum(0);
+        let checksum = {
+            let data = self.buffer.as_ref();
+            !checksum::data(data)
+        };
+        self.set_checksum(checksum)
+    }
+}
+
+/// A high-level representation of an Internet Group Management Protocol header.
+///
+/// IGMPv3 membership reports are only representable when they carry a single
+/// group record without sources, which is all a host in EXCLUDE mode ever sends.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Repr {
+    MembershipQuery {
+        max_resp_time: Duration,
+        group_addr: Ipv4Address,
+        version: IgmpVersion,
+    },
+    MembershipReport {
+        group_addr: Ipv4Address,
+        version: IgmpVersion,
+    },
+    MembershipReportV3 {
+        group_addr: Ipv4Address,
+        record_type: RecordType,
+    },
+    LeaveGroup {
+        group_addr: Ipv4Address,
+    },
+}
+
+/// Type of IGMP membership report version
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+pub enum IgmpVersion {
+    /// IGMPv1
+    Version1,
+    /// IGMPv2
+    Version2,
+    /// IGMPv3
+    Version3,
+}
+
+impl Repr {
+    /// Parse an Internet Group Management Protocol packet and return
+    /// a high-level representation.
+    pub fn parse<T>(packet: &Packet<&T>) -> Result<Repr>
+    where
+        T: AsRef<[u8]> + ?Sized,
+    {
+        packet.check_len()?;
+
+        // An IGMPv3 report carries the number of group records where the
+        // other messages carry the group address.
+        if packet.msg_type() == Message::MembershipReportV3 {
+            if !packet.is_v3_report()
+                || packet.report_num_records() != 1
+                || packet.record_num_sources() != 0
+                || packet.record_aux_data_len() != 0
+            {
+                return Err(Error);
+            }
+            let group_addr = packet.record_mcast_addr();
+            if !group_addr.is_multicast() {
+                return Err(Error);
+            }
+            return Ok(Repr::MembershipReportV3 {
+                group_addr,
+                record_type: packet.record_type(),
+            });
+        }
+
+        // Check if the address is 0.0.0.0 or multicast
+        let addr = packet.group_addr();
+        if !addr.is_unspecified() && !addr.is_multicast() {
+            return Err(Error);
+        }
+
+        // construct a packet based on the Type field
+        match packet.msg_type() {
+            Message::MembershipQuery => {
+                let max_resp_time = max_resp_code_to_duration(packet.max_resp_code());
+                // See RFC 3376: 7.1. Query Version Distinctions
+                let version = if packet.is_v3_query() {
+                    IgmpVersion::Version3
+                } else if packet.max_resp_code() == 0 {
+                    IgmpVersion::Version1
+                } else {
+                    IgmpVersion::Version2
+                };
+                Ok(Repr::MembershipQuery {
+                    max_resp_time,
+                    group_addr: addr,
+                    version,
+                })
+            }
+            Message::MembershipReportV2 => Ok(Repr::MembershipReport {
+                group_addr: packet.group_addr(),
+                version: IgmpVersion::Version2,
+            }),
+            Message::LeaveGroup => Ok(Repr::LeaveGroup {
+                group_addr: packet.group_addr(),
+            }),
+            Message::MembershipReportV1 => {
+                // for backwards compatibility with IGMPv1
+                Ok(Repr::MembershipReport {
+                    group_addr: packet.group_addr(),
+                    version: IgmpVersion::Version1,
+                })
+            }
+            _ => Err(Error),
+        }
+    }
+
+    /// Return the length of a packet that will be emitted from this high-level representation.
+    pub const fn buffer_len(&self) -> usize {
+        match self {
+            Repr::MembershipQuery {
+                version: IgmpVersion::Version3,
+                ..
+            } => field::QUERY_NUM_SOURCES.end,
+            Repr::MembershipReport {
+                version: IgmpVersion::Version3,
+                ..
+            }
+            | Repr::MembershipReportV3 { .. } => field::RECORD_MCAST_ADDRESS.end,
+            _ => field::GROUP_ADDRESS.end,
+        }
+    }
+
+    /// Emit a high-level representation into an Internet Group Management Protocol packet.
+    ///
+    /// A `MembershipReport` with version `Version3` is emitted as a current-state
+    /// report, i.e. a single `MODE_IS_EXCLUDE` group record.
+    pub fn emit<T>(&self, packet: &mut Packet<&mut T>)
+    where
+        T: AsRef<[u8]> + AsMut<[u8]> + ?Sized,
+    {
+        match *self {
+            Repr::MembershipQuery {
+                max_resp_time,
+                group_addr,
+                version,
+            } => {
+                packet.set_msg_type(Message::MembershipQuery);
+                match version {
+                    IgmpVersion::Version1 => packet.set_max_resp_code(0),
+                    IgmpVersion::Version2 => {
+                        packet.set_max_resp_code(duration_to_max_resp_code(max_resp_time))
+                    }
+                    IgmpVersion::Version3 => {
+                        packet.set_max_resp_code(duration_to_max_resp_code(max_resp_time));
+                        packet.set_query_s_qrv(false, 0);
+                        packet.set_query_qqic(0);
+                        packet.set_query_num_sources(0);
+                    }
+                }
+                packet.set_group_address(group_addr);
+            }
+            Repr::MembershipReport {
+                group_addr,
+                version: IgmpVersion::Version3,
+            } => emit_v3_report(packet, group_addr, RecordType::ModeIsExclude),
+            Repr::MembershipReport {
+                group_addr,
+                version,
+            } => {
+                match version {
+                    IgmpVersion::Version1 => packet.set_msg_type(Message::MembershipReportV1),
+                    _ => packet.set_msg_type(Message::MembershipReportV2),
+                };
+                packet.set_max_resp_code(0);
+                packet.set_group_address(group_addr);
+            }
+            Repr::MembershipReportV3 {
+                group_addr,
+                record_type,
+            } => emit_v3_report(packet, group_addr, record_type),
+            Repr::LeaveGroup { group_addr } => {
+                packet.set_msg_type(Message::LeaveGroup);
+                packet.set_max_resp_code(0);
+                packet.set_group_address(group_addr);
+            }
+        }
+
+        packet.fill_checksum()
+    }
+}
+
+fn emit_v3_report<T>(packet: &mut Packet<&mut T>, group_addr: Ipv4Address, record_type: RecordType)
+where
+    T: AsRef<[u8]> + AsMut<[u8]> + ?Sized,
+{
+    packet.set_msg_type(Message::MembershipReportV3);
+    packet.set_max_resp_code(0);
+    packet.set_report_num_records(1);
+    packet.set_record_type(record_type);
+    packet.set_record_aux_data_len(0);
+    packet.set_record_num_sources(0);
+    packet.set_record_mcast_addr(group_addr);
+}
+
+fn max_resp_code_to_duration(value: u8) -> Duration {
+    let value: u64 = value.into();
+    let decisecs = if value < 128 {
+        value
+    } else {
+        let mant = value & 0xF;
+        let exp = (value >> 4) & 0x7;
+        (mant | 0x10) << (exp + 3)
+    };
+    Duration::from_millis(decisecs * 100)
+}
+
+const fn duration_to_max_resp_code(duration: Duration) -> u8 {
+    let decisecs = duration.total_millis() / 100;
+    if decisecs < 128 {
+        decisecs as u8
+    } else if decisecs < 31744 {
+        let mut mant = decisecs >> 3;
+        let mut exp = 0u8;
+        while mant > 0x1F && exp < 0x8 {
+            mant >>= 1;
+            exp += 1;
+        }
+        0x80 | (exp << 4) | (mant as u8 & 0xF)
+    } else {
+        0xFF
+    }
+}
+
+impl<T: AsRef<[u8]> + ?Sized> fmt::Display for Packet<&T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match Repr::parse(self) {
+            Ok(repr) => write!(f, "{repr}"),
+            Err(err) => write!(f, "IGMP ({err}) type={}", self.msg_type()),
+        }
+    }
+}
+
+impl fmt::Display for Repr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Repr::MembershipQuery {
+                max_resp_time,
+                group_addr,
+                version,
+            } => write!(
+                f,
+                "IGMP membership query max_resp_time={max_resp_time} group_addr={group_addr} version={version:?}"
+            ),
+            Repr::MembershipReport {
+                group_addr,
+                version,
+            } => write!(
+                f,
+                "IGMP membership report group_addr={group_addr} version={version:?}"
+            ),
+            Repr::MembershipReportV3 {
+                group_addr,
+                record_type,
+            } => write!(
+                f,
+                "IGMP membership report group_addr={group_addr} record_type={record_type} version=Version3"
+            ),
+            Repr::LeaveGroup { group_addr } => {
+                write!(f, "IGMP leave group group_addr={group_addr}")
+            }
+        }
+    }
+}
+
+use crate::wire::pretty_print::{PrettyIndent, PrettyPrint};
+
+impl<T: AsRef<[u8]>> PrettyPrint for Packet<T> {
+    fn pretty_print(
+        buffer: &dyn AsRef<[u8]>,
+        f: &mut fmt::Formatter,
+        indent: &mut PrettyIndent,
+    ) -> fmt::Result {
+        match Packet::new_checked(buffer) {
+            Err(err) => write!(f, "{indent}({err})"),
+            Ok(packet) => write!(f, "{indent}{packet}"),
+        }
+    }
+}
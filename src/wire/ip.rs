@@ -4,11 +4,13 @@ use core::fmt;
 use super::{Error, Result};
 use crate::phy::ChecksumCapabilities;
 use crate::wire::{Ipv4Address, Ipv4AddressExt, Ipv4Cidr, Ipv4Packet, Ipv4Repr};
+use crate::wire::{Ipv6Address, Ipv6AddressExt, Ipv6Cidr, Ipv6Packet, Ipv6Repr};
 
 /// Internet protocol version.
 #[derive(Debug, Hash, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
 pub enum Version {
     Ipv4,
+    Ipv6,
 }
 
 impl Version {
@@ -19,6 +21,7 @@ impl Version {
     pub const fn of_packet(data: &[u8]) -> Result<Version> {
         match data[0] >> 4 {
             4 => Ok(Version::Ipv4),
+            6 => Ok(Version::Ipv6),
             _ => Err(Error),
         }
     }
@@ -28,6 +31,7 @@ impl fmt::Display for Version {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
             Version::Ipv4 => write!(f, "IPv4"),
+            Version::Ipv6 => write!(f, "IPv6"),
         }
     }
 }
@@ -75,6 +79,8 @@ impl fmt::Display for Protocol {
 pub enum Address {
     /// An IPv4 address.
     Ipv4(Ipv4Address),
+    /// An IPv6 address.
+    Ipv6(Ipv6Address),
 }
 
 impl Address {
@@ -83,10 +89,26 @@ impl Address {
         Address::Ipv4(Ipv4Address::new(a0, a1, a2, a3))
     }
 
+    /// Create an address wrapping an IPv6 address with the given octets.
+    #[allow(clippy::too_many_arguments)]
+    pub const fn v6(
+        a0: u16,
+        a1: u16,
+        a2: u16,
+        a3: u16,
+        a4: u16,
+        a5: u16,
+        a6: u16,
+        a7: u16,
+    ) -> Address {
+        Address::Ipv6(Ipv6Address::new(a0, a1, a2, a3, a4, a5, a6, a7))
+    }
+
     /// Return the protocol version.
     pub const fn version(&self) -> Version {
         match self {
             Address::Ipv4(_) => Version::Ipv4,
+            Address::Ipv6(_) => Version::Ipv6,
         }
     }
 
@@ -94,6 +116,7 @@ impl Address {
     pub fn is_unicast(&self) -> bool {
         match self {
             Address::Ipv4(addr) => addr.x_is_unicast(),
+            Address::Ipv6(addr) => addr.x_is_unicast(),
         }
     }
 
@@ -101,6 +124,7 @@ impl Address {
     pub const fn is_multicast(&self) -> bool {
         match self {
             Address::Ipv4(addr) => addr.is_multicast(),
+            Address::Ipv6(addr) => addr.is_multicast(),
         }
     }
 
@@ -108,6 +132,7 @@ impl Address {
     pub fn is_broadcast(&self) -> bool {
         match self {
             Address::Ipv4(addr) => addr.is_broadcast(),
+            Address::Ipv6(_) => false,
         }
     }
 
@@ -115,6 +140,15 @@ impl Address {
     pub fn is_unspecified(&self) -> bool {
         match self {
             Address::Ipv4(addr) => addr.is_unspecified(),
+            Address::Ipv6(addr) => addr.is_unspecified(),
+        }
+    }
+
+    /// Query whether the address falls into the "loopback" range.
+    pub fn is_loopback(&self) -> bool {
+        match self {
+            Address::Ipv4(addr) => addr.is_loopback(),
+            Address::Ipv6(addr) => addr.is_loopback(),
         }
     }
 
@@ -123,6 +157,16 @@ impl Address {
     pub fn prefix_len(&self) -> Option<u8> {
         match self {
             Address::Ipv4(addr) => addr.prefix_len(),
+            Address::Ipv6(addr) => addr.prefix_len(),
+        }
+    }
+}
+
+impl From<::core::net::IpAddr> for Address {
+    fn from(x: ::core::net::IpAddr) -> Address {
+        match x {
+            ::core::net::IpAddr::V4(ipv4) => Address::Ipv4(ipv4),
+            ::core::net::IpAddr::V6(ipv6) => Address::Ipv6(ipv6),
         }
     }
 }
@@ -131,6 +175,7 @@ impl From<Address> for ::core::net::IpAddr {
     fn from(x: Address) -> ::core::net::IpAddr {
         match x {
             Address::Ipv4(ipv4) => ::core::net::IpAddr::V4(ipv4),
+            Address::Ipv6(ipv6) => ::core::net::IpAddr::V6(ipv6),
         }
     }
 }
@@ -141,10 +186,17 @@ impl From<Ipv4Address> for Address {
     }
 }
 
+impl From<Ipv6Address> for Address {
+    fn from(addr: Ipv6Address) -> Self {
+        Address::Ipv6(addr)
+    }
+}
+
 impl fmt::Display for Address {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
             Address::Ipv4(addr) => write!(f, "{addr}"),
+            Address::Ipv6(addr) => write!(f, "{addr}"),
         }
     }
 }
@@ -154,6 +206,7 @@ impl fmt::Display for Address {
 #[derive(Debug, Hash, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
 pub enum Cidr {
     Ipv4(Ipv4Cidr),
+    Ipv6(Ipv6Cidr),
 }
 
 impl Cidr {
@@ -164,6 +217,7 @@ impl Cidr {
     pub fn new(addr: Address, prefix_len: u8) -> Cidr {
         match addr {
             Address::Ipv4(addr) => Cidr::Ipv4(Ipv4Cidr::new(addr, prefix_len)),
+            Address::Ipv6(addr) => Cidr::Ipv6(Ipv6Cidr::new(addr, prefix_len)),
         }
     }
 
@@ -171,6 +225,7 @@ impl Cidr {
     pub const fn address(&self) -> Address {
         match *self {
             Cidr::Ipv4(cidr) => Address::Ipv4(cidr.address()),
+            Cidr::Ipv6(cidr) => Address::Ipv6(cidr.address()),
         }
     }
 
@@ -178,6 +233,7 @@ impl Cidr {
     pub const fn prefix_len(&self) -> u8 {
         match *self {
             Cidr::Ipv4(cidr) => cidr.prefix_len(),
+            Cidr::Ipv6(cidr) => cidr.prefix_len(),
         }
     }
 
@@ -186,7 +242,7 @@ impl Cidr {
     pub fn contains_addr(&self, addr: &Address) -> bool {
         match (self, addr) {
             (Cidr::Ipv4(cidr), Address::Ipv4(addr)) => cidr.contains_addr(addr),
-            #[allow(unreachable_patterns)]
+            (Cidr::Ipv6(cidr), Address::Ipv6(addr)) => cidr.contains_addr(addr),
             _ => false,
         }
     }
@@ -196,7 +252,7 @@ impl Cidr {
     pub fn contains_subnet(&self, subnet: &Cidr) -> bool {
         match (self, subnet) {
             (Cidr::Ipv4(cidr), Cidr::Ipv4(other)) => cidr.contains_subnet(other),
-            #[allow(unreachable_patterns)]
+            (Cidr::Ipv6(cidr), Cidr::Ipv6(other)) => cidr.contains_subnet(other),
             _ => false,
         }
     }
@@ -208,10 +264,17 @@ impl From<Ipv4Cidr> for Cidr {
     }
 }
 
+impl From<Ipv6Cidr> for Cidr {
+    fn from(addr: Ipv6Cidr) -> Self {
+        Cidr::Ipv6(addr)
+    }
+}
+
 impl fmt::Display for Cidr {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
             Cidr::Ipv4(cidr) => write!(f, "{cidr}"),
+            Cidr::Ipv6(cidr) => write!(f, "{cidr}"),
         }
     }
 }
@@ -235,6 +298,15 @@ impl Endpoint {
     }
 }
 
+impl From<::core::net::SocketAddr> for Endpoint {
+    fn from(x: ::core::net::SocketAddr) -> Endpoint {
+        Endpoint {
+            addr: x.ip().into(),
+            port: x.port(),
+        }
+    }
+}
+
 impl From<::core::net::SocketAddrV4> for Endpoint {
     fn from(x: ::core::net::SocketAddrV4) -> Endpoint {
         Endpoint {
@@ -244,6 +316,15 @@ impl From<::core::net::SocketAddrV4> for Endpoint {
     }
 }
 
+impl From<::core::net::SocketAddrV6> for Endpoint {
+    fn from(x: ::core::net::SocketAddrV6) -> Endpoint {
+        Endpoint {
+            addr: (*x.ip()).into(),
+            port: x.port(),
+        }
+    }
+}
+
 impl fmt::Display for Endpoint {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "{}:{}", self.addr, self.port)
@@ -278,6 +359,15 @@ impl ListenEndpoint {
     }
 }
 
+impl From<::core::net::SocketAddr> for ListenEndpoint {
+    fn from(x: ::core::net::SocketAddr) -> ListenEndpoint {
+        ListenEndpoint {
+            addr: Some(x.ip().into()),
+            port: x.port(),
+        }
+    }
+}
+
 impl From<::core::net::SocketAddrV4> for ListenEndpoint {
     fn from(x: ::core::net::SocketAddrV4) -> ListenEndpoint {
         ListenEndpoint {
@@ -287,6 +377,15 @@ impl From<::core::net::SocketAddrV4> for ListenEndpoint {
     }
 }
 
+impl From<::core::net::SocketAddrV6> for ListenEndpoint {
+    fn from(x: ::core::net::SocketAddrV6) -> ListenEndpoint {
+        ListenEndpoint {
+            addr: Some((*x.ip()).into()),
+            port: x.port(),
+        }
+    }
+}
+
 impl fmt::Display for ListenEndpoint {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         if let Some(addr) = self.addr {
@@ -328,6 +427,7 @@ impl<T: Into<Address>> From<(T, u16)> for ListenEndpoint {
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Repr {
     Ipv4(Ipv4Repr),
+    Ipv6(Ipv6Repr),
 }
 
 impl From<Ipv4Repr> for Repr {
@@ -336,6 +436,12 @@ impl From<Ipv4Repr> for Repr {
     }
 }
 
+impl From<Ipv6Repr> for Repr {
+    fn from(repr: Ipv6Repr) -> Repr {
+        Repr::Ipv6(repr)
+    }
+}
+
 impl Repr {
     /// Create a new IpRepr, choosing the right IP version for the src/dst addrs.
     ///
@@ -357,7 +463,13 @@ impl Repr {
                 payload_len,
                 hop_limit,
             }),
-            #[allow(unreachable_patterns)]
+            (Address::Ipv6(src_addr), Address::Ipv6(dst_addr)) => Self::Ipv6(Ipv6Repr {
+                src_addr,
+                dst_addr,
+                next_header,
+                payload_len,
+                hop_limit,
+            }),
             _ => panic!("IP version mismatch: src={src_addr:?} dst={dst_addr:?}"),
         }
     }
@@ -366,6 +478,7 @@ impl Repr {
     pub const fn version(&self) -> Version {
         match *self {
             Repr::Ipv4(_) => Version::Ipv4,
+            Repr::Ipv6(_) => Version::Ipv6,
         }
     }
 
@@ -373,6 +486,7 @@ impl Repr {
     pub const fn src_addr(&self) -> Address {
         match *self {
             Repr::Ipv4(repr) => Address::Ipv4(repr.src_addr),
+            Repr::Ipv6(repr) => Address::Ipv6(repr.src_addr),
         }
     }
 
@@ -380,6 +494,7 @@ impl Repr {
     pub const fn dst_addr(&self) -> Address {
         match *self {
             Repr::Ipv4(repr) => Address::Ipv4(repr.dst_addr),
+            Repr::Ipv6(repr) => Address::Ipv6(repr.dst_addr),
         }
     }
 
@@ -387,6 +502,7 @@ impl Repr {
     pub const fn next_header(&self) -> Protocol {
         match *self {
             Repr::Ipv4(repr) => repr.next_header,
+            Repr::Ipv6(repr) => repr.next_header,
         }
     }
 
@@ -394,6 +510,7 @@ impl Repr {
     pub const fn payload_len(&self) -> usize {
         match *self {
             Repr::Ipv4(repr) => repr.payload_len,
+            Repr::Ipv6(repr) => repr.payload_len,
         }
     }
 
@@ -401,6 +518,7 @@ impl Repr {
     pub fn set_payload_len(&mut self, length: usize) {
         match self {
             Repr::Ipv4(Ipv4Repr { payload_len, .. }) => *payload_len = length,
+            Repr::Ipv6(Ipv6Repr { payload_len, .. }) => *payload_len = length,
         }
     }
 
@@ -408,6 +526,7 @@ impl Repr {
     pub const fn hop_limit(&self) -> u8 {
         match *self {
             Repr::Ipv4(Ipv4Repr { hop_limit, .. }) => hop_limit,
+            Repr::Ipv6(Ipv6Repr { hop_limit, .. }) => hop_limit,
         }
     }
 
@@ -415,6 +534,7 @@ impl Repr {
     pub const fn header_len(&self) -> usize {
         match *self {
             Repr::Ipv4(repr) => repr.buffer_len(),
+            Repr::Ipv6(repr) => repr.buffer_len(),
         }
     }
 
@@ -426,6 +546,7 @@ impl Repr {
     ) {
         match *self {
             Repr::Ipv4(repr) => repr.emit(&mut Ipv4Packet::new_unchecked(buffer), _checksum_caps),
+            Repr::Ipv6(repr) => repr.emit(&mut Ipv6Packet::new_unchecked(buffer)),
         }
     }
 
@@ -506,6 +627,23 @@ pub mod checksum {
         ])
     }
 
+    pub fn pseudo_header_v6(
+        src_addr: &Ipv6Address,
+        dst_addr: &Ipv6Address,
+        next_header: Protocol,
+        length: u32,
+    ) -> u16 {
+        let mut proto_len = [0u8; 4];
+        proto_len[1] = next_header.into();
+        NetworkEndian::write_u16(&mut proto_len[2..4], length as u16);
+
+        combine(&[
+            data(&src_addr.octets()),
+            data(&dst_addr.octets()),
+            data(&proto_len[..]),
+        ])
+    }
+
     pub fn pseudo_header(
         src_addr: &Address,
         dst_addr: &Address,
@@ -516,7 +654,9 @@ pub mod checksum {
             (Address::Ipv4(src_addr), Address::Ipv4(dst_addr)) => {
                 pseudo_header_v4(src_addr, dst_addr, next_header, length)
             }
-            #[allow(unreachable_patterns)]
+            (Address::Ipv6(src_addr), Address::Ipv6(dst_addr)) => {
+                pseudo_header_v6(src_addr, dst_addr, next_header, length)
+            }
             _ => unreachable!(),
         }
     }
@@ -542,7 +682,7 @@ pub fn pretty_print_ip_payload<T: Into<Repr>>(
     use super::pretty_print::PrettyPrint;
     #[allow(unused)]
     use crate::wire::ip::checksum::format_checksum;
-    use crate::wire::Icmpv4Packet;
+    use crate::wire::{Icmpv4Packet, Icmpv6Packet, IgmpPacket};
     // use crate::wire::{TcpPacket, TcpRepr, UdpPacket, UdpRepr};
 
     #[allow(unused)]
@@ -553,6 +693,14 @@ pub fn pretty_print_ip_payload<T: Into<Repr>>(
             indent.increase(f)?;
             Icmpv4Packet::<&[u8]>::pretty_print(&payload, f, indent)
         }
+        Protocol::Icmpv6 => {
+            indent.increase(f)?;
+            Icmpv6Packet::<&[u8]>::pretty_print(&payload, f, indent)
+        }
+        Protocol::Igmp => {
+            indent.increase(f)?;
+            IgmpPacket::<&[u8]>::pretty_print(&payload, f, indent)
+        }
         // Protocol::Udp => {
         //     indent.increase(f)?;
         //     match UdpPacket::<&[u8]>::new_checked(payload) {
@@ -32,6 +32,9 @@ pub const MULTICAST_ALL_SYSTEMS: Address = Address::new(224, 0, 0, 1);
 /// All multicast-capable routers
 pub const MULTICAST_ALL_ROUTERS: Address = Address::new(224, 0, 0, 2);
 
+/// All IGMPv3-capable multicast routers
+pub const MULTICAST_ALL_IGMPV3_ROUTERS: Address = Address::new(224, 0, 0, 22);
+
 #[derive(Debug, Eq, PartialEq, Ord, PartialOrd, Clone, Copy)]
 pub struct Key {
     id: u16,
@@ -87,6 +87,12 @@ pub use self::ethernet::{
     Repr as EthernetRepr, HEADER_LEN as ETHERNET_HEADER_LEN,
 };
 
+mod vlan;
+pub use self::vlan::{
+    Packet as VlanPacket, Repr as VlanRepr, HEADER_LEN as VLAN_HEADER_LEN,
+    VID_PRIORITY_TAGGED as VLAN_VID_PRIORITY_TAGGED, VID_RESERVED as VLAN_VID_RESERVED,
+};
+
 mod arp;
 pub use self::arp::{
     Hardware as ArpHardware, Operation as ArpOperation, Packet as ArpPacket, Repr as ArpRepr,
@@ -104,19 +110,76 @@ pub(crate) use self::ipv4::AddressExt as Ipv4AddressExt;
 pub use self::ipv4::{
     Address as Ipv4Address, Cidr as Ipv4Cidr, Key as Ipv4FragKey, Packet as Ipv4Packet,
     Repr as Ipv4Repr, HEADER_LEN as IPV4_HEADER_LEN, MIN_MTU as IPV4_MIN_MTU,
+    MULTICAST_ALL_IGMPV3_ROUTERS as IPV4_MULTICAST_ALL_IGMPV3_ROUTERS,
     MULTICAST_ALL_ROUTERS as IPV4_MULTICAST_ALL_ROUTERS,
     MULTICAST_ALL_SYSTEMS as IPV4_MULTICAST_ALL_SYSTEMS,
 };
 
+pub(crate) mod ipv6;
+pub use self::ipv6::{
+    Address as Ipv6Address, Cidr as Ipv6Cidr, Packet as Ipv6Packet, Repr as Ipv6Repr,
+    HEADER_LEN as IPV6_HEADER_LEN,
+    LINK_LOCAL_ALL_MLDV2_ROUTERS as IPV6_LINK_LOCAL_ALL_MLDV2_ROUTERS,
+    LINK_LOCAL_ALL_NODES as IPV6_LINK_LOCAL_ALL_NODES,
+    LINK_LOCAL_ALL_ROUTERS as IPV6_LINK_LOCAL_ALL_ROUTERS, MIN_MTU as IPV6_MIN_MTU,
+};
+pub(crate) use self::ipv6::{AddressExt as Ipv6AddressExt, MulticastScope as Ipv6MulticastScope};
+
+mod ipv6option;
+pub use self::ipv6option::{
+    FailureType as Ipv6OptionFailureType, Ipv6Option, Ipv6OptionsIterator, Repr as Ipv6OptionRepr,
+    RouterAlert as Ipv6OptionRouterAlert, Type as Ipv6OptionType,
+};
+
+mod ipv6ext_header;
+pub use self::ipv6ext_header::{Header as Ipv6ExtHeader, Repr as Ipv6ExtHeaderRepr};
+
+mod ipv6fragment;
+pub use self::ipv6fragment::{Header as Ipv6FragmentHeader, Repr as Ipv6FragmentRepr};
+
+mod ipv6hbh;
+pub use self::ipv6hbh::{Header as Ipv6HopByHopHeader, Repr as Ipv6HopByHopRepr};
+
+mod ipv6routing;
+pub use self::ipv6routing::{
+    Header as Ipv6RoutingHeader, Repr as Ipv6RoutingRepr, Type as Ipv6RoutingType,
+};
+
 mod icmpv4;
 pub use self::icmpv4::{
     DstUnreachable as Icmpv4DstUnreachable, Message as Icmpv4Message, Packet as Icmpv4Packet,
     ParamProblem as Icmpv4ParamProblem, Redirect as Icmpv4Redirect, Repr as Icmpv4Repr,
-    TimeExceeded as Icmpv4TimeExceeded,
+    RouterAddrs as Icmpv4RouterAddrs, TimeExceeded as Icmpv4TimeExceeded,
+};
+
+mod igmp;
+pub use self::igmp::{
+    IgmpVersion, Packet as IgmpPacket, RecordType as IgmpRecordType, Repr as IgmpRepr,
+};
+
+mod icmpv6;
+pub use self::icmpv6::{
+    DstUnreachable as Icmpv6DstUnreachable, Message as Icmpv6Message, Packet as Icmpv6Packet,
+    ParamProblem as Icmpv6ParamProblem, Repr as Icmpv6Repr, TimeExceeded as Icmpv6TimeExceeded,
+};
+
+mod ndisc;
+pub use self::ndisc::{
+    NeighborFlags as NdiscNeighborFlags, Repr as NdiscRepr, RouterFlags as NdiscRouterFlags,
+};
+
+mod ndiscoption;
+pub use self::ndiscoption::{
+    NdiscOption, PrefixInfoFlags as NdiscPrefixInfoFlags,
+    PrefixInformation as NdiscPrefixInformation, RedirectedHeader as NdiscRedirectedHeader,
+    Repr as NdiscOptionRepr, Type as NdiscOptionType,
 };
 
-// mod igmp;
-// pub use self::igmp::{IgmpVersion, Packet as IgmpPacket, Repr as IgmpRepr};
+mod mld;
+pub use self::mld::{
+    AddressRecord as MldAddressRecord, AddressRecordRepr as MldAddressRecordRepr,
+    RecordType as MldRecordType, Repr as MldRepr,
+};
 
 mod icmp;
 pub use self::icmp::Repr as IcmpRepr;
@@ -124,6 +187,20 @@ pub use self::icmp::Repr as IcmpRepr;
 mod udp;
 pub use self::udp::{Packet as UdpPacket, Repr as UdpRepr, HEADER_LEN as UDP_HEADER_LEN};
 
+pub(crate) mod dhcpv4;
+pub use self::dhcpv4::{
+    DhcpOption, DhcpOptionWriter, Flags as DhcpFlags, MessageType as DhcpMessageType,
+    OpCode as DhcpOpCode, Packet as DhcpPacket, Repr as DhcpRepr, CLIENT_PORT as DHCP_CLIENT_PORT,
+    MAX_DNS_SERVER_COUNT as DHCP_MAX_DNS_SERVER_COUNT, SERVER_PORT as DHCP_SERVER_PORT,
+};
+
+pub(crate) mod dns;
+pub use self::dns::{
+    Flags as DnsFlags, Opcode as DnsOpcode, Packet as DnsPacket, Question as DnsQuestion,
+    Rcode as DnsRcode, Record as DnsRecord, RecordData as DnsRecordData, Repr as DnsRepr,
+    Type as DnsQueryType,
+};
+
 mod tcp;
 pub use self::tcp::{
     Control as TcpControl, Packet as TcpPacket, Repr as TcpRepr, SeqNumber as TcpSeqNumber,
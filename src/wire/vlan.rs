@@ -0,0 +1,260 @@
+use byteorder::{ByteOrder, NetworkEndian};
+use core::fmt;
+
+use super::{Error, EthernetProtocol, Result};
+
+/// A read/write wrapper around an IEEE 802.1Q tag.
+///
+/// The tag protocol identifier (`0x8100` for a customer tag, `0x88a8` for an
+/// 802.1ad service tag) is the EtherType field of the enclosing frame or tag;
+/// this packet starts with the tag control information that follows it.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Packet<T: AsRef<[u8]>> {
+    buffer: T,
+}
+
+mod field {
+    use crate::wire::field::*;
+
+    pub const TCI: Field = 0..2;
+    pub const ETHERTYPE: Field = 2..4;
+    pub const PAYLOAD: Rest = 4..;
+}
+
+/// The length of the tag control information and the encapsulated EtherType.
+pub const HEADER_LEN: usize = field::PAYLOAD.start;
+
+/// The VLAN identifier reserved for priority-tagged frames, which belong to the
+/// native VLAN of the port.
+pub const VID_PRIORITY_TAGGED: u16 = 0x000;
+/// The VLAN identifier reserved for implementation use, never sent on the wire.
+pub const VID_RESERVED: u16 = 0xfff;
+
+impl<T: AsRef<[u8]>> Packet<T> {
+    /// Imbue a raw octet buffer with 802.1Q tag structure.
+    pub const fn new_unchecked(buffer: T) -> Packet<T> {
+        Packet { buffer }
+    }
+
+    /// Shorthand for a combination of [new_unchecked] and [check_len].
+    ///
+    /// [new_unchecked]: #method.new_unchecked
+    /// [check_len]: #method.check_len
+    pub fn new_checked(buffer: T) -> Result<Packet<T>> {
+        let packet = Self::new_unchecked(buffer);
+        packet.check_len()?;
+        Ok(packet)
+    }
+
+    /// Ensure that no accessor method will panic if called.
+    /// Returns `Err(Error)` if the buffer is too short.
+    pub fn check_len(&self) -> Result<()> {
+        let len = self.buffer.as_ref().len();
+        if len < HEADER_LEN {
+            Err(Error)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Consume the packet, returning the underlying buffer.
+    pub fn into_inner(self) -> T {
+        self.buffer
+    }
+
+    /// Return the priority code point field.
+    #[inline]
+    pub fn pcp(&self) -> u8 {
+        let data = self.buffer.as_ref();
+        data[field::TCI.start] >> 5
+    }
+
+    /// Return the drop eligible indicator field.
+    #[inline]
+    pub fn dei(&self) -> bool {
+        let data = self.buffer.as_ref();
+        data[field::TCI.start] & 0x10 != 0
+    }
+
+    /// Return the VLAN identifier field.
+    #[inline]
+    pub fn vid(&self) -> u16 {
+        let data = self.buffer.as_ref();
+        NetworkEndian::read_u16(&data[field::TCI]) & 0xfff
+    }
+
+    /// Return the EtherType of the encapsulated payload.
+    #[inline]
+    pub fn ethertype(&self) -> EthernetProtocol {
+        let data = self.buffer.as_ref();
+        let raw = NetworkEndian::read_u16(&data[field::ETHERTYPE]);
+        EthernetProtocol::from(raw)
+    }
+}
+
+impl<'a, T: AsRef<[u8]> + ?Sized> Packet<&'a T> {
+    /// Return a pointer to the payload.
+    #[inline]
+    pub fn payload(&self) -> &'a [u8] {
+        let data = self.buffer.as_ref();
+        &data[field::PAYLOAD]
+    }
+}
+
+impl<T: AsRef<[u8]> + AsMut<[u8]>> Packet<T> {
+    /// Set the priority code point field.
+    #[inline]
+    pub fn set_pcp(&mut self, value: u8) {
+        let data = self.buffer.as_mut();
+        data[field::TCI.start] = (data[field::TCI.start] & 0x1f) | (value << 5);
+    }
+
+    /// Set the drop eligible indicator field.
+    #[inline]
+    pub fn set_dei(&mut self, value: bool) {
+        let data = self.buffer.as_mut();
+        let raw = data[field::TCI.start] & !0x10;
+        data[field::TCI.start] = if value { raw | 0x10 } else { raw };
+    }
+
+    /// Set the VLAN identifier field.
+    #[inline]
+    pub fn set_vid(&mut self, value: u16) {
+        let data = self.buffer.as_mut();
+        let raw = NetworkEndian::read_u16(&data[field::TCI]);
+        NetworkEndian::write_u16(&mut data[field::TCI], (raw & !0xfff) | (value & 0xfff))
+    }
+
+    /// Set the EtherType of the encapsulated payload.
+    #[inline]
+    pub fn set_ethertype(&mut self, value: EthernetProtocol) {
+        let data = self.buffer.as_mut();
+        NetworkEndian::write_u16(&mut data[field::ETHERTYPE], value.into())
+    }
+
+    /// Return a mutable pointer to the payload.
+    #[inline]
+    pub fn payload_mut(&mut self) -> &mut [u8] {
+        let data = self.buffer.as_mut();
+        &mut data[field::PAYLOAD]
+    }
+}
+
+impl<T: AsRef<[u8]>> AsRef<[u8]> for Packet<T> {
+    fn as_ref(&self) -> &[u8] {
+        self.buffer.as_ref()
+    }
+}
+
+impl<T: AsRef<[u8]>> fmt::Display for Packet<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "802.1Q vid={} pcp={} dei={} type={}",
+            self.vid(),
+            self.pcp(),
+            self.dei() as u8,
+            self.ethertype()
+        )
+    }
+}
+
+use crate::wire::pretty_print::{PrettyIndent, PrettyPrint};
+
+impl<T: AsRef<[u8]>> PrettyPrint for Packet<T> {
+    fn pretty_print(
+        buffer: &dyn AsRef<[u8]>,
+        f: &mut fmt::Formatter,
+        indent: &mut PrettyIndent,
+    ) -> fmt::Result {
+        let packet = match Packet::new_checked(buffer) {
+            Err(err) => return write!(f, "{indent}({err})"),
+            Ok(packet) => packet,
+        };
+        write!(f, "{indent}{packet}")?;
+
+        match packet.ethertype() {
+            EthernetProtocol::Arp => {
+                indent.increase(f)?;
+                super::ArpPacket::<&[u8]>::pretty_print(&packet.payload(), f, indent)
+            }
+            EthernetProtocol::Ipv4 => {
+                indent.increase(f)?;
+                super::Ipv4Packet::<&[u8]>::pretty_print(&packet.payload(), f, indent)
+            }
+            EthernetProtocol::Ipv6 => {
+                indent.increase(f)?;
+                super::Ipv6Packet::<&[u8]>::pretty_print(&packet.payload(), f, indent)
+            }
+            EthernetProtocol::Dot1Q | EthernetProtocol::Dot1Ad => {
+                indent.increase(f)?;
+                Packet::<&[u8]>::pretty_print(&packet.payload(), f, indent)
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// A high-level representation of an IEEE 802.1Q tag.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Repr {
+    /// Priority code point, from 0 to 7.
+    pub pcp: u8,
+    /// Drop eligible indicator.
+    pub dei: bool,
+    /// VLAN identifier, from 0 to 4094.
+    pub vid: u16,
+    /// EtherType of the encapsulated payload.
+    pub ethertype: EthernetProtocol,
+}
+
+impl Repr {
+    /// Parse an 802.1Q tag and return a high-level representation.
+    pub fn parse<T: AsRef<[u8]> + ?Sized>(packet: &Packet<&T>) -> Result<Repr> {
+        packet.check_len()?;
+
+        if packet.vid() == VID_RESERVED {
+            return Err(Error);
+        }
+
+        Ok(Repr {
+            pcp: packet.pcp(),
+            dei: packet.dei(),
+            vid: packet.vid(),
+            ethertype: packet.ethertype(),
+        })
+    }
+
+    /// Return the length of a tag that will be emitted from this high-level representation.
+    pub const fn buffer_len(&self) -> usize {
+        HEADER_LEN
+    }
+
+    /// Emit a high-level representation into an 802.1Q tag.
+    ///
+    /// # Panics
+    /// This function panics if the priority or the VLAN identifier are out of range.
+    pub fn emit<T: AsRef<[u8]> + AsMut<[u8]>>(&self, packet: &mut Packet<T>) {
+        assert!(self.pcp < 8, "invalid 802.1Q priority {}", self.pcp);
+        assert!(
+            self.vid < VID_RESERVED,
+            "invalid VLAN identifier {}",
+            self.vid
+        );
+
+        packet.set_pcp(self.pcp);
+        packet.set_dei(self.dei);
+        packet.set_vid(self.vid);
+        packet.set_ethertype(self.ethertype);
+    }
+}
+
+impl fmt::Display for Repr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "802.1Q vid={} pcp={} dei={} type={}",
+            self.vid, self.pcp, self.dei as u8, self.ethertype
+        )
+    }
+}